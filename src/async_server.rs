@@ -0,0 +1,237 @@
+//! Async counterpart of [`ServerManager`](crate::server::ServerManager) built on tokio, for
+//! embedding the runner in an async application. Enabled with the `async` feature.
+use crate::server::{DEFAULT_STOP_COMMAND, STOP_TIMEOUT};
+use anyhow::{Context, Result};
+use log::*;
+use std::path::PathBuf;
+use std::process::{ExitStatus, Stdio};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader};
+use tokio::process::{ChildStdin, Command};
+use tokio::sync::{broadcast, mpsc, watch, Mutex};
+
+/// Lines kept for subscribers falling behind, older lines are dropped for them.
+const OUTPUT_CHANNEL_CAPACITY: usize = 1024;
+
+/// Owns the minecraft server process like [`ServerManager`](crate::server::ServerManager), with
+/// async methods. The output isn't echoed, it's published line by line to the receivers of
+/// [`AsyncServerManager::subscribe_output`].
+///
+/// Has to be used from within a tokio runtime.
+pub struct AsyncServerManager {
+	java: PathBuf,
+	args: Vec<String>,
+	working_directory: PathBuf,
+	stop_commands: Vec<String>,
+	process: Mutex<Option<Process>>,
+	output: broadcast::Sender<String>,
+}
+
+/// The launched process. The child itself is owned by the task waiting on it, so the process can
+/// be written to and killed while someone waits for it to exit.
+struct Process {
+	pid: Option<u32>,
+	stdin: ChildStdin,
+	status: watch::Receiver<Option<ExitStatus>>,
+	kill: mpsc::Sender<()>,
+}
+
+impl AsyncServerManager {
+	pub fn new(java: PathBuf, args: Vec<String>, working_directory: PathBuf) -> Self {
+		AsyncServerManager {
+			java,
+			args,
+			working_directory,
+			stop_commands: vec![String::from(DEFAULT_STOP_COMMAND)],
+			process: Mutex::new(None),
+			output: broadcast::channel(OUTPUT_CHANNEL_CAPACITY).0,
+		}
+	}
+
+	/// Commands written to the server, in order, to stop it gracefully. `stop` by default.
+	pub fn with_stop_commands(mut self, stop_commands: Vec<String>) -> Self {
+		self.stop_commands = stop_commands;
+		self
+	}
+
+	/// Receives the lines the server prints to stdout and stderr from now on.
+	pub fn subscribe_output(&self) -> broadcast::Receiver<String> {
+		self.output.subscribe()
+	}
+
+	/// Launches the server and returns its PID.
+	pub async fn spawn(&self) -> Result<u32> {
+		let mut process = self.process.lock().await;
+		if let Some(process) = &*process {
+			if process.status.borrow().is_none() {
+				anyhow::bail!("The server is already running");
+			}
+		}
+
+		let mut child = Command::new(&self.java)
+			.args(&self.args)
+			.current_dir(&self.working_directory)
+			.stdin(Stdio::piped())
+			.stdout(Stdio::piped())
+			.stderr(Stdio::piped())
+			.kill_on_drop(true)
+			.spawn()
+			.with_context(|| format!("Failed to launch \"{}\"", self.java.display()))?;
+		let pid = child.id();
+		let stdin = child
+			.stdin
+			.take()
+			.ok_or_else(|| anyhow::anyhow!("Server stdin is not available"))?;
+		if let Some(stdout) = child.stdout.take() {
+			tokio::spawn(publish_output(stdout, self.output.clone()));
+		}
+		if let Some(stderr) = child.stderr.take() {
+			tokio::spawn(publish_output(stderr, self.output.clone()));
+		}
+
+		let (status_sender, status) = watch::channel(None);
+		let (kill, mut kill_requests) = mpsc::channel(1);
+		tokio::spawn(async move {
+			let status = loop {
+				tokio::select! {
+					status = child.wait() => break status,
+					Some(()) = kill_requests.recv() => {
+						if let Err(e) = child.start_kill() {
+							warn!("Failed to kill the server: {:?}.", e);
+						}
+					}
+				}
+			};
+			match status {
+				Ok(status) => {
+					let _ = status_sender.send(Some(status));
+				}
+				// Dropping the sender fails the waiters
+				Err(e) => error!("Failed to wait for the server: {:?}.", e),
+			}
+		});
+
+		*process = Some(Process {
+			pid,
+			stdin,
+			status,
+			kill,
+		});
+		pid.ok_or_else(|| anyhow::anyhow!("The server exited right after being launched"))
+	}
+
+	/// PID of the current server process.
+	pub async fn pid(&self) -> Option<u32> {
+		let process = self.process.lock().await;
+		process
+			.as_ref()
+			.filter(|process| process.status.borrow().is_none())
+			.and_then(|process| process.pid)
+	}
+
+	/// Waits for the server to exit.
+	pub async fn wait(&self) -> Result<ExitStatus> {
+		let mut status = match &*self.process.lock().await {
+			Some(process) => process.status.clone(),
+			None => anyhow::bail!("The server is not launched"),
+		};
+		loop {
+			if let Some(status) = *status.borrow() {
+				return Ok(status);
+			}
+			status
+				.changed()
+				.await
+				.map_err(|_| anyhow::anyhow!("Lost track of the server process"))?;
+		}
+	}
+
+	/// Writes a console command to the server.
+	pub async fn send_command(&self, command: &str) -> Result<()> {
+		let mut process = self.process.lock().await;
+		let process = process
+			.as_mut()
+			.ok_or_else(|| anyhow::anyhow!("The server is not launched"))?;
+		process
+			.stdin
+			.write_all(format!("{}\n", command).as_bytes())
+			.await?;
+		process.stdin.flush().await?;
+		Ok(())
+	}
+
+	/// Stops the server gracefully with the stop commands, killing it when it doesn't exit within
+	/// [`STOP_TIMEOUT`].
+	pub async fn stop(&self) -> Result<ExitStatus> {
+		let kill = match &*self.process.lock().await {
+			Some(process) => process.kill.clone(),
+			None => anyhow::bail!("The server is not launched"),
+		};
+
+		info!("Stopping the server.");
+		for command in &self.stop_commands {
+			if let Err(e) = self.send_command(command).await {
+				warn!("Failed to send the stop command \"{}\": {:?}.", command, e);
+			}
+		}
+		match tokio::time::timeout(STOP_TIMEOUT, self.wait()).await {
+			Ok(status) => status,
+			Err(_) => {
+				warn!(
+					"Server didn't stop within {}s, killing it.",
+					STOP_TIMEOUT.as_secs()
+				);
+				let _ = kill.send(()).await;
+				self.wait().await
+			}
+		}
+	}
+
+	/// Gracefully stops the server and launches it again. Returns the PID of the new process.
+	pub async fn restart(&self) -> Result<u32> {
+		if self.pid().await.is_some() {
+			self.stop().await?;
+		}
+		self.spawn().await
+	}
+}
+
+async fn publish_output(stream: impl AsyncRead + Unpin, output: broadcast::Sender<String>) {
+	let mut lines = BufReader::new(stream).lines();
+	loop {
+		match lines.next_line().await {
+			Ok(Some(line)) => {
+				// No subscribers is fine
+				let _ = output.send(line);
+			}
+			Ok(None) => return,
+			Err(e) => {
+				debug!("Failed to read server output: {:?}", e);
+				return;
+			}
+		}
+	}
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+	use super::*;
+
+	#[tokio::test]
+	async fn commands_and_output() {
+		let server = AsyncServerManager::new(
+			PathBuf::from("sh"),
+			vec![
+				String::from("-c"),
+				String::from("echo ready; read command; echo \"got $command\""),
+			],
+			std::env::temp_dir(),
+		);
+		let mut output = server.subscribe_output();
+		server.spawn().await.unwrap();
+		assert_eq!(output.recv().await.unwrap(), "ready");
+		server.send_command("stop").await.unwrap();
+		assert_eq!(output.recv().await.unwrap(), "got stop");
+		assert!(server.wait().await.unwrap().success());
+		assert_eq!(server.pid().await, None);
+	}
+}
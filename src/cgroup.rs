@@ -0,0 +1,135 @@
+//! Resource limits of the cgroup the runner is in, e.g. set by Docker or Kubernetes. The JVM
+//! launched by the runner inherits them, so they matter more than the host's resources.
+#[cfg(target_os = "linux")]
+use std::path::PathBuf;
+use sysinfo::{System, SystemExt};
+
+#[cfg(target_os = "linux")]
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Memory the server can use in bytes: the cgroup limit when there is one, otherwise the host
+/// RAM.
+pub fn memory_ceiling() -> u64 {
+	let host_total = host_memory();
+	memory_limit().map_or(host_total, |limit| limit.min(host_total))
+}
+
+/// Total RAM of the host in bytes.
+pub fn host_memory() -> u64 {
+	let mut system = System::new();
+	system.refresh_memory();
+	// sysinfo reports KiB
+	system.total_memory() * 1024
+}
+
+/// Memory limit of the cgroup in bytes. `None` when there is no limit or no cgroup.
+#[cfg(target_os = "linux")]
+pub fn memory_limit() -> Option<u64> {
+	read_v2("memory.max")
+		.or_else(|| read_v1("memory", "memory.limit_in_bytes"))
+		.and_then(|limit| parse_limit(&limit))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn memory_limit() -> Option<u64> {
+	None
+}
+
+/// Processors the cgroup CPU quota allows, rounded up. `None` when there is no quota.
+#[cfg(target_os = "linux")]
+pub fn cpu_limit() -> Option<u32> {
+	if let Some(cpu_max) = read_v2("cpu.max") {
+		let mut parts = cpu_max.split_whitespace();
+		let quota = parts.next().and_then(parse_limit)?;
+		let period = parts.next()?.parse().ok()?;
+		return processors(quota, period);
+	}
+	let quota: i64 = read_v1("cpu", "cpu.cfs_quota_us")?.trim().parse().ok()?;
+	let period = read_v1("cpu", "cpu.cfs_period_us")?.trim().parse().ok()?;
+	// -1 is no quota
+	if quota <= 0 {
+		return None;
+	}
+	processors(quota as u64, period)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn cpu_limit() -> Option<u32> {
+	None
+}
+
+#[cfg(any(target_os = "linux", test))]
+fn processors(quota: u64, period: u64) -> Option<u32> {
+	if period == 0 {
+		return None;
+	}
+	Some(((quota + period - 1) / period).max(1) as u32)
+}
+
+/// Parses a limit file, which holds a number or `max` for no limit.
+#[cfg(any(target_os = "linux", test))]
+fn parse_limit(limit: &str) -> Option<u64> {
+	match limit.trim() {
+		"max" => None,
+		limit => limit.parse().ok(),
+	}
+}
+
+/// Reads a file of the cgroup v2 the runner is in. Inside a container the cgroup usually is
+/// mounted as the root, so that is tried as well.
+#[cfg(target_os = "linux")]
+fn read_v2(file: &str) -> Option<String> {
+	let path = own_cgroup(|line| line.strip_prefix("0::").map(ToOwned::to_owned));
+	candidates(PathBuf::from(CGROUP_ROOT), path, file)
+}
+
+/// Reads a file of the given cgroup v1 controller.
+#[cfg(target_os = "linux")]
+fn read_v1(controller: &str, file: &str) -> Option<String> {
+	let path = own_cgroup(|line| {
+		let mut parts = line.splitn(3, ':');
+		let controllers = parts.nth(1)?;
+		let path = parts.next()?;
+		controllers
+			.split(',')
+			.any(|c| c == controller)
+			.then(|| path.to_string())
+	});
+	candidates(PathBuf::from(CGROUP_ROOT).join(controller), path, file)
+}
+
+#[cfg(target_os = "linux")]
+fn own_cgroup(find: impl Fn(&str) -> Option<String>) -> Option<String> {
+	std::fs::read_to_string("/proc/self/cgroup")
+		.ok()?
+		.lines()
+		.find_map(find)
+}
+
+#[cfg(target_os = "linux")]
+fn candidates(mount: PathBuf, path: Option<String>, file: &str) -> Option<String> {
+	let own = path.map(|path| mount.join(path.trim_start_matches('/')).join(file));
+	own.into_iter()
+		.chain(std::iter::once(mount.join(file)))
+		.find_map(|path| std::fs::read_to_string(path).ok())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn limits() {
+		assert_eq!(parse_limit("max\n"), None);
+		assert_eq!(parse_limit("2147483648\n"), Some(2147483648));
+		assert_eq!(parse_limit(""), None);
+	}
+
+	#[test]
+	fn processors_are_rounded_up() {
+		assert_eq!(processors(200000, 100000), Some(2));
+		assert_eq!(processors(150000, 100000), Some(2));
+		assert_eq!(processors(10000, 100000), Some(1));
+		assert_eq!(processors(100000, 0), None);
+	}
+}
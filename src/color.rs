@@ -0,0 +1,64 @@
+use std::str::FromStr;
+
+/// Whether ANSI colors are emitted, `auto` by default.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+	Auto,
+	Always,
+	Never,
+}
+
+impl FromStr for ColorChoice {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"auto" => Ok(ColorChoice::Auto),
+			"always" => Ok(ColorChoice::Always),
+			"never" => Ok(ColorChoice::Never),
+			_ => Err(format!(
+				"Unknown color choice \"{}\", expected auto, always or never",
+				s
+			)),
+		}
+	}
+}
+
+impl ColorChoice {
+	/// With `auto`, colors are used when the log output (stderr) is a terminal and `NO_COLOR`
+	/// isn't set.
+	pub fn enabled(self) -> bool {
+		match self {
+			ColorChoice::Always => true,
+			ColorChoice::Never => false,
+			ColorChoice::Auto => {
+				std::env::var_os("NO_COLOR").is_none() && atty::is(atty::Stream::Stderr)
+			}
+		}
+	}
+
+	/// Wraps the text in the given SGR code, e.g. `1` for bold, when colors are enabled.
+	pub fn paint(self, text: &str, code: &str) -> String {
+		if self.enabled() {
+			format!("\x1b[{}m{}\x1b[0m", code, text)
+		} else {
+			text.to_string()
+		}
+	}
+}
+
+pub const BOLD: &str = "1";
+pub const RED: &str = "31";
+pub const GREEN: &str = "32";
+pub const YELLOW: &str = "33";
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn paint_only_with_colors() {
+		assert_eq!(ColorChoice::Always.paint("1.", BOLD), "\x1b[1m1.\x1b[0m");
+		assert_eq!(ColorChoice::Never.paint("1.", BOLD), "1.");
+	}
+}
@@ -0,0 +1,523 @@
+use crate::error::RunnerError;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub const CONFIG_FILENAME: &str = "minecraft_runner_config.ron";
+
+/// Settings stored in the working directory. Top level settings are the defaults, which can be
+/// overridden by a named profile (selected with `--profile`).
+///
+/// Maps and nested structs have to stay at the end, otherwise the config can't be written as toml.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+#[serde(default)]
+pub struct MinecraftRunnerConfig {
+	pub jar_preference: PathBuf,
+	pub min: Option<String>,
+	pub max: Option<String>,
+	pub port: Option<u16>,
+	pub jvm_args: Vec<String>,
+	/// Java found by the last run, checked before probing the usual places.
+	pub java_path_cache: Option<PathBuf>,
+	/// Console commands sent once the server finishes loading.
+	pub startup_commands: Vec<String>,
+	/// Console commands stopping the server gracefully, `stop` when empty.
+	pub stop_commands: Vec<String>,
+	/// Regex matching the line printed once the server finishes loading, for servers that don't
+	/// print the vanilla `Done (12.345s)!` line.
+	pub done_line_pattern: Option<String>,
+	/// Extra arguments forwarded verbatim to AutoIpMinecraft.jar, after `server.properties`.
+	pub auto_ip_args: Vec<String>,
+	/// Launch snapshot and pre-release jars without asking.
+	pub allow_snapshots: bool,
+	/// Regex recognizing a player joining, with a `name` group, for servers that don't print
+	/// the vanilla `<name> joined the game` line.
+	pub player_join_pattern: Option<String>,
+	/// Regex recognizing a player leaving, with a `name` group.
+	pub player_leave_pattern: Option<String>,
+	/// Expected SHA-256 of the launched jar (hex). The launch is refused when it doesn't match.
+	pub jar_sha256: Option<String>,
+	/// Helper jars run in order before the server is launched, after AutoIpMinecraft.jar.
+	pub pre_launch_jars: Vec<PreLaunchJar>,
+	/// Memory for specific jars, keyed by the jar file name. Takes precedence over the memory set
+	/// above, but not over the environment or the flags.
+	pub jar_memory: HashMap<String, MemorySpec>,
+	/// Messages announcing a restart with --announce-restart-reason, keyed by the reason
+	/// (scheduled, health_check, manual or crash). Reasons left out use a default message.
+	pub restart_messages: HashMap<String, String>,
+	pub profiles: HashMap<String, ProfileConfig>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct MemorySpec {
+	pub min: Option<String>,
+	pub max: Option<String>,
+}
+
+/// Jar run with `java -jar <jar> <args...>` before the server, e.g. to sync configs or back up the
+/// world.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PreLaunchJar {
+	pub jar: PathBuf,
+	#[serde(default)]
+	pub args: Vec<String>,
+	#[serde(default)]
+	pub on_failure: FailurePolicy,
+}
+
+/// What to do when a pre-launch jar can't be run or exits with an error.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FailurePolicy {
+	Ignore,
+	Warn,
+	Abort,
+}
+
+impl Default for FailurePolicy {
+	fn default() -> Self {
+		FailurePolicy::Warn
+	}
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ProfileConfig {
+	pub jar_preference: Option<PathBuf>,
+	pub min: Option<String>,
+	pub max: Option<String>,
+	pub port: Option<u16>,
+	pub jvm_args: Option<Vec<String>>,
+}
+
+impl MinecraftRunnerConfig {
+	/// Merges the settings of the given profile on top of the top level defaults.
+	pub fn profile_settings(&self, profile: Option<&str>) -> Result<ProfileConfig> {
+		let defaults = ProfileConfig {
+			jar_preference: Some(self.jar_preference.clone()).filter(|p| !p.as_os_str().is_empty()),
+			min: self.min.clone(),
+			max: self.max.clone(),
+			port: self.port,
+			jvm_args: Some(self.jvm_args.clone()),
+		};
+
+		let name = match profile {
+			Some(v) => v,
+			None => return Ok(defaults),
+		};
+
+		let profile = match self.profiles.get(name) {
+			Some(v) => v.clone(),
+			None => {
+				let mut available: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+				available.sort_unstable();
+				if available.is_empty() {
+					anyhow::bail!("Profile \"{}\" not found. No profiles are defined.", name);
+				}
+				anyhow::bail!(
+					"Profile \"{}\" not found. Available profiles: {}.",
+					name,
+					available.join(", ")
+				);
+			}
+		};
+
+		Ok(ProfileConfig {
+			jar_preference: profile.jar_preference.or(defaults.jar_preference),
+			min: profile.min.or(defaults.min),
+			max: profile.max.or(defaults.max),
+			port: profile.port.or(defaults.port),
+			jvm_args: profile.jvm_args.or(defaults.jvm_args),
+		})
+	}
+}
+
+pub fn is_not_found(e: &anyhow::Error) -> bool {
+	e.downcast_ref::<std::io::Error>()
+		.map(|io_err| io_err.kind() == std::io::ErrorKind::NotFound)
+		.unwrap_or_default()
+}
+
+/// File formats the config can be stored in, picked by the file extension.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConfigFormat {
+	Ron,
+	Toml,
+}
+
+impl ConfigFormat {
+	pub fn from_path(path: &Path) -> Result<Self> {
+		match path.extension().and_then(|ext| ext.to_str()) {
+			Some("ron") => Ok(ConfigFormat::Ron),
+			Some("toml") => Ok(ConfigFormat::Toml),
+			_ => anyhow::bail!(
+				"Unsupported config format of {:?}, expected a .ron or a .toml file.",
+				path
+			),
+		}
+	}
+
+	/// Tells the format apart by the content, for configs without a file name. RON configs start
+	/// with the `(` of the struct (or its name, or an `#![enable(...)]` attribute).
+	pub fn detect(str: &str) -> Self {
+		let str = str.trim_start();
+		if str.starts_with('(')
+			|| str.starts_with("#![")
+			|| str.starts_with("MinecraftRunnerConfig")
+		{
+			ConfigFormat::Ron
+		} else {
+			ConfigFormat::Toml
+		}
+	}
+
+	pub fn parse(self, str: &str) -> Result<MinecraftRunnerConfig> {
+		let config: MinecraftRunnerConfig = match self {
+			ConfigFormat::Ron => ron::from_str(str)?,
+			ConfigFormat::Toml => toml::from_str(str)?,
+		};
+		Ok(config)
+	}
+
+	pub fn serialize(self, config: &MinecraftRunnerConfig) -> Result<String> {
+		let str = match self {
+			ConfigFormat::Ron => ron::to_string(config)?,
+			ConfigFormat::Toml => toml::to_string(config)?,
+		};
+		Ok(str)
+	}
+}
+
+/// Starter config written by `config init`. Serde can't write comments, so it's kept by hand and
+/// has to list every field of [`MinecraftRunnerConfig`] with its default value.
+pub const TEMPLATE: &str = r#"// minecraft_runner config. Every field is optional, the values below are the defaults.
+// Environment variables (MINECRAFT_RUNNER_*) and command line flags take precedence.
+(
+	// Jar to launch, remembered after choosing one when there are several. Empty to ask.
+	jar_preference: "",
+	// Minimum and maximum JVM heap size, e.g. Some("4GiB"), or a percentage of the memory available
+	// (the cgroup limit in a container), e.g. Some("75%"). 1GiB and 16GiB when None.
+	min: None,
+	max: None,
+	// Port overriding server-port of server.properties, e.g. Some(25565).
+	port: None,
+	// Extra JVM arguments, e.g. ["-XX:+UseG1GC"].
+	jvm_args: [],
+	// Java found by the last run, filled in by the runner.
+	java_path_cache: None,
+	// Console commands sent once the server finishes loading, e.g. ["gamerule keepInventory true"].
+	startup_commands: [],
+	// Console commands stopping the server gracefully. ["stop"] when empty.
+	stop_commands: [],
+	// Regex matching the line printed once the server finishes loading, for servers that don't
+	// print the vanilla "Done (12.345s)!" line.
+	done_line_pattern: None,
+	// Extra arguments forwarded verbatim to AutoIpMinecraft.jar, after server.properties.
+	auto_ip_args: [],
+	// Launch snapshot and pre-release jars without asking.
+	allow_snapshots: false,
+	// Regexes recognizing a player joining and leaving, with a (?P<name>...) group, for servers
+	// that don't print the vanilla "Steve joined the game" and "Steve left the game" lines.
+	player_join_pattern: None,
+	player_leave_pattern: None,
+	// Expected SHA-256 of the launched jar, printed by the hash subcommand, e.g. Some("9a1b...").
+	// The launch is refused when it doesn't match.
+	jar_sha256: None,
+	// Helper jars run in order before the server, after AutoIpMinecraft.jar. on_failure is
+	// ignore, warn (the default) or abort, e.g.
+	// [(jar: "backup.jar", args: ["--upload"], on_failure: abort)]
+	pre_launch_jars: [],
+	// Memory for specific jars, keyed by the jar file name, e.g.
+	// { "modpack.jar": (min: Some("4GiB"), max: Some("12GiB")) }
+	jar_memory: {},
+	// Messages announcing a restart with --announce-restart-reason, keyed by the reason
+	// (scheduled, health_check, manual or crash), e.g. { "crash": "Back in a minute!" }
+	restart_messages: {},
+	// Named sets of settings selected with --profile, overriding the ones above, e.g.
+	// { "creative": (jar_preference: Some("paper.jar"), min: None, max: Some("8GiB"), port: None, jvm_args: None) }
+	profiles: {},
+)
+"#;
+
+/// Writes [`TEMPLATE`] to `config_path`, refusing to replace an existing config without `force`.
+pub fn write_template(config_path: &Path, force: bool) -> Result<()> {
+	if ConfigFormat::from_path(config_path)? != ConfigFormat::Ron {
+		anyhow::bail!("The template is RON, {:?} isn't a .ron file.", config_path);
+	}
+	if config_path.exists() && !force {
+		anyhow::bail!(
+			"{:?} already exists. Pass --force to overwrite it.",
+			config_path
+		);
+	}
+	std::fs::write(config_path, TEMPLATE).with_context(|| format!("Path: {:?}", config_path))
+}
+
+/// Path of the config used when `--config` isn't given.
+pub fn default_config_path(working_directory: &Path) -> PathBuf {
+	working_directory.join(CONFIG_FILENAME)
+}
+
+pub fn read_config(config_path: &Path) -> Result<MinecraftRunnerConfig> {
+	let format = ConfigFormat::from_path(config_path)?;
+	let str =
+		std::fs::read_to_string(config_path).with_context(|| format!("Path: {:?}", config_path))?;
+	parse_config(format, &str, config_path)
+}
+
+/// Reads the whole standard input as the config, for configs generated on the fly that shouldn't
+/// be stored on the disk.
+pub fn read_config_stdin() -> Result<MinecraftRunnerConfig> {
+	use std::io::Read;
+
+	let mut str = String::new();
+	std::io::stdin()
+		.read_to_string(&mut str)
+		.context("Failed to read the config from stdin")?;
+	parse_config(ConfigFormat::detect(&str), &str, Path::new("<stdin>"))
+}
+
+fn parse_config(format: ConfigFormat, str: &str, path: &Path) -> Result<MinecraftRunnerConfig> {
+	format.parse(str).map_err(|e| {
+		RunnerError::ConfigParse {
+			path: path.to_path_buf(),
+			source: e.into(),
+		}
+		.into()
+	})
+}
+
+/// Reads the config, falling back to the defaults when it doesn't exist. A broken config fails with
+/// [`RunnerError::ConfigParse`] instead of being silently replaced.
+pub fn load_config(config_path: &Path) -> Result<MinecraftRunnerConfig> {
+	match read_config(config_path) {
+		Ok(v) => Ok(v),
+		Err(e) if is_not_found(&e) => Ok(MinecraftRunnerConfig::default()),
+		Err(e) => Err(e),
+	}
+}
+
+/// Reads the config (or starts with the default one if it doesn't exist), applies `update` and
+/// writes it back.
+pub fn update_config<F>(config_path: &Path, update: F) -> Result<()>
+where
+	F: FnOnce(&mut MinecraftRunnerConfig),
+{
+	let mut config = match read_config(config_path) {
+		Ok(v) => v,
+		Err(e) if is_not_found(&e) => MinecraftRunnerConfig::default(),
+		Err(e) => return Err(e),
+	};
+	update(&mut config);
+	write_config(&config, config_path)
+}
+
+/// Remembers the chosen jar. With a profile selected, the preference is stored in that profile.
+pub fn save_jar_preference(jar: &Path, config_path: &Path, profile: Option<&str>) -> Result<()> {
+	let jar_preference = jar
+		.file_name()
+		.map(PathBuf::from)
+		.ok_or(anyhow::anyhow!("Failed to get the filename of {:?}.", jar))?;
+	update_config(config_path, |config| match profile {
+		Some(profile) => {
+			config
+				.profiles
+				.entry(profile.to_string())
+				.or_default()
+				.jar_preference = Some(jar_preference)
+		}
+		None => config.jar_preference = jar_preference,
+	})
+}
+
+pub fn save_java_path_cache(java: &Path, config_path: &Path) -> Result<()> {
+	update_config(config_path, |config| {
+		config.java_path_cache = Some(java.to_path_buf())
+	})
+}
+
+pub fn write_config(config: &MinecraftRunnerConfig, config_path: &Path) -> Result<()> {
+	let str = ConfigFormat::from_path(config_path)?.serialize(config)?;
+	std::fs::write(config_path, str).with_context(|| format!("Path: {:?}", config_path))?;
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn config_with_profiles() -> MinecraftRunnerConfig {
+		let mut profiles = HashMap::new();
+		profiles.insert(
+			String::from("smp"),
+			ProfileConfig {
+				max: Some(String::from("12GiB")),
+				port: Some(25566),
+				..Default::default()
+			},
+		);
+		profiles.insert(String::from("creative"), ProfileConfig::default());
+		MinecraftRunnerConfig {
+			jar_preference: PathBuf::from("server.jar"),
+			min: Some(String::from("2GiB")),
+			max: Some(String::from("4GiB")),
+			jvm_args: vec![String::from("-Dfoo=bar")],
+			profiles,
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn profile_overrides_defaults() {
+		let settings = config_with_profiles()
+			.profile_settings(Some("smp"))
+			.unwrap();
+		assert_eq!(
+			settings,
+			ProfileConfig {
+				jar_preference: Some(PathBuf::from("server.jar")),
+				min: Some(String::from("2GiB")),
+				max: Some(String::from("12GiB")),
+				port: Some(25566),
+				jvm_args: Some(vec![String::from("-Dfoo=bar")]),
+			}
+		);
+	}
+
+	#[test]
+	fn no_profile_uses_defaults() {
+		let settings = config_with_profiles().profile_settings(None).unwrap();
+		assert_eq!(settings.max, Some(String::from("4GiB")));
+		assert_eq!(settings.port, None);
+	}
+
+	#[test]
+	fn missing_profile_lists_available_ones() {
+		let err = config_with_profiles()
+			.profile_settings(Some("nope"))
+			.unwrap_err();
+		assert_eq!(
+			err.to_string(),
+			"Profile \"nope\" not found. Available profiles: creative, smp."
+		);
+	}
+
+	#[test]
+	fn config_without_new_fields_parses() {
+		let config: MinecraftRunnerConfig =
+			ron::from_str("(jar_preference:\"server.jar\")").unwrap();
+		assert_eq!(config.jar_preference, PathBuf::from("server.jar"));
+		assert!(config.profiles.is_empty());
+	}
+
+	#[test]
+	fn round_trip_ron() {
+		let config = config_with_profiles();
+		let str = ConfigFormat::Ron.serialize(&config).unwrap();
+		assert_eq!(ConfigFormat::Ron.parse(&str).unwrap(), config);
+	}
+
+	#[test]
+	fn round_trip_toml() {
+		let config = config_with_profiles();
+		let str = ConfigFormat::Toml.serialize(&config).unwrap();
+		assert_eq!(ConfigFormat::Toml.parse(&str).unwrap(), config);
+	}
+
+	#[test]
+	fn broken_config_fails_to_load() {
+		let directory = std::env::temp_dir();
+		let missing = directory.join(format!(
+			"minecraft_runner_missing_{}.ron",
+			std::process::id()
+		));
+		assert_eq!(
+			load_config(&missing).unwrap(),
+			MinecraftRunnerConfig::default()
+		);
+
+		let broken = directory.join(format!(
+			"minecraft_runner_broken_{}.ron",
+			std::process::id()
+		));
+		std::fs::write(&broken, "(jar_preference: ").unwrap();
+		let err = load_config(&broken).unwrap_err();
+		std::fs::remove_file(&broken).unwrap();
+		assert_eq!(
+			crate::error::exit_code(&err),
+			crate::error::exit_codes::CONFIG_PARSE
+		);
+	}
+
+	#[test]
+	fn format_from_extension() {
+		assert_eq!(
+			ConfigFormat::from_path(Path::new("a/config.ron")).unwrap(),
+			ConfigFormat::Ron
+		);
+		assert_eq!(
+			ConfigFormat::from_path(Path::new("config.toml")).unwrap(),
+			ConfigFormat::Toml
+		);
+		assert!(ConfigFormat::from_path(Path::new("config.json")).is_err());
+	}
+
+	#[test]
+	fn template_lists_every_field_with_its_default() {
+		let config: MinecraftRunnerConfig = ron::from_str(TEMPLATE).unwrap();
+		assert_eq!(config, MinecraftRunnerConfig::default());
+
+		let fields = serde_json::to_value(MinecraftRunnerConfig::default()).unwrap();
+		for field in fields.as_object().unwrap().keys() {
+			assert!(
+				TEMPLATE.contains(&format!("\t{}: ", field)),
+				"{} is missing from the template",
+				field
+			);
+		}
+	}
+
+	#[test]
+	fn pre_launch_jars_default_to_warning() {
+		let config: MinecraftRunnerConfig = ron::from_str(
+			r#"(pre_launch_jars: [(jar: "sync.jar"), (jar: "backup.jar", args: ["--upload"], on_failure: abort)])"#,
+		)
+		.unwrap();
+		assert_eq!(
+			config.pre_launch_jars,
+			vec![
+				PreLaunchJar {
+					jar: PathBuf::from("sync.jar"),
+					args: Vec::new(),
+					on_failure: FailurePolicy::Warn,
+				},
+				PreLaunchJar {
+					jar: PathBuf::from("backup.jar"),
+					args: vec![String::from("--upload")],
+					on_failure: FailurePolicy::Abort,
+				},
+			]
+		);
+	}
+
+	#[test]
+	fn format_from_content() {
+		assert_eq!(
+			ConfigFormat::detect("\n  (jar_preference:\"server.jar\")"),
+			ConfigFormat::Ron
+		);
+		assert_eq!(
+			ConfigFormat::detect("MinecraftRunnerConfig(max: Some(\"4GiB\"))"),
+			ConfigFormat::Ron
+		);
+		assert_eq!(
+			ConfigFormat::detect("jar_preference = \"server.jar\"\n"),
+			ConfigFormat::Toml
+		);
+		let config = config_with_profiles();
+		let str = ConfigFormat::Toml.serialize(&config).unwrap();
+		assert_eq!(ConfigFormat::detect(&str).parse(&str).unwrap(), config);
+	}
+}
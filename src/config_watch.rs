@@ -0,0 +1,57 @@
+use crate::config::{self, MinecraftRunnerConfig};
+use anyhow::Result;
+use log::*;
+use notify::{DebouncedEvent, RecursiveMode, Watcher};
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Editors often write a file in several steps, changes closer together than this are merged.
+const DEBOUNCE: Duration = Duration::from_secs(1);
+
+/// Calls `on_change` with the config every time its file changes.
+///
+/// The directory is watched instead of the file, so configs replaced by an editor (written to a
+/// temporary file and renamed) keep being followed. A config that can't be read is skipped,
+/// leaving the last good one in effect.
+pub fn watch<F>(config_path: &Path, mut on_change: F) -> Result<()>
+where
+	F: FnMut(MinecraftRunnerConfig) + Send + 'static,
+{
+	let directory = config_path
+		.parent()
+		.filter(|parent| !parent.as_os_str().is_empty())
+		.unwrap_or_else(|| Path::new("."));
+	let (sender, receiver) = mpsc::channel();
+	let mut watcher = notify::watcher(sender, DEBOUNCE)?;
+	watcher.watch(directory, RecursiveMode::NonRecursive)?;
+
+	let config_path = config_path.to_path_buf();
+	std::thread::spawn(move || {
+		// Dropping the watcher stops the events
+		let _watcher = watcher;
+		for event in receiver {
+			let path = match event {
+				DebouncedEvent::Create(path)
+				| DebouncedEvent::Write(path)
+				| DebouncedEvent::Rename(_, path) => path,
+				DebouncedEvent::Error(e, _) => {
+					warn!("Failed to watch the config: {:?}.", e);
+					continue;
+				}
+				_ => continue,
+			};
+			if path.file_name() != config_path.file_name() {
+				continue;
+			}
+			match config::read_config(&config_path) {
+				Ok(config) => on_change(config),
+				Err(e) => warn!(
+					"Ignoring the changed config, keeping the last one: {:?}.",
+					e
+				),
+			}
+		}
+	});
+	Ok(())
+}
@@ -0,0 +1,142 @@
+use crate::server::ServerManager;
+use log::*;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::path::Path;
+
+pub const HISTORY_FILENAME: &str = "minecraft_runner_history.txt";
+
+const PROMPT: &str = "> ";
+
+/// Commands offered by tab completion.
+const COMMON_COMMANDS: &[&str] = &[
+	"ban",
+	"ban-ip",
+	"banlist",
+	"deop",
+	"difficulty",
+	"gamemode",
+	"gamerule",
+	"give",
+	"help",
+	"kick",
+	"kill",
+	"list",
+	"op",
+	"pardon",
+	"pardon-ip",
+	"reload",
+	"save-all",
+	"save-off",
+	"save-on",
+	"say",
+	"seed",
+	"stop",
+	"tell",
+	"time",
+	"tp",
+	"weather",
+	"whitelist",
+];
+
+/// Interactive replacement for [`crate::server::forward_console`]: shows a prompt with history
+/// and tab completion, and forwards the entered lines to the server. Ctrl-D stops the server.
+pub fn run_console(minecraft_server: &ServerManager, history_path: &Path) {
+	let mut editor = Editor::<ConsoleHelper>::new();
+	editor.set_helper(Some(ConsoleHelper));
+	if let Err(e) = editor.load_history(history_path) {
+		debug!("Failed to load the console history: {:?}", e);
+	}
+
+	loop {
+		match editor.readline(PROMPT) {
+			Ok(line) => {
+				let line = line.trim();
+				if line.is_empty() {
+					continue;
+				}
+				editor.add_history_entry(line);
+				if !minecraft_server.is_running() {
+					warn!("Server is not running, ignoring \"{}\".", line);
+					continue;
+				}
+				if let Err(e) = minecraft_server.send_command(line) {
+					warn!("Failed to forward \"{}\" to the server: {:?}.", line, e);
+				}
+			}
+			Err(ReadlineError::Interrupted) => {
+				info!("Press Ctrl-D to stop the server.");
+			}
+			Err(ReadlineError::Eof) => {
+				if let Err(e) = minecraft_server.stop() {
+					error!("Failed to stop the server: {:?}", e);
+				}
+				break;
+			}
+			Err(e) => {
+				warn!("Console input failed: {:?}", e);
+				break;
+			}
+		}
+	}
+
+	if let Err(e) = editor.save_history(history_path) {
+		warn!("Failed to save the console history: {:?}.", e);
+	}
+}
+
+struct ConsoleHelper;
+
+impl Completer for ConsoleHelper {
+	type Candidate = String;
+
+	fn complete(
+		&self,
+		line: &str,
+		pos: usize,
+		_ctx: &Context<'_>,
+	) -> rustyline::Result<(usize, Vec<String>)> {
+		Ok((0, complete_command(&line[..pos])))
+	}
+}
+
+impl Hinter for ConsoleHelper {
+	type Hint = String;
+}
+
+impl Highlighter for ConsoleHelper {}
+
+impl Validator for ConsoleHelper {}
+
+impl Helper for ConsoleHelper {}
+
+/// Completes only the command name, i.e. when nothing but the command was typed so far.
+fn complete_command(input: &str) -> Vec<String> {
+	if input.contains(' ') {
+		return Vec::new();
+	}
+	COMMON_COMMANDS
+		.iter()
+		.filter(|command| command.starts_with(input))
+		.map(|command| command.to_string())
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn completes_command_names() {
+		assert_eq!(
+			complete_command("sa"),
+			vec!["save-all", "save-off", "save-on", "say"]
+		);
+		assert_eq!(complete_command("stop"), vec!["stop"]);
+		assert!(complete_command("say hel").is_empty());
+	}
+}
@@ -0,0 +1,183 @@
+use crate::server::ServerManager;
+use anyhow::{Context, Result};
+use log::*;
+use std::ffi::CString;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::net::Shutdown;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, OpenOptionsExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// FIFO in the server directory, every line written to it is forwarded to the server's console.
+/// `minecraft_runner say` writes to it, other programs can too, e.g.
+/// `echo "save-all" > minecraft_runner.control`. Only available on Unix.
+pub const CONTROL_FIFO_FILENAME: &str = "minecraft_runner.control";
+
+/// The control FIFO of a running runner. The FIFO is removed when this is dropped.
+pub struct ControlFifo {
+	path: PathBuf,
+}
+
+impl ControlFifo {
+	/// Creates the FIFO (replacing a stale one) and forwards the lines written to it to the
+	/// server.
+	pub fn create(working_directory: &Path, minecraft_server: Arc<ServerManager>) -> Result<Self> {
+		let path = working_directory.join(CONTROL_FIFO_FILENAME);
+		if path.exists() {
+			std::fs::remove_file(&path).with_context(|| format!("Path: {:?}", path))?;
+		}
+		let c_path = CString::new(path.as_os_str().as_bytes())?;
+		if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+			return Err(std::io::Error::last_os_error())
+				.with_context(|| format!("Failed to create the FIFO {:?}", path));
+		}
+
+		let fifo = path.clone();
+		std::thread::spawn(move || loop {
+			// Blocks until a writer opens the FIFO, and reaches the end once all writers close it
+			let file = match File::open(&fifo) {
+				Ok(v) => v,
+				Err(e) => {
+					warn!("Control FIFO closed: {:?}.", e);
+					return;
+				}
+			};
+			for line in BufReader::new(file).lines().filter_map(|line| line.ok()) {
+				let line = line.trim();
+				if line.is_empty() {
+					continue;
+				}
+				info!("Forwarding \"{}\" from the control FIFO.", line);
+				if let Err(e) = minecraft_server.send_command(line) {
+					warn!("Failed to forward \"{}\" to the server: {:?}.", line, e);
+				}
+			}
+		});
+		Ok(ControlFifo { path })
+	}
+
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+}
+
+impl Drop for ControlFifo {
+	fn drop(&mut self) {
+		if let Err(e) = std::fs::remove_file(&self.path) {
+			debug!("Failed to remove {:?}: {:?}", self.path, e);
+		}
+	}
+}
+
+/// Writes `command` to the control FIFO of the runner in `working_directory`. Fails when no
+/// runner was launched there with `--control-fifo`.
+pub fn send(working_directory: &Path, command: &str) -> Result<()> {
+	let path = working_directory.join(CONTROL_FIFO_FILENAME);
+	let is_fifo = std::fs::metadata(&path)
+		.map(|meta| meta.file_type().is_fifo())
+		.unwrap_or_default();
+	if !is_fifo {
+		anyhow::bail!(
+			"No control FIFO at {:?}, launch the runner with --control-fifo.",
+			path
+		);
+	}
+	// Without a reader a non-blocking open fails instead of hanging
+	let mut fifo = OpenOptions::new()
+		.write(true)
+		.custom_flags(libc::O_NONBLOCK)
+		.open(&path)
+		.with_context(|| format!("No runner is reading the control FIFO {:?}", path))?;
+	writeln!(fifo, "{}", command)?;
+	Ok(())
+}
+
+/// Unix domain socket forwarding the lines received from every client to the server's console,
+/// and streaming the server's stdout back, starting with the latest lines. Works as a console with
+/// e.g. `nc -U`. The socket file is removed when this is dropped.
+pub struct ControlSocket {
+	path: PathBuf,
+}
+
+impl ControlSocket {
+	/// Listens on `path`, replacing a stale socket left by a runner that didn't exit cleanly. Only
+	/// the user running the runner can connect.
+	pub fn bind(path: &Path, minecraft_server: Arc<ServerManager>) -> Result<Self> {
+		if let Ok(meta) = std::fs::symlink_metadata(path) {
+			if !meta.file_type().is_socket() {
+				anyhow::bail!("{:?} already exists and isn't a socket.", path);
+			}
+			std::fs::remove_file(path).with_context(|| format!("Path: {:?}", path))?;
+		}
+		let listener = UnixListener::bind(path)
+			.with_context(|| format!("Failed to listen on the control socket {:?}", path))?;
+		std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+			.with_context(|| format!("Path: {:?}", path))?;
+
+		std::thread::spawn(move || {
+			for stream in listener.incoming() {
+				match stream {
+					Ok(stream) => {
+						let minecraft_server = Arc::clone(&minecraft_server);
+						std::thread::spawn(move || serve_client(stream, &minecraft_server));
+					}
+					Err(e) => warn!("Failed to accept a control socket client: {:?}.", e),
+				}
+			}
+		});
+		Ok(ControlSocket {
+			path: path.to_path_buf(),
+		})
+	}
+}
+
+impl Drop for ControlSocket {
+	fn drop(&mut self) {
+		if let Err(e) = std::fs::remove_file(&self.path) {
+			debug!("Failed to remove {:?}: {:?}", self.path, e);
+		}
+	}
+}
+
+fn serve_client(stream: UnixStream, minecraft_server: &ServerManager) {
+	debug!("Control socket client connected.");
+	let mut writer = match stream.try_clone() {
+		Ok(v) => v,
+		Err(e) => {
+			warn!("Failed to serve a control socket client: {:?}.", e);
+			return;
+		}
+	};
+	let (recent, output) = minecraft_server.follow_output();
+	// Exits on the first failed write, i.e. once the client is gone
+	std::thread::spawn(move || {
+		for line in recent.into_iter().chain(output) {
+			if writer.write_all(&line).is_err() {
+				return;
+			}
+		}
+	});
+
+	for line in BufReader::new(&stream).lines() {
+		let line = match line {
+			Ok(v) => v,
+			Err(e) => {
+				debug!("Failed to read from a control socket client: {:?}", e);
+				break;
+			}
+		};
+		let line = line.trim();
+		if line.is_empty() {
+			continue;
+		}
+		info!("Forwarding \"{}\" from the control socket.", line);
+		if let Err(e) = minecraft_server.send_command(line) {
+			warn!("Failed to forward \"{}\" to the server: {:?}.", line, e);
+		}
+	}
+	let _ = stream.shutdown(Shutdown::Both);
+	debug!("Control socket client disconnected.");
+}
@@ -0,0 +1,109 @@
+use crate::logs;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Lines of `logs/latest.log` saved next to the collected crash reports.
+const DIAGNOSTICS_LOG_LINES: usize = 200;
+
+/// Crash reports written to `crash-reports/` since `since`, oldest first.
+pub fn find_crash_reports(working_directory: &Path, since: SystemTime) -> Vec<PathBuf> {
+	files_since(&working_directory.join("crash-reports"), "txt", since)
+}
+
+/// Heap dumps written to `directory` since `since`, oldest first.
+pub fn find_heap_dumps(directory: &Path, since: SystemTime) -> Vec<PathBuf> {
+	files_since(directory, "hprof", since)
+}
+
+fn files_since(directory: &Path, extension: &str, since: SystemTime) -> Vec<PathBuf> {
+	let entries = match std::fs::read_dir(directory) {
+		Ok(v) => v,
+		Err(_) => return Vec::new(),
+	};
+	let mut files: Vec<(SystemTime, PathBuf)> = entries
+		.filter_map(|entry| entry.ok())
+		.filter(|entry| entry.path().extension().map(|ext| ext == extension) == Some(true))
+		.filter_map(|entry| {
+			let modified = entry.metadata().ok()?.modified().ok()?;
+			Some((modified, entry.path()))
+		})
+		.filter(|(modified, _)| *modified >= since)
+		.collect();
+	files.sort();
+	files.into_iter().map(|(_, path)| path).collect()
+}
+
+/// The `Description:` line of a crash report, e.g. "Exception in server tick loop".
+pub fn summary(report: &Path) -> Option<String> {
+	let content = std::fs::read_to_string(report).ok()?;
+	parse_summary(&content)
+}
+
+fn parse_summary(content: &str) -> Option<String> {
+	content
+		.lines()
+		.find_map(|line| line.strip_prefix("Description: "))
+		.map(|description| description.trim().to_string())
+}
+
+/// Copies the crash reports and the tail of the server log into `diagnostics/<timestamp>/`. Heap
+/// dumps are too big to copy, only their paths are listed in `heap-dumps.txt`. Returns the created
+/// directory.
+pub fn collect(
+	working_directory: &Path,
+	reports: &[PathBuf],
+	heap_dumps: &[PathBuf],
+) -> Result<PathBuf> {
+	let timestamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+	let directory = working_directory.join("diagnostics").join(timestamp);
+	std::fs::create_dir_all(&directory).with_context(|| format!("Path: {:?}", directory))?;
+
+	for report in reports {
+		if let Some(file_name) = report.file_name() {
+			std::fs::copy(report, directory.join(file_name))
+				.with_context(|| format!("Path: {:?}", report))?;
+		}
+	}
+
+	if !heap_dumps.is_empty() {
+		let list: String = heap_dumps
+			.iter()
+			.map(|dump| format!("{}\n", dump.display()))
+			.collect();
+		std::fs::write(directory.join("heap-dumps.txt"), list)?;
+	}
+
+	let log_path = logs::latest_log_path(working_directory);
+	match logs::tail(&log_path, DIAGNOSTICS_LOG_LINES) {
+		Ok(lines) => {
+			let mut content = lines.join("\n");
+			content.push('\n');
+			std::fs::write(directory.join("latest.log"), content)?;
+		}
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+		Err(e) => return Err(e).with_context(|| format!("Path: {:?}", log_path)),
+	}
+	Ok(directory)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn description_is_the_summary() {
+		let report = "---- Minecraft Crash Report ----\n\
+			// Oops.\n\
+			\n\
+			Time: 2021-07-10, 12:00\n\
+			Description: Exception in server tick loop\n\
+			\n\
+			java.lang.NullPointerException\n";
+		assert_eq!(
+			parse_summary(report).as_deref(),
+			Some("Exception in server tick loop")
+		);
+		assert_eq!(parse_summary("no description"), None);
+	}
+}
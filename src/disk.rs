@@ -0,0 +1,100 @@
+use crate::world;
+use anyhow::Result;
+use log::*;
+use std::path::Path;
+
+/// Checks the free space on the filesystem of the working directory. Warns when there's less than
+/// `min_free` bytes, or not enough room for the world to grow and for a backup of it. With
+/// `strict`, these are errors instead.
+pub fn check_free_space(working_directory: &Path, min_free: u64, strict: bool) -> Result<()> {
+	let available = fs2::available_space(working_directory)?;
+	info!("Free disk space: {}", format_size(available));
+
+	let report = |message: String| -> Result<()> {
+		if strict {
+			anyhow::bail!(message);
+		}
+		warn!("{}", message);
+		Ok(())
+	};
+
+	if available < min_free {
+		report(format!(
+			"Only {} of disk space is left, less than {}.",
+			format_size(available),
+			format_size(min_free)
+		))?;
+	}
+
+	let world_size = world::world_size(working_directory)?;
+	// Room for the world to double: once for new chunks, once for a backup copy
+	if available < world_size.saturating_mul(2) {
+		report(format!(
+			"Only {} of disk space is left, not enough for the world ({}) and a backup of it.",
+			format_size(available),
+			format_size(world_size)
+		))?;
+	}
+	Ok(())
+}
+
+/// Checks whether files can be created in `directory` by creating (and removing) a probe file.
+/// Permission bits alone don't tell, e.g. for read-only mounts.
+pub fn is_writable(directory: &Path) -> bool {
+	let probe = directory.join(format!(
+		".minecraft_runner_write_test_{}",
+		std::process::id()
+	));
+	match std::fs::OpenOptions::new()
+		.write(true)
+		.create_new(true)
+		.open(&probe)
+	{
+		Ok(_) => {
+			let _ = std::fs::remove_file(&probe);
+			true
+		}
+		Err(e) => {
+			debug!("{} isn't writable: {:?}", directory.display(), e);
+			false
+		}
+	}
+}
+
+/// Formats a byte count with a binary unit, e.g. `1.5 GiB`.
+pub fn format_size(bytes: u64) -> String {
+	const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+	let mut size = bytes as f64;
+	let mut unit = 0;
+	while size >= 1024.0 && unit < UNITS.len() - 1 {
+		size /= 1024.0;
+		unit += 1;
+	}
+	if unit == 0 {
+		format!("{} B", bytes)
+	} else {
+		format!("{:.1} {}", size, UNITS[unit])
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn sizes_are_human_readable() {
+		assert_eq!(format_size(512), "512 B");
+		assert_eq!(format_size(1536), "1.5 KiB");
+		assert_eq!(format_size(1024 * 1024 * 1024), "1.0 GiB");
+		assert_eq!(format_size(5 * 1024u64.pow(4)), "5.0 TiB");
+	}
+
+	#[test]
+	fn writable_directory() {
+		let dir = std::env::temp_dir();
+		assert!(is_writable(&dir));
+		assert!(!is_writable(
+			&dir.join("minecraft_runner_missing_directory")
+		));
+	}
+}
@@ -0,0 +1,102 @@
+use anyhow::{Context, Result};
+use log::*;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Where the server directory is mounted in the container.
+const CONTAINER_DIRECTORY: &str = "/data";
+
+/// Launches the server in a Docker container instead of directly, see `--docker`.
+///
+/// The server directory is mounted at the same relative paths, so jars and the world are found as
+/// usual, but absolute host paths (e.g. in `--classpath`) don't exist in the container. The image has
+/// to provide `java`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DockerLaunch {
+	pub image: String,
+	pub container: String,
+	/// Published on the same port of the host
+	pub ports: Vec<u16>,
+}
+
+impl DockerLaunch {
+	/// The container is named after the runner PID, so several runners don't clash.
+	pub fn new(image: String, ports: Vec<u16>) -> Self {
+		DockerLaunch {
+			image,
+			container: format!("minecraft_runner_{}", std::process::id()),
+			ports,
+		}
+	}
+
+	/// Arguments of `docker` up to the image, the java command follows them. stdin stays open, so
+	/// console commands keep working.
+	pub fn run_args(&self, working_directory: &Path) -> Vec<String> {
+		let mut args: Vec<String> = vec![
+			String::from("run"),
+			String::from("--rm"),
+			String::from("--interactive"),
+			String::from("--name"),
+			self.container.clone(),
+			String::from("--volume"),
+			format!("{}:{}", working_directory.display(), CONTAINER_DIRECTORY),
+			String::from("--workdir"),
+			String::from(CONTAINER_DIRECTORY),
+		];
+		for port in &self.ports {
+			args.push(String::from("--publish"));
+			args.push(format!("{0}:{0}", port));
+		}
+		args.push(self.image.clone());
+		args
+	}
+}
+
+/// Returns the docker version, failing when docker isn't installed.
+pub fn check_installed() -> Result<String> {
+	let output = Command::new("docker")
+		.arg("--version")
+		.stdin(Stdio::null())
+		.output()
+		.context("Docker isn't installed (\"docker --version\" failed)")?;
+	if !output.status.success() {
+		anyhow::bail!("\"docker --version\" failed with {}", output.status);
+	}
+	Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Stops the container with SIGTERM, which the server handles by saving and shutting down. Docker
+/// kills it when it doesn't stop within `timeout`.
+pub fn stop(container: &str, timeout: Duration) -> Result<()> {
+	info!("Stopping the container \"{}\".", container);
+	let status = Command::new("docker")
+		.args(&["stop", "--time", &timeout.as_secs().to_string(), container])
+		.stdin(Stdio::null())
+		.stdout(Stdio::null())
+		.status()
+		.context("Failed to run docker stop")?;
+	if !status.success() {
+		anyhow::bail!("docker stop failed with {}", status);
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn run_args_mount_and_publish() {
+		let docker = DockerLaunch {
+			image: String::from("eclipse-temurin:17-jre"),
+			container: String::from("minecraft_runner_1"),
+			ports: vec![25565],
+		};
+		assert_eq!(
+			docker.run_args(Path::new("/srv/minecraft")).join(" "),
+			"run --rm --interactive --name minecraft_runner_1 --volume /srv/minecraft:/data \
+			 --workdir /data --publish 25565:25565 eclipse-temurin:17-jre"
+		);
+	}
+}
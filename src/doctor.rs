@@ -0,0 +1,287 @@
+use crate::color::{self, ColorChoice};
+use crate::config::{self, MinecraftRunnerConfig};
+use crate::error::RunnerError;
+use crate::find_jar::{self, FindServerJar};
+use crate::properties::{self, ServerProperties};
+use crate::settings::EffectiveSettings;
+use crate::{cgroup, disk, java, jvm, Opt};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const EULA_FILENAME: &str = "eula.txt";
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+	Pass,
+	/// Worth a look, but the server can launch
+	Warn,
+	/// The server won't launch (or work) like this
+	Fail,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Check {
+	pub name: &'static str,
+	pub status: Status,
+	pub detail: String,
+}
+
+/// Results of the pre-flight checks run by `doctor`.
+#[derive(Serialize, Debug, Default)]
+pub struct Report {
+	pub checks: Vec<Check>,
+	pub passed: bool,
+}
+
+impl Report {
+	fn add(&mut self, name: &'static str, status: Status, detail: impl Into<String>) {
+		self.checks.push(Check {
+			name,
+			status,
+			detail: detail.into(),
+		});
+		self.passed = self.checks.iter().all(|check| check.status != Status::Fail);
+	}
+
+	pub fn print(&self, color: ColorChoice) {
+		for check in &self.checks {
+			let status = match check.status {
+				Status::Pass => color.paint("PASS", color::GREEN),
+				Status::Warn => color.paint("WARN", color::YELLOW),
+				Status::Fail => color.paint("FAIL", color::RED),
+			};
+			println!("[{}] {}: {}", status, check.name, check.detail);
+		}
+		match self.passed {
+			true => println!("Ready to launch."),
+			false => println!("Not ready to launch, fix the failed checks first."),
+		}
+	}
+}
+
+/// Checks everything the launch depends on, without launching the server or writing anything.
+pub fn run(
+	opt: &Opt,
+	config: &MinecraftRunnerConfig,
+	config_path: Option<&Path>,
+	working_directory: &Path,
+	settings: &EffectiveSettings,
+) -> Report {
+	let mut report = Report {
+		passed: true,
+		..Default::default()
+	};
+
+	match config_path {
+		None => report.add("Config", Status::Pass, "Read from stdin"),
+		Some(path) => match config::read_config(path) {
+			Ok(_) => report.add("Config", Status::Pass, path.display().to_string()),
+			Err(e) if config::is_not_found(&e) => report.add(
+				"Config",
+				Status::Pass,
+				format!("{} doesn't exist, using the defaults", path.display()),
+			),
+			Err(e) => report.add("Config", Status::Fail, format!("{:#}", e)),
+		},
+	}
+
+	let java = crate::resolve_java(opt, config, None);
+	let java_major = java
+		.as_ref()
+		.ok()
+		.and_then(|java| java::java_major(java, Duration::from_secs(opt.java_probe_timeout)));
+	match (&java, java_major) {
+		(Ok(java), Some(major)) => report.add(
+			"Java",
+			Status::Pass,
+			format!("{} (java {})", java.display(), major),
+		),
+		(Ok(java), None) => report.add(
+			"Java",
+			Status::Warn,
+			format!("{} (unknown version)", java.display()),
+		),
+		(Err(e), _) => report.add("Java", Status::Fail, e.to_string()),
+	}
+
+	let jar = check_jar(&mut report, opt, working_directory, settings);
+
+	if let (Some(java_major), Some(jar)) = (java_major, &jar) {
+		let required = find_jar::read_jar_version(jar)
+			.ok()
+			.and_then(|version| java::minimum_java_major(&version).map(|major| (version, major)));
+		match required {
+			Some((version, required)) if java_major < required => report.add(
+				"Java version",
+				Status::Fail,
+				format!(
+					"Minecraft {} needs java {} or newer, found java {}",
+					version, required, java_major
+				),
+			),
+			Some((version, required)) => report.add(
+				"Java version",
+				Status::Pass,
+				format!("Minecraft {} needs java {}+", version, required),
+			),
+			None => report.add(
+				"Java version",
+				Status::Warn,
+				"The Minecraft version of the jar is unknown",
+			),
+		}
+	}
+
+	match ServerProperties::read(&working_directory.join(EULA_FILENAME)) {
+		Ok(eula) if eula.get("eula").as_deref() == Some("true") => {
+			report.add("EULA", Status::Pass, "Accepted")
+		}
+		Ok(_) => report.add(
+			"EULA",
+			Status::Fail,
+			RunnerError::EulaNotAccepted {
+				path: PathBuf::from(EULA_FILENAME),
+			}
+			.to_string(),
+		),
+		Err(e) => report.add("EULA", Status::Fail, format!("{:#}", e)),
+	}
+
+	let min_free_space = opt
+		.min_free_space
+		.clone()
+		.into::<human_size::Byte>()
+		.value() as u64;
+	match disk::check_free_space(working_directory, min_free_space, true) {
+		Ok(()) => report.add(
+			"Disk space",
+			Status::Pass,
+			fs2::available_space(working_directory)
+				.map(|available| format!("{} free", disk::format_size(available)))
+				.unwrap_or_default(),
+		),
+		Err(e) if opt.strict_disk => report.add("Disk space", Status::Fail, format!("{:#}", e)),
+		Err(e) => report.add("Disk space", Status::Warn, format!("{:#}", e)),
+	}
+
+	let ceiling = cgroup::memory_ceiling();
+	let max_mib = settings.max_mib.value;
+	let status = match max_mib * 1024 * 1024 > ceiling {
+		true => Status::Warn,
+		false => Status::Pass,
+	};
+	report.add(
+		"Memory",
+		status,
+		format!(
+			"Max JVM size {}, {} available",
+			jvm::format_jvm_size(max_mib),
+			disk::format_size(ceiling)
+		),
+	);
+
+	check_port(&mut report, opt, working_directory, settings);
+
+	report
+}
+
+/// Adds the jar check and returns the jar that would be launched, when it's known without asking.
+fn check_jar(
+	report: &mut Report,
+	opt: &Opt,
+	working_directory: &Path,
+	settings: &EffectiveSettings,
+) -> Option<PathBuf> {
+	if let (Some(classpath), Some(main_class)) = (&opt.classpath, &opt.main_class) {
+		match jvm::Launch::main_class(working_directory, classpath, main_class) {
+			Ok(launch) => report.add("Jar", Status::Pass, format!("Launching {}", launch)),
+			Err(e) => report.add("Jar", Status::Fail, format!("{:#}", e)),
+		}
+		return None;
+	}
+
+	let jar = match find_jar::find_server_jar(working_directory, &settings.jar_preferences()) {
+		Ok(FindServerJar::ServerJar(jar))
+		| Ok(FindServerJar::PreferredJar(jar, _))
+		| Ok(FindServerJar::OneUnknownJar(jar)) => jar,
+		Ok(FindServerJar::MultipleJars(jars)) => {
+			report.add(
+				"Jar",
+				Status::Warn,
+				format!("{} jars found, one has to be chosen on launch", jars.len()),
+			);
+			return None;
+		}
+		Ok(FindServerJar::None) => {
+			let no_jars = find_jar::no_jars_error(working_directory);
+			report.add("Jar", Status::Fail, no_jars.to_string());
+			return None;
+		}
+		Err(e) => {
+			report.add("Jar", Status::Fail, format!("{:#}", e));
+			return None;
+		}
+	};
+	if !find_jar::looks_like_jar(&jar) {
+		report.add(
+			"Jar",
+			Status::Fail,
+			format!("{} isn't a valid jar", jar.display()),
+		);
+		return None;
+	}
+	report.add("Jar", Status::Pass, jar.display().to_string());
+	Some(jar)
+}
+
+fn check_port(
+	report: &mut Report,
+	opt: &Opt,
+	working_directory: &Path,
+	settings: &EffectiveSettings,
+) {
+	if opt.random_port {
+		report.add(
+			"Port",
+			Status::Pass,
+			"A random free port is picked on launch",
+		);
+		return;
+	}
+	let port = match &settings.port {
+		Some(port) => Ok(port.value),
+		None => properties::get_property(working_directory, "server-port").map(|port| {
+			port.and_then(|port| port.parse().ok())
+				.unwrap_or(crate::DEFAULT_SERVER_PORT)
+		}),
+	};
+	let result = port.and_then(|port| {
+		let ip = crate::server_bind_ip(working_directory)?;
+		crate::check_port_free(ip, port)?;
+		Ok(port)
+	});
+	match result {
+		Ok(port) => report.add("Port", Status::Pass, format!("{} is free", port)),
+		Err(e) => report.add("Port", Status::Fail, format!("{:#}", e)),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn fails_on_any_failed_check() {
+		let mut report = Report {
+			passed: true,
+			..Default::default()
+		};
+		report.add("Java", Status::Pass, "java");
+		report.add("Memory", Status::Warn, "too much");
+		assert!(report.passed);
+		report.add("EULA", Status::Fail, "not accepted");
+		assert!(!report.passed);
+	}
+}
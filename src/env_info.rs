@@ -0,0 +1,86 @@
+use crate::cgroup;
+use crate::disk::format_size;
+use crate::find_jar::JarInfo;
+use crate::mods::{self, ModInfo};
+use crate::settings::EffectiveSettings;
+use serde::Serialize;
+use std::path::PathBuf;
+use sysinfo::{System, SystemExt};
+
+/// Snapshot of the environment printed by `env-info`, for bug reports.
+#[derive(Serialize)]
+pub struct EnvInfo<'a> {
+	pub runner_version: &'static str,
+	pub os: &'static str,
+	pub arch: &'static str,
+	pub total_memory: u64,
+	pub available_memory: u64,
+	pub cgroup_memory_limit: Option<u64>,
+	pub java: Option<PathBuf>,
+	pub java_major: Option<u32>,
+	pub working_directory: PathBuf,
+	pub jars: Vec<JarInfo>,
+	pub mods: Vec<ModInfo>,
+	pub settings: &'a EffectiveSettings,
+}
+
+impl<'a> EnvInfo<'a> {
+	pub fn new(
+		java: Option<PathBuf>,
+		java_major: Option<u32>,
+		working_directory: PathBuf,
+		jars: Vec<JarInfo>,
+		settings: &'a EffectiveSettings,
+	) -> Self {
+		let mut system = System::new();
+		system.refresh_memory();
+		EnvInfo {
+			runner_version: env!("CARGO_PKG_VERSION"),
+			os: std::env::consts::OS,
+			arch: std::env::consts::ARCH,
+			// sysinfo reports KiB
+			total_memory: system.total_memory() * 1024,
+			available_memory: system.available_memory() * 1024,
+			cgroup_memory_limit: cgroup::memory_limit(),
+			java,
+			java_major,
+			mods: mods::scan_server(&working_directory),
+			working_directory,
+			jars,
+			settings,
+		}
+	}
+
+	pub fn print(&self) -> anyhow::Result<()> {
+		println!("minecraft_runner {}", self.runner_version);
+		println!("OS: {} ({})", self.os, self.arch);
+		println!(
+			"Memory: {} available of {}",
+			format_size(self.available_memory),
+			format_size(self.total_memory)
+		);
+		if let Some(limit) = self.cgroup_memory_limit {
+			println!("Cgroup memory limit: {}", format_size(limit));
+		}
+		match (&self.java, self.java_major) {
+			(Some(java), Some(major)) => println!("Java: {} (java {})", java.display(), major),
+			(Some(java), None) => println!("Java: {} (unknown version)", java.display()),
+			(None, _) => println!("Java: not found"),
+		}
+		println!("Working directory: {}", self.working_directory.display());
+		println!("Jars:");
+		for jar in &self.jars {
+			println!("  {}", jar);
+		}
+		println!("Plugins and mods: {}", self.mods.len());
+		for mod_info in &self.mods {
+			println!("  {}", mod_info);
+		}
+		let pretty = ron::ser::PrettyConfig::new();
+		println!(
+			"Settings:\n{}",
+			ron::ser::to_string_pretty(self.settings, pretty)?
+		);
+		Ok(())
+	}
+}
@@ -0,0 +1,119 @@
+use std::io;
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+/// Failures callers may want to tell apart, e.g. to pick an HTTP status or an exit code. Other
+/// failures stay plain `anyhow` errors with a message.
+#[derive(thiserror::Error, Debug)]
+pub enum RunnerError {
+	#[error("Java not found")]
+	JavaNotFound,
+	#[error("No server jars found laying around in the current directory (\"{}\").", .directory.display())]
+	NoJars { directory: PathBuf },
+	#[error("No server jars found, but \"{}\" is a directory that looks like an extracted jar. Launch the packaged .jar file instead.", .directory.display())]
+	ExtractedJar { directory: PathBuf },
+	#[error("EULA not accepted, set eula=true in {} after reading https://aka.ms/MinecraftEULA", .path.display())]
+	EulaNotAccepted { path: PathBuf },
+	#[error("Jar number {number} is out of range, there are {count} jars")]
+	JarOutOfRange { number: usize, count: usize },
+	#[error("Port {port} on {ip} is already in use")]
+	PortInUse {
+		ip: IpAddr,
+		port: u16,
+		#[source]
+		source: io::Error,
+	},
+	#[error("Failed to parse the config {path:?}")]
+	ConfigParse {
+		path: PathBuf,
+		#[source]
+		source: Box<dyn std::error::Error + Send + Sync>,
+	},
+}
+
+/// Exit codes of the runner, so scripts supervising it can tell why it failed.
+///
+/// | Code | Meaning                          |
+/// |------|----------------------------------|
+/// | 0    | Clean exit                       |
+/// | 1    | Any other failure                |
+/// | 2    | Java not found                   |
+/// | 3    | No server jars found             |
+/// | 4    | EULA not accepted                |
+/// | 5    | Port already in use              |
+/// | 6    | Jar number out of range          |
+/// | 7    | Config can't be parsed           |
+pub mod exit_codes {
+	pub const UNKNOWN: i32 = 1;
+	pub const JAVA_NOT_FOUND: i32 = 2;
+	pub const NO_JARS: i32 = 3;
+	pub const EULA_NOT_ACCEPTED: i32 = 4;
+	pub const PORT_IN_USE: i32 = 5;
+	pub const JAR_OUT_OF_RANGE: i32 = 6;
+	pub const CONFIG_PARSE: i32 = 7;
+}
+
+impl RunnerError {
+	pub fn exit_code(&self) -> i32 {
+		match self {
+			RunnerError::JavaNotFound => exit_codes::JAVA_NOT_FOUND,
+			RunnerError::NoJars { .. } | RunnerError::ExtractedJar { .. } => exit_codes::NO_JARS,
+			RunnerError::EulaNotAccepted { .. } => exit_codes::EULA_NOT_ACCEPTED,
+			RunnerError::JarOutOfRange { .. } => exit_codes::JAR_OUT_OF_RANGE,
+			RunnerError::PortInUse { .. } => exit_codes::PORT_IN_USE,
+			RunnerError::ConfigParse { .. } => exit_codes::CONFIG_PARSE,
+		}
+	}
+}
+
+/// Exit code of the first `RunnerError` in the chain of `e`, `1` when there is none.
+pub fn exit_code(e: &anyhow::Error) -> i32 {
+	e.chain()
+		.find_map(|e| e.downcast_ref::<RunnerError>())
+		.map(RunnerError::exit_code)
+		.unwrap_or(exit_codes::UNKNOWN)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use anyhow::Context;
+
+	#[test]
+	fn exit_codes() {
+		let port_in_use = || RunnerError::PortInUse {
+			ip: IpAddr::from([127, 0, 0, 1]),
+			port: 25565,
+			source: io::Error::from(io::ErrorKind::AddrInUse),
+		};
+		assert_eq!(exit_code(&RunnerError::JavaNotFound.into()), 2);
+		assert_eq!(
+			exit_code(
+				&RunnerError::NoJars {
+					directory: PathBuf::from("server")
+				}
+				.into()
+			),
+			3
+		);
+		assert_eq!(
+			exit_code(
+				&RunnerError::EulaNotAccepted {
+					path: PathBuf::from("eula.txt")
+				}
+				.into()
+			),
+			4
+		);
+		assert_eq!(exit_code(&port_in_use().into()), 5);
+		assert_eq!(
+			exit_code(
+				&Err::<(), _>(port_in_use())
+					.context("The JMX port is not available")
+					.unwrap_err()
+			),
+			5
+		);
+		assert_eq!(exit_code(&anyhow::anyhow!("Something else")), 1);
+	}
+}
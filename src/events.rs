@@ -0,0 +1,155 @@
+//! Player join and leave events parsed from the server output.
+use crate::server::ServerManager;
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::sync::{mpsc, Arc, Mutex};
+
+/// Vanilla prints `[12:34:56] [Server thread/INFO]: Steve joined the game`.
+pub const DEFAULT_JOIN_PATTERN: &str = r"(?:^|: )(?P<name>[A-Za-z0-9_.]{1,16}) joined the game\s*$";
+pub const DEFAULT_LEAVE_PATTERN: &str = r"(?:^|: )(?P<name>[A-Za-z0-9_.]{1,16}) left the game\s*$";
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", content = "player", rename_all = "lowercase")]
+pub enum PlayerEvent {
+	Joined(String),
+	Left(String),
+}
+
+impl PlayerEvent {
+	pub fn player(&self) -> &str {
+		match self {
+			PlayerEvent::Joined(player) | PlayerEvent::Left(player) => player,
+		}
+	}
+}
+
+/// Regexes recognizing the join and leave lines. Both need a `name` capture group.
+pub struct PlayerPatterns {
+	joined: Regex,
+	left: Regex,
+}
+
+impl PlayerPatterns {
+	/// Uses the vanilla patterns, unless overridden e.g. for a modded server.
+	pub fn new(joined: Option<&str>, left: Option<&str>) -> Result<Self> {
+		Ok(PlayerPatterns {
+			joined: compile(joined.unwrap_or(DEFAULT_JOIN_PATTERN))?,
+			left: compile(left.unwrap_or(DEFAULT_LEAVE_PATTERN))?,
+		})
+	}
+
+	pub fn parse(&self, line: &str) -> Option<PlayerEvent> {
+		let name = |pattern: &Regex| {
+			pattern
+				.captures(line)
+				.and_then(|captures| captures.name("name"))
+				.map(|name| name.as_str().to_string())
+		};
+		name(&self.joined)
+			.map(PlayerEvent::Joined)
+			.or_else(|| name(&self.left).map(PlayerEvent::Left))
+	}
+}
+
+fn compile(pattern: &str) -> Result<Regex> {
+	let regex =
+		Regex::new(pattern).with_context(|| format!("Invalid player pattern \"{}\"", pattern))?;
+	if !regex.capture_names().any(|name| name == Some("name")) {
+		anyhow::bail!(
+			"The player pattern \"{}\" has no (?P<name>...) group",
+			pattern
+		);
+	}
+	Ok(regex)
+}
+
+/// Players online according to the join and leave events.
+#[derive(Default)]
+pub struct OnlinePlayers {
+	players: Mutex<BTreeSet<String>>,
+}
+
+impl OnlinePlayers {
+	pub fn apply(&self, event: &PlayerEvent) {
+		let mut players = self.players.lock().unwrap();
+		match event {
+			PlayerEvent::Joined(player) => players.insert(player.clone()),
+			PlayerEvent::Left(player) => players.remove(player),
+		};
+	}
+
+	/// Sorted player names.
+	pub fn list(&self) -> Vec<String> {
+		self.players.lock().unwrap().iter().cloned().collect()
+	}
+
+	pub fn count(&self) -> usize {
+		self.players.lock().unwrap().len()
+	}
+}
+
+/// Follows the server output from a thread, keeping the returned players up to date and sending
+/// every event to the returned receiver. Dropping the receiver only stops the events, the players
+/// keep being tracked.
+pub fn track(
+	server: &ServerManager,
+	patterns: PlayerPatterns,
+) -> (Arc<OnlinePlayers>, mpsc::Receiver<PlayerEvent>) {
+	let players = Arc::new(OnlinePlayers::default());
+	let (sender, events) = mpsc::channel();
+	let (recent, lines) = server.follow_output();
+	let online = Arc::clone(&players);
+	std::thread::spawn(move || {
+		for line in recent.into_iter().chain(lines) {
+			if let Some(event) = patterns.parse(&String::from_utf8_lossy(&line)) {
+				online.apply(&event);
+				let _ = sender.send(event);
+			}
+		}
+	});
+	(players, events)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn vanilla_join_and_leave() {
+		let patterns = PlayerPatterns::new(None, None).unwrap();
+		assert_eq!(
+			patterns.parse("[12:34:56] [Server thread/INFO]: Steve joined the game\n"),
+			Some(PlayerEvent::Joined(String::from("Steve")))
+		);
+		assert_eq!(
+			patterns.parse("[12:34:56 INFO]: Alex_2 left the game"),
+			Some(PlayerEvent::Left(String::from("Alex_2")))
+		);
+		assert_eq!(
+			patterns.parse("[12:34:56] [Server thread/INFO]: <Steve> Alex joined the game"),
+			None
+		);
+	}
+
+	#[test]
+	fn custom_patterns_need_a_name() {
+		let patterns = PlayerPatterns::new(Some(r"\+ (?P<name>\w+)"), None).unwrap();
+		assert_eq!(
+			patterns.parse("+ Steve"),
+			Some(PlayerEvent::Joined(String::from("Steve")))
+		);
+		assert!(PlayerPatterns::new(Some(r"(\w+) logged in"), None).is_err());
+	}
+
+	#[test]
+	fn online_players() {
+		let players = OnlinePlayers::default();
+		players.apply(&PlayerEvent::Joined(String::from("Steve")));
+		players.apply(&PlayerEvent::Joined(String::from("Alex")));
+		players.apply(&PlayerEvent::Left(String::from("Steve")));
+		assert_eq!(players.list(), vec![String::from("Alex")]);
+		assert_eq!(players.count(), 1);
+	}
+}
@@ -1,3 +1,5 @@
+use crate::color::{self, ColorChoice};
+use crate::error::RunnerError;
 use anyhow::{Context, Result};
 use log::*;
 use nom::character::complete::{digit1, space0};
@@ -7,6 +9,7 @@ use serde::{Deserialize, Serialize};
 use std::io;
 use std::ops::RangeInclusive;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 pub enum FindServerJar {
 	ServerJar(PathBuf),
@@ -16,39 +19,117 @@ pub enum FindServerJar {
 	None,
 }
 
-pub fn find_server_jar(root: &Path) -> Result<FindServerJar> {
+/// Jars in `root`, sorted by file name, so they're listed in the same order on every run and
+/// platform.
+fn list_jars(root: &Path) -> io::Result<Vec<PathBuf>> {
 	let mut jars: Vec<PathBuf> = std::fs::read_dir(root)?
 		.filter_map(|entry| entry.ok())
 		.map(|entry| entry.path())
 		.filter(|path| path.is_file())
 		.filter(|path| path.extension().map(|ext| ext == "jar").unwrap_or_default())
 		.collect();
+	sort_jars(&mut jars);
+	Ok(jars)
+}
+
+/// Finds a directory that looks like a jar extracted in place, e.g. `server.jar/` unpacked by an
+/// archive tool. It's skipped when looking for jars, which would otherwise only be reported as
+/// "no jars found".
+pub fn find_extracted_jar(root: &Path) -> Option<PathBuf> {
+	let mut dirs: Vec<PathBuf> = std::fs::read_dir(root)
+		.ok()?
+		.filter_map(|entry| entry.ok())
+		.map(|entry| entry.path())
+		.filter(|path| path.is_dir())
+		.filter(|path| looks_like_extracted_jar(path))
+		.collect();
+	sort_jars(&mut dirs);
+	dirs.into_iter().next()
+}
+
+fn looks_like_extracted_jar(dir: &Path) -> bool {
+	dir.extension().map(|ext| ext == "jar").unwrap_or_default()
+		|| dir.join("META-INF").join("MANIFEST.MF").is_file()
+}
+
+/// How often the server directory is listed before concluding there are no jars. Network mounts
+/// may fail or list nothing for a moment after being mounted.
+#[derive(Debug, Clone, Copy)]
+pub struct ListRetry {
+	pub attempts: u32,
+	pub delay: Duration,
+}
+
+impl Default for ListRetry {
+	fn default() -> Self {
+		ListRetry {
+			attempts: 3,
+			delay: Duration::from_secs(1),
+		}
+	}
+}
+
+fn list_jars_with_retry(root: &Path, retry: ListRetry) -> io::Result<Vec<PathBuf>> {
+	let mut attempt = 1;
+	loop {
+		let jars = list_jars(root);
+		let reason = match &jars {
+			Ok(found) if !found.is_empty() => None,
+			Ok(_) => Some(String::from("no jars found")),
+			Err(e) => Some(e.to_string()),
+		};
+		let reason = match reason {
+			Some(v) if attempt < retry.attempts => v,
+			_ => return jars,
+		};
+		debug!(
+			"Listing {} failed ({}), retrying in {}s ({}/{}).",
+			root.display(),
+			reason,
+			retry.delay.as_secs_f64(),
+			attempt,
+			retry.attempts
+		);
+		std::thread::sleep(retry.delay);
+		attempt += 1;
+	}
+}
+
+fn sort_jars(jars: &mut [PathBuf]) {
+	jars.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+}
+
+/// Finds the jar to launch in `root`, trying the preferred jars in order until one of them
+/// exists.
+pub fn find_server_jar(root: &Path, jar_preferences: &[&Path]) -> Result<FindServerJar> {
+	find_server_jar_with_retry(root, jar_preferences, ListRetry::default())
+}
+
+/// [`find_server_jar`] with `retry` for listing `root`.
+pub fn find_server_jar_with_retry(
+	root: &Path,
+	jar_preferences: &[&Path],
+	retry: ListRetry,
+) -> Result<FindServerJar> {
+	let mut jars = list_jars_with_retry(root, retry)?;
 
 	if jars.is_empty() {
 		return Ok(FindServerJar::None);
 	}
 
 	// See if there's a previously set jar preference that exists
-	match read_config(root) {
-		Ok(config) => {
-			if let Some(preferred_jar) = jars
-				.iter()
-				.find(|jar| jar.file_name() == config.jar_preference.file_name())
-				.map(ToOwned::to_owned)
-			{
-				return Ok(FindServerJar::PreferredJar(preferred_jar, jars));
-			}
-		}
-		Err(e)
-			if e.downcast_ref::<std::io::Error>()
-				.map(|io_err| io_err.kind() == std::io::ErrorKind::NotFound)
-				.unwrap_or_default() =>
+	for jar_preference in jar_preferences {
+		if let Some(preferred_jar) = jars
+			.iter()
+			.find(|jar| jar.file_name() == jar_preference.file_name())
+			.map(ToOwned::to_owned)
 		{
-			()
-		}
-		Err(e) => {
-			warn!("Failed to read config: {:?}.", e);
+			return Ok(FindServerJar::PreferredJar(preferred_jar, jars));
 		}
+		warn!(
+			"The preferred jar \"{}\" doesn't exist, ignoring it.",
+			jar_preference.display()
+		);
 	}
 
 	if jars.len() == 1 {
@@ -66,12 +147,143 @@ pub fn find_server_jar(root: &Path) -> Result<FindServerJar> {
 	}
 }
 
-pub fn ask_which_jar_to_use(jars: &[PathBuf]) -> io::Result<PathBuf> {
-	let server_jar: Option<(usize, &PathBuf)> = jars.iter().enumerate().find(|(_idx, path)| {
+/// A jar in the server directory, as printed by `list-jars`.
+#[derive(Serialize, Debug)]
+pub struct JarInfo {
+	pub path: PathBuf,
+	/// Named `server.jar`, which is launched without asking when it's the only jar
+	pub is_default: bool,
+	pub is_preferred: bool,
+	pub version: Option<String>,
+}
+
+impl std::fmt::Display for JarInfo {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.path.display())?;
+		if let Some(version) = &self.version {
+			write!(f, " {}", version)?;
+		}
+		let mut tags = Vec::new();
+		if self.is_default {
+			tags.push("default");
+		}
+		if self.is_preferred {
+			tags.push("preferred");
+		}
+		if !tags.is_empty() {
+			write!(f, " ({})", tags.join(", "))?;
+		}
+		Ok(())
+	}
+}
+
+pub fn describe_jars(root: &Path, jar_preference: Option<&Path>) -> Result<Vec<JarInfo>> {
+	let jars = list_jars(root)?
+		.into_iter()
+		.map(|path| JarInfo {
+			is_default: path.file_name().map(|name| name == "server.jar") == Some(true),
+			is_preferred: jar_preference
+				.map(|jar_preference| path.file_name() == jar_preference.file_name())
+				.unwrap_or_default(),
+			version: read_jar_version(&path).ok(),
+			path,
+		})
+		.collect();
+	Ok(jars)
+}
+
+#[derive(Deserialize)]
+struct JarVersionInfo {
+	id: String,
+}
+
+/// Reads the Minecraft version from the `version.json` bundled in the server jar. Only vanilla
+/// based jars since 1.14 bundle it.
+pub fn read_jar_version(jar: &Path) -> Result<String> {
+	let file = std::fs::File::open(jar).with_context(|| format!("Path: {:?}", jar))?;
+	let mut archive = zip::ZipArchive::new(file)?;
+	let version_json = archive
+		.by_name("version.json")
+		.with_context(|| format!("{:?} doesn't contain version.json", jar))?;
+	let info: JarVersionInfo = serde_json::from_reader(version_json)?;
+	Ok(info.id)
+}
+
+/// Reads `Main-Class` from the manifest of the jar.
+pub fn read_main_class(jar: &Path) -> Result<String> {
+	use std::io::Read;
+
+	let file = std::fs::File::open(jar).with_context(|| format!("Path: {:?}", jar))?;
+	let mut archive = zip::ZipArchive::new(file)?;
+	let mut manifest = String::new();
+	archive
+		.by_name("META-INF/MANIFEST.MF")
+		.with_context(|| format!("{:?} doesn't contain a manifest", jar))?
+		.read_to_string(&mut manifest)?;
+	manifest
+		.lines()
+		.find_map(|line| line.strip_prefix("Main-Class:"))
+		.map(|main_class| main_class.trim().to_string())
+		.ok_or_else(|| anyhow::anyhow!("{:?} has no Main-Class", jar))
+}
+
+/// Hex encoded SHA-256 of the jar, read in chunks.
+pub fn sha256(jar: &Path) -> Result<String> {
+	use sha2::{Digest, Sha256};
+
+	let mut file = std::fs::File::open(jar).with_context(|| format!("Path: {:?}", jar))?;
+	let mut hasher = Sha256::new();
+	io::copy(&mut file, &mut hasher).with_context(|| format!("Failed to read {:?}", jar))?;
+	Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Checks the zip magic bytes, which every jar starts with.
+pub fn looks_like_jar(path: &Path) -> bool {
+	use std::io::Read;
+
+	let mut magic = [0u8; 4];
+	std::fs::File::open(path)
+		.and_then(|mut file| file.read_exact(&mut magic))
+		.map(|_| magic == *b"PK\x03\x04")
+		.unwrap_or_default()
+}
+
+/// Asks whether to launch a jar that isn't named `server.jar`. Defaults to yes, unless the file
+/// doesn't look like a jar at all.
+pub fn confirm_unknown_jar(jar: &Path) -> io::Result<bool> {
+	let name = jar
+		.file_name()
+		.map(|name| name.to_string_lossy().into_owned())
+		.unwrap_or_default();
+	let is_jar = looks_like_jar(jar);
+	if is_jar {
+		info!("Found \"{}\", launch it? [Y/n]", name);
+	} else {
+		warn!(
+			"Found \"{}\", but it doesn't look like a valid jar. Launch it anyway? [y/N]",
+			name
+		);
+	}
+
+	let mut line = String::new();
+	std::io::stdin().read_line(&mut line)?;
+	Ok(match line.trim().to_lowercase().as_str() {
+		"" => is_jar,
+		answer => answer == "y" || answer == "yes",
+	})
+}
+
+/// Index of `server.jar`, which is listed first when asking which jar to use.
+pub fn server_jar_idx(jars: &[PathBuf]) -> Option<usize> {
+	jars.iter().position(|path| {
 		path.file_name()
 			.map(|file_name| file_name == "server.jar")
 			.unwrap_or_default()
-	});
+	})
+}
+
+pub fn ask_which_jar_to_use(jars: &[PathBuf], color: ColorChoice) -> Result<PathBuf> {
+	let server_jar: Option<(usize, &PathBuf)> = server_jar_idx(jars).map(|idx| (idx, &jars[idx]));
 
 	type ServerJarFilterClosure = dyn for<'r, 's> FnMut(&'r (usize, &'s PathBuf)) -> bool;
 
@@ -85,12 +297,21 @@ pub fn ask_which_jar_to_use(jars: &[PathBuf]) -> io::Result<PathBuf> {
 	let mut idx = 1;
 
 	if let Some((_, server_jar)) = server_jar {
-		info!("{}. {} (default)", idx, server_jar.display());
+		info!(
+			"{} {} {}",
+			color.paint(&format!("{}.", idx), color::BOLD),
+			server_jar.display(),
+			color.paint("(default)", color::GREEN)
+		);
 		idx += 1;
 	}
 
 	for (_, file_name) in jars.iter().enumerate().filter(server_jar_filter) {
-		info!("{}. {}", idx, file_name.display());
+		info!(
+			"{} {}",
+			color.paint(&format!("{}.", idx), color::BOLD),
+			file_name.display()
+		);
 		idx += 1;
 	}
 
@@ -111,20 +332,47 @@ pub fn ask_which_jar_to_use(jars: &[PathBuf]) -> io::Result<PathBuf> {
 		}
 	};
 
-	Ok(get_jar_from_jars(jars, idx, server_jar.map(|(idx, _)| idx)))
+	Ok(get_jar_from_jars(
+		jars,
+		idx,
+		server_jar.map(|(idx, _)| idx),
+	)?)
 }
 
-fn get_jar_from_jars(jars: &[PathBuf], idx: usize, server_jar_idx: Option<usize>) -> PathBuf {
-	let server_jar_idx = match server_jar_idx {
-		Some(idx) => idx,
-		None => return jars[idx].clone(),
-	};
+/// The error for `directory` when [`find_server_jar`] found no jars in it. A jar extracted into a
+/// directory is pointed out, as it's the likely reason for no jars being found.
+pub fn no_jars_error(directory: &Path) -> RunnerError {
+	match find_extracted_jar(directory) {
+		Some(directory) => RunnerError::ExtractedJar { directory },
+		None => RunnerError::NoJars {
+			directory: directory.to_path_buf(),
+		},
+	}
+}
 
-	match idx {
-		0 => jars[server_jar_idx].clone(),
-		idx if (1..=server_jar_idx).contains(&idx) => jars[idx - 1].clone(),
-		idx if ((server_jar_idx + 1)..=usize::MAX).contains(&idx) => jars[idx].clone(),
-		_ => unreachable!(),
+/// Picks the jar at `idx` of the order the jars are listed in: server.jar first, then the rest in
+/// the original order.
+pub fn get_jar_from_jars(
+	jars: &[PathBuf],
+	idx: usize,
+	server_jar_idx: Option<usize>,
+) -> Result<PathBuf, RunnerError> {
+	let ordered: Vec<&PathBuf> = server_jar_idx
+		.and_then(|server_jar_idx| jars.get(server_jar_idx))
+		.into_iter()
+		.chain(
+			jars.iter()
+				.enumerate()
+				.filter(|(i, _)| Some(*i) != server_jar_idx)
+				.map(|(_, jar)| jar),
+		)
+		.collect();
+	match ordered.get(idx) {
+		Some(jar) => Ok(jar.to_path_buf()),
+		None => Err(RunnerError::JarOutOfRange {
+			number: idx + 1,
+			count: jars.len(),
+		}),
 	}
 }
 
@@ -146,46 +394,74 @@ fn parse_number_in_range(number_input: &str, range: RangeInclusive<usize>) -> Re
 	Ok(number)
 }
 
-const CONFIG_FILENAME: &str = "minecraft_runner_config.ron";
+#[cfg(test)]
+mod tests {
+	use super::*;
 
-#[derive(Serialize, Deserialize, Default)]
-struct MinecraftRunnerConfig {
-	jar_preference: PathBuf,
-}
+	#[test]
+	fn jars_are_sorted_by_file_name() {
+		let mut jars = vec![
+			PathBuf::from("dir/server.jar"),
+			PathBuf::from("dir/forge.jar"),
+			PathBuf::from("dir/paper.jar"),
+			PathBuf::from("dir/fabric.jar"),
+		];
+		sort_jars(&mut jars);
+		assert_eq!(
+			jars,
+			vec![
+				PathBuf::from("dir/fabric.jar"),
+				PathBuf::from("dir/forge.jar"),
+				PathBuf::from("dir/paper.jar"),
+				PathBuf::from("dir/server.jar"),
+			]
+		);
+	}
 
-fn read_config(working_directory: &Path) -> Result<MinecraftRunnerConfig> {
-	let config_path = working_directory.join(CONFIG_FILENAME);
-	let str = std::fs::read_to_string(&config_path)
-		.with_context(|| format!("Path: {:?}", config_path))?;
-	let config: MinecraftRunnerConfig = ron::from_str(&str)?;
-	Ok(config)
-}
+	#[test]
+	fn extracted_jar_is_found() {
+		let root = std::env::temp_dir().join(format!(
+			"minecraft_runner_extracted_jar_{}",
+			std::process::id()
+		));
+		let manifest_dir = root.join("paper").join("META-INF");
+		std::fs::create_dir_all(&manifest_dir).unwrap();
+		std::fs::create_dir_all(root.join("world")).unwrap();
+		assert_eq!(find_extracted_jar(&root), None);
+
+		std::fs::write(manifest_dir.join("MANIFEST.MF"), "Manifest-Version: 1.0\n").unwrap();
+		assert_eq!(find_extracted_jar(&root), Some(root.join("paper")));
+
+		std::fs::create_dir_all(root.join("fabric.jar")).unwrap();
+		assert_eq!(find_extracted_jar(&root), Some(root.join("fabric.jar")));
+
+		std::fs::remove_dir_all(&root).unwrap();
+	}
 
-pub fn save_jar_preference(jar: &Path, working_directory: &Path) -> Result<()> {
-	let mut config = match read_config(working_directory) {
-		Ok(v) => v,
-		Err(e)
-			if e.downcast_ref::<std::io::Error>()
-				.map(|io_err| io_err.kind() == std::io::ErrorKind::NotFound)
-				.unwrap_or_default() =>
-		{
-			MinecraftRunnerConfig::default()
-		}
-		Err(e) => return Err(e.into()),
-	};
-	config.jar_preference = jar
-		.file_name()
-		.map(PathBuf::from)
-		.ok_or(anyhow::anyhow!("Failed to get the filename of {:?}.", jar))?;
-	let config_path = working_directory.join(CONFIG_FILENAME);
-	std::fs::write(&config_path, ron::to_string(&config)?)
-		.with_context(|| format!("Path: {:?}", config_path))?;
-	Ok(())
-}
+	#[test]
+	fn listing_is_retried() {
+		let missing = std::env::temp_dir().join("minecraft_runner_missing_server_directory");
+		let retry = ListRetry {
+			attempts: 3,
+			delay: Duration::from_millis(1),
+		};
+		assert!(list_jars_with_retry(&missing, retry).is_err());
+		assert!(find_server_jar_with_retry(&missing, &[], retry).is_err());
+	}
 
-#[cfg(test)]
-mod tests {
-	use super::*;
+	#[test]
+	fn sha256_of_file() {
+		let path = std::env::temp_dir().join(format!(
+			"minecraft_runner_sha256_{}.jar",
+			std::process::id()
+		));
+		std::fs::write(&path, "abc").unwrap();
+		assert_eq!(
+			sha256(&path).unwrap(),
+			"ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+		);
+		std::fs::remove_file(&path).unwrap();
+	}
 
 	#[test]
 	fn idx_no_server_jar() {
@@ -195,10 +471,30 @@ mod tests {
 			PathBuf::from("s3.jar"),
 			PathBuf::from("s4.jar"),
 		];
-		assert_eq!(get_jar_from_jars(&jars, 0, None), PathBuf::from("s1.jar"));
-		assert_eq!(get_jar_from_jars(&jars, 1, None), PathBuf::from("s2.jar"));
-		assert_eq!(get_jar_from_jars(&jars, 2, None), PathBuf::from("s3.jar"));
-		assert_eq!(get_jar_from_jars(&jars, 3, None), PathBuf::from("s4.jar"));
+		assert_eq!(
+			get_jar_from_jars(&jars, 0, None).unwrap(),
+			PathBuf::from("s1.jar")
+		);
+		assert_eq!(
+			get_jar_from_jars(&jars, 1, None).unwrap(),
+			PathBuf::from("s2.jar")
+		);
+		assert_eq!(
+			get_jar_from_jars(&jars, 2, None).unwrap(),
+			PathBuf::from("s3.jar")
+		);
+		assert_eq!(
+			get_jar_from_jars(&jars, 3, None).unwrap(),
+			PathBuf::from("s4.jar")
+		);
+	}
+
+	#[test]
+	fn idx_out_of_range() {
+		let jars = vec![PathBuf::from("s1.jar"), PathBuf::from("server.jar")];
+		assert!(get_jar_from_jars(&jars, 2, None).is_err());
+		assert!(get_jar_from_jars(&jars, 2, Some(1)).is_err());
+		assert!(get_jar_from_jars(&[], 0, None).is_err());
 	}
 
 	#[test]
@@ -212,23 +508,23 @@ mod tests {
 		];
 		let server_jar_idx = Some(2);
 		assert_eq!(
-			get_jar_from_jars(&jars, 0, server_jar_idx),
+			get_jar_from_jars(&jars, 0, server_jar_idx).unwrap(),
 			PathBuf::from("server.jar")
 		);
 		assert_eq!(
-			get_jar_from_jars(&jars, 1, server_jar_idx),
+			get_jar_from_jars(&jars, 1, server_jar_idx).unwrap(),
 			PathBuf::from("s1.jar")
 		);
 		assert_eq!(
-			get_jar_from_jars(&jars, 2, server_jar_idx),
+			get_jar_from_jars(&jars, 2, server_jar_idx).unwrap(),
 			PathBuf::from("s2.jar")
 		);
 		assert_eq!(
-			get_jar_from_jars(&jars, 3, server_jar_idx),
+			get_jar_from_jars(&jars, 3, server_jar_idx).unwrap(),
 			PathBuf::from("s3.jar")
 		);
 		assert_eq!(
-			get_jar_from_jars(&jars, 4, server_jar_idx),
+			get_jar_from_jars(&jars, 4, server_jar_idx).unwrap(),
 			PathBuf::from("s4.jar")
 		);
 	}
@@ -244,23 +540,23 @@ mod tests {
 		];
 		let server_jar_idx = Some(0);
 		assert_eq!(
-			get_jar_from_jars(&jars, 0, server_jar_idx),
+			get_jar_from_jars(&jars, 0, server_jar_idx).unwrap(),
 			PathBuf::from("server.jar")
 		);
 		assert_eq!(
-			get_jar_from_jars(&jars, 1, server_jar_idx),
+			get_jar_from_jars(&jars, 1, server_jar_idx).unwrap(),
 			PathBuf::from("s1.jar")
 		);
 		assert_eq!(
-			get_jar_from_jars(&jars, 2, server_jar_idx),
+			get_jar_from_jars(&jars, 2, server_jar_idx).unwrap(),
 			PathBuf::from("s2.jar")
 		);
 		assert_eq!(
-			get_jar_from_jars(&jars, 3, server_jar_idx),
+			get_jar_from_jars(&jars, 3, server_jar_idx).unwrap(),
 			PathBuf::from("s3.jar")
 		);
 		assert_eq!(
-			get_jar_from_jars(&jars, 4, server_jar_idx),
+			get_jar_from_jars(&jars, 4, server_jar_idx).unwrap(),
 			PathBuf::from("s4.jar")
 		);
 	}
@@ -276,23 +572,23 @@ mod tests {
 		];
 		let server_jar_idx = Some(4);
 		assert_eq!(
-			get_jar_from_jars(&jars, 0, server_jar_idx),
+			get_jar_from_jars(&jars, 0, server_jar_idx).unwrap(),
 			PathBuf::from("server.jar")
 		);
 		assert_eq!(
-			get_jar_from_jars(&jars, 1, server_jar_idx),
+			get_jar_from_jars(&jars, 1, server_jar_idx).unwrap(),
 			PathBuf::from("s1.jar")
 		);
 		assert_eq!(
-			get_jar_from_jars(&jars, 2, server_jar_idx),
+			get_jar_from_jars(&jars, 2, server_jar_idx).unwrap(),
 			PathBuf::from("s2.jar")
 		);
 		assert_eq!(
-			get_jar_from_jars(&jars, 3, server_jar_idx),
+			get_jar_from_jars(&jars, 3, server_jar_idx).unwrap(),
 			PathBuf::from("s3.jar")
 		);
 		assert_eq!(
-			get_jar_from_jars(&jars, 4, server_jar_idx),
+			get_jar_from_jars(&jars, 4, server_jar_idx).unwrap(),
 			PathBuf::from("s4.jar")
 		);
 	}
@@ -0,0 +1,35 @@
+use anyhow::{Context, Result};
+use log::*;
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+
+/// Runs a user supplied hook command, split like a shell would, in `working_directory`. The output
+/// of the hook is logged at `output_level`.
+pub fn run_hook(
+	command: &str,
+	working_directory: &Path,
+	envs: &[(&str, String)],
+	output_level: Level,
+) -> Result<ExitStatus> {
+	let words = shell_words::split(command)
+		.with_context(|| format!("Invalid hook command \"{}\"", command))?;
+	let (program, args) = match words.split_first() {
+		Some(v) => v,
+		None => anyhow::bail!("Hook command is empty"),
+	};
+
+	info!("Running hook \"{}\".", command);
+	let output = Command::new(program)
+		.args(args)
+		.current_dir(working_directory)
+		.envs(envs.iter().cloned())
+		.output()
+		.with_context(|| format!("Failed to run hook \"{}\"", command))?;
+	for line in String::from_utf8_lossy(&output.stdout).lines() {
+		log!(output_level, "[hook] {}", line);
+	}
+	for line in String::from_utf8_lossy(&output.stderr).lines() {
+		log!(output_level, "[hook] {}", line);
+	}
+	Ok(output.status)
+}
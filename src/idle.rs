@@ -0,0 +1,87 @@
+use crate::ping;
+use crate::server::ServerManager;
+use log::*;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Parses durations like `90`, `90s`, `15m` or `2h`. A plain number is in seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+	let s = s.trim();
+	let (number, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+	let number: u64 = number
+		.parse()
+		.map_err(|_| format!("Invalid duration \"{}\", expected e.g. 90s, 15m or 2h", s))?;
+	let seconds = match unit {
+		"" | "s" => number,
+		"m" => number * 60,
+		"h" => number * 60 * 60,
+		unit => return Err(format!("Unknown unit \"{}\", expected s, m or h", unit)),
+	};
+	Ok(Duration::from_secs(seconds))
+}
+
+/// Stops the server once nobody has been online for `idle_timeout`. Returns when the server
+/// exits. A failed ping counts as unknown: it neither stops the server nor resets the timer.
+pub fn watch(minecraft_server: &ServerManager, address: SocketAddr, idle_timeout: Duration) {
+	let mut empty_since: Option<Instant> = None;
+	loop {
+		std::thread::sleep(CHECK_INTERVAL);
+		if !minecraft_server.is_running() {
+			return;
+		}
+		if !minecraft_server.is_ready() {
+			empty_since = None;
+			continue;
+		}
+
+		let online = match ping::online_players(address, PING_TIMEOUT) {
+			Ok(v) => v,
+			Err(e) => {
+				debug!("Failed to ping the server: {:?}", e);
+				continue;
+			}
+		};
+		match (online, empty_since) {
+			(0, None) => {
+				info!(
+					"Server is empty, stopping it in {}s unless someone joins.",
+					idle_timeout.as_secs()
+				);
+				empty_since = Some(Instant::now());
+			}
+			(0, Some(since)) if since.elapsed() >= idle_timeout => {
+				info!(
+					"Server was empty for {}s, stopping it.",
+					idle_timeout.as_secs()
+				);
+				if let Err(e) = minecraft_server.stop() {
+					error!("Failed to stop the idle server: {:?}.", e);
+				}
+				return;
+			}
+			(0, Some(_)) => (),
+			(online, Some(_)) => {
+				info!("{} players online, idle shutdown cancelled.", online);
+				empty_since = None;
+			}
+			(_, None) => (),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn durations() {
+		assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+		assert_eq!(parse_duration("15m"), Ok(Duration::from_secs(15 * 60)));
+		assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(2 * 60 * 60)));
+		assert!(parse_duration("2d").is_err());
+		assert!(parse_duration("m").is_err());
+	}
+}
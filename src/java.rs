@@ -0,0 +1,204 @@
+use crate::error::RunnerError;
+use log::*;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::time::Duration;
+use wait_timeout::ChildExt;
+
+#[cfg(not(windows))]
+const JAVA: &str = "java";
+#[cfg(windows)]
+const JAVA: &str = "java.exe";
+
+#[cfg(not(windows))]
+pub fn find_java(probe_timeout: Duration) -> Result<PathBuf, RunnerError> {
+	if let Some(java) = find_java_in(JAVA.as_ref(), probe_timeout) {
+		return Ok(java);
+	}
+
+	let path: PathBuf = ["/usr/bin", JAVA].iter().collect();
+	find_java_in(&path, probe_timeout).ok_or(RunnerError::JavaNotFound)
+}
+
+#[cfg(windows)]
+pub fn find_java(probe_timeout: Duration) -> Result<PathBuf, RunnerError> {
+	use winapi::um::knownfolders::*;
+
+	if let Some(java) = find_java_in(JAVA.as_ref(), probe_timeout) {
+		return Ok(java);
+	}
+
+	let x86_program_files = winutils::get_known_folder(&FOLDERID_ProgramFilesX86)
+		.unwrap_or_else(|| String::from(r"C:\Progam Files (x86)"));
+
+	let bundled_jre: PathBuf = [
+		&x86_program_files,
+		"Minecraft Launcher",
+		"runtime",
+		"jre-x64",
+		"bin",
+		JAVA,
+	]
+	.iter()
+	.collect();
+
+	find_java_in(&bundled_jre, probe_timeout).ok_or(RunnerError::JavaNotFound)
+}
+
+/// Checks whether `place` is a working java by running `java -version`. A probe that doesn't
+/// finish within `timeout` is killed and treated as not found.
+pub fn find_java_in(place: &Path, timeout: Duration) -> Option<PathBuf> {
+	let output = run_version(place, timeout)?;
+	if output.status.success() {
+		Some(PathBuf::from(place))
+	} else {
+		debug!("{}", String::from_utf8_lossy(&output.stdout));
+		debug!("{}", String::from_utf8_lossy(&output.stderr));
+		None
+	}
+}
+
+/// Major version of the given java, e.g. 8 or 17.
+pub fn java_major(java: &Path, timeout: Duration) -> Option<u32> {
+	let output = run_version(java, timeout)?;
+	// java prints the version to stderr
+	parse_java_major(&String::from_utf8_lossy(&output.stderr))
+}
+
+fn run_version(place: &Path, timeout: Duration) -> Option<Output> {
+	let mut child = match Command::new(place)
+		.arg("-version")
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+	{
+		Ok(v) => v,
+		Err(e) => {
+			debug!("{:?}", e);
+			return None;
+		}
+	};
+
+	match child.wait_timeout(timeout) {
+		Ok(Some(_)) => (),
+		Ok(None) => {
+			debug!(
+				"\"{}\" -version didn't finish within {}s.",
+				place.display(),
+				timeout.as_secs_f32()
+			);
+			let _ = child.kill();
+			let _ = child.wait();
+			return None;
+		}
+		Err(e) => {
+			debug!("{:?}", e);
+			return None;
+		}
+	}
+
+	match child.wait_with_output() {
+		Ok(output) => Some(output),
+		Err(e) => {
+			debug!("{:?}", e);
+			None
+		}
+	}
+}
+
+/// Parses the major version out of `java -version` output. Handles both the old `1.8.0_301` and
+/// the new `17.0.1` format.
+pub fn parse_java_major(version_output: &str) -> Option<u32> {
+	let version = version_output
+		.split("version \"")
+		.nth(1)?
+		.split('"')
+		.next()?;
+	let mut parts = version.split(|c: char| !c.is_ascii_digit());
+	let major = match parts.next()? {
+		"1" => parts.next()?,
+		major => major,
+	};
+	major.parse().ok()
+}
+
+/// Lowest java major a Minecraft release runs on. Returns `None` for versions that aren't
+/// releases, e.g. snapshots.
+pub fn minimum_java_major(minecraft_version: &str) -> Option<u32> {
+	let mut parts = minecraft_version.split('.');
+	if parts.next()? != "1" {
+		return None;
+	}
+	let minor: u32 = parts.next()?.parse().ok()?;
+	let patch: u32 = match parts.next() {
+		Some(patch) => patch.parse().ok()?,
+		None => 0,
+	};
+	let java = match (minor, patch) {
+		(minor, _) if minor >= 21 => 21,
+		(20, patch) if patch >= 5 => 21,
+		(minor, _) if minor >= 18 => 17,
+		(17, _) => 16,
+		_ => 8,
+	};
+	Some(java)
+}
+
+#[cfg(windows)]
+mod winutils {
+	use winapi::{ctypes::c_void, shared::guiddef::GUID, um::shlobj::*};
+
+	pub fn get_known_folder(folder_id: &GUID) -> Option<String> {
+		let mut path: *mut u16 = std::ptr::null_mut();
+		let result = unsafe {
+			SHGetKnownFolderPath(
+				folder_id,
+				KF_FLAG_DEFAULT,
+				std::ptr::null_mut::<c_void>(),
+				&mut path as *mut *mut u16,
+			)
+		};
+		let path = unsafe {
+			let mut len = 0usize;
+			while *path.add(len) != 0 {
+				len += 1;
+			}
+			let path_str = String::from_utf16_lossy(std::slice::from_raw_parts_mut(path, len));
+			winapi::um::combaseapi::CoTaskMemFree(path as *mut c_void);
+			path_str
+		};
+		if result == 0 {
+			Some(path)
+		} else {
+			None
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_java_major() {
+		assert_eq!(
+			parse_java_major("java version \"1.8.0_301\"\nJava(TM) SE Runtime Environment"),
+			Some(8)
+		);
+		assert_eq!(
+			parse_java_major("openjdk version \"17.0.1\" 2021-10-19\nOpenJDK Runtime Environment"),
+			Some(17)
+		);
+		assert_eq!(parse_java_major("openjdk version \"16-ea\""), Some(16));
+		assert_eq!(parse_java_major("garbage"), None);
+	}
+
+	#[test]
+	fn minimum_java_for_minecraft() {
+		assert_eq!(minimum_java_major("1.16.5"), Some(8));
+		assert_eq!(minimum_java_major("1.17"), Some(16));
+		assert_eq!(minimum_java_major("1.18.1"), Some(17));
+		assert_eq!(minimum_java_major("1.20.5"), Some(21));
+		assert_eq!(minimum_java_major("21w44a"), None);
+	}
+}
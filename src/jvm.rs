@@ -0,0 +1,526 @@
+use crate::find_jar;
+use crate::settings::EffectiveSettings;
+use anyhow::Result;
+use log::*;
+use serde::Serialize;
+use std::net::IpAddr;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Server software that gets its own JVM flags.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ServerType {
+	Vanilla,
+	Paper,
+	Spigot,
+}
+
+impl ServerType {
+	fn flags(self) -> &'static [&'static str] {
+		match self {
+			ServerType::Vanilla => &[],
+			// Marks the JVM as running with Aikar's flags, which Paper and Spigot check for
+			ServerType::Paper | ServerType::Spigot => &[
+				"-Dusing.aikars.flags=https://mcflags.emc.gs",
+				"-Daikars.new.flags=true",
+			],
+		}
+	}
+}
+
+/// Detects Paper and Spigot by the jar name, the main class in the jar manifest and the paper
+/// config in the working directory.
+pub fn detect_server_type(working_directory: &Path, server_jar: &str) -> ServerType {
+	let jar_name = server_jar.to_lowercase();
+	let main_class = find_jar::read_main_class(&working_directory.join(server_jar))
+		.unwrap_or_default()
+		.to_lowercase();
+	if jar_name.starts_with("paper")
+		|| main_class.contains("papermc")
+		|| main_class.contains("paperclip")
+		|| working_directory.join("config/paper-global.yml").exists()
+	{
+		ServerType::Paper
+	} else if jar_name.starts_with("spigot") || main_class.contains("craftbukkit") {
+		ServerType::Spigot
+	} else {
+		ServerType::Vanilla
+	}
+}
+
+/// What java launches: an executable jar, or a main class on a classpath for distributions that
+/// don't ship one.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Launch {
+	/// File name of the jar in the working directory.
+	Jar(String),
+	MainClass {
+		classpath: String,
+		main_class: String,
+	},
+}
+
+impl Launch {
+	/// Builds a main class launch, checking that every classpath entry exists. Entries ending with
+	/// `*` (all the jars in a directory) only need the directory to exist.
+	pub fn main_class(working_directory: &Path, classpath: &str, main_class: &str) -> Result<Self> {
+		for entry in std::env::split_paths(classpath) {
+			let entry = working_directory.join(entry);
+			let path = match entry.file_name() {
+				Some(name) if name == "*" => entry.parent().unwrap_or(working_directory),
+				_ => entry.as_path(),
+			};
+			if !path.exists() {
+				anyhow::bail!("Classpath entry {:?} doesn't exist.", path);
+			}
+		}
+		Ok(Launch::MainClass {
+			classpath: classpath.to_string(),
+			main_class: main_class.to_string(),
+		})
+	}
+
+	pub fn jar(&self) -> Option<&str> {
+		match self {
+			Launch::Jar(jar) => Some(jar.as_str()),
+			Launch::MainClass { .. } => None,
+		}
+	}
+
+	fn args(&self) -> Vec<String> {
+		let args = match self {
+			Launch::Jar(jar) => vec!["-jar", jar.as_str()],
+			Launch::MainClass {
+				classpath,
+				main_class,
+			} => vec!["-cp", classpath.as_str(), main_class.as_str()],
+		};
+		args.into_iter().map(String::from).collect()
+	}
+}
+
+impl std::fmt::Display for Launch {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Launch::Jar(jar) => write!(f, "\"{}\"", jar),
+			Launch::MainClass { main_class, .. } => write!(f, "the main class {}", main_class),
+		}
+	}
+}
+
+/// Assembles the arguments java is launched with: JVM flags, the jar (or main class) and the
+/// server arguments. `extra_jvm_args` are the JVM flags enabled by the runner's options, they come
+/// after the configured ones.
+pub fn server_args(
+	settings: &EffectiveSettings,
+	working_directory: &Path,
+	launch: &Launch,
+	extra_jvm_args: &[String],
+) -> Vec<String> {
+	let mut args = memory_args(
+		settings.min_mib.value,
+		settings.max_mib.value,
+		&settings.jvm_args.value,
+	);
+	args.extend(
+		gc_flags(settings.gc.value, settings.experimental_flags.value)
+			.into_iter()
+			.map(String::from),
+	);
+	if let (true, Some(server_jar)) = (settings.server_type_flags.value, launch.jar()) {
+		let server_type = detect_server_type(working_directory, server_jar);
+		if server_type != ServerType::Vanilla {
+			info!("Detected a {:?} server.", server_type);
+		}
+		args.extend(server_type.flags().iter().map(|flag| flag.to_string()));
+	}
+	args.extend(settings.jvm_args.value.iter().cloned());
+	args.extend(extra_jvm_args.iter().cloned());
+	args.extend(launch.args());
+	args.push(String::from("nogui"));
+	if let Some(port) = &settings.port {
+		args.push(String::from("--port"));
+		args.push(port.value.to_string());
+	}
+	args.extend(settings.server_args.value.iter().cloned());
+	args
+}
+
+/// System properties that expose JMX on `port` for VisualVM or JConsole. Without `authenticate`
+/// anyone who can reach the port controls the JVM. With it, the JVM reads the users from
+/// `$JAVA_HOME/conf/management/jmxremote.password`.
+pub fn jmx_args(port: u16, host: Option<IpAddr>, authenticate: bool) -> Vec<String> {
+	let mut args = vec![
+		String::from("-Dcom.sun.management.jmxremote"),
+		format!("-Dcom.sun.management.jmxremote.port={}", port),
+		// RMI picks a random second port by default, which breaks firewalls and tunnels
+		format!("-Dcom.sun.management.jmxremote.rmi.port={}", port),
+		String::from("-Dcom.sun.management.jmxremote.ssl=false"),
+		format!(
+			"-Dcom.sun.management.jmxremote.authenticate={}",
+			authenticate
+		),
+	];
+	if let Some(host) = host {
+		args.push(format!("-Dcom.sun.management.jmxremote.host={}", host));
+		args.push(format!("-Djava.rmi.server.hostname={}", host));
+	}
+	args
+}
+
+/// Flags writing the GC log to `path`. Java 9+ uses unified logging, java 8 only knows the legacy
+/// flags. An unknown version is assumed to be a modern one.
+pub fn gc_log_args(path: &Path, java_major: Option<u32>) -> Vec<String> {
+	match java_major {
+		Some(major) if major < 9 => vec![
+			format!("-Xloggc:{}", path.display()),
+			String::from("-XX:+PrintGCDetails"),
+			String::from("-XX:+PrintGCDateStamps"),
+		],
+		_ => vec![format!(
+			"-Xlog:gc*:file={}:time,uptime,level,tags",
+			path.display()
+		)],
+	}
+}
+
+/// Flags making the JVM write a heap dump into `directory` when it runs out of memory.
+pub fn heap_dump_args(directory: &Path) -> Vec<String> {
+	vec![
+		String::from("-XX:+HeapDumpOnOutOfMemoryError"),
+		format!("-XX:HeapDumpPath={}", directory.display()),
+	]
+}
+
+/// Flags backing the heap with large pages. Linux uses transparent huge pages, other systems
+/// explicit large pages.
+pub fn large_pages_args() -> Vec<String> {
+	if cfg!(target_os = "linux") {
+		vec![String::from("-XX:+UseTransparentHugePages")]
+	} else if cfg!(windows) {
+		vec![
+			String::from("-XX:+UseLargePages"),
+			String::from("-XX:LargePageSizeInBytes=2m"),
+		]
+	} else {
+		vec![String::from("-XX:+UseLargePages")]
+	}
+}
+
+/// Explains why the JVM likely won't get large pages, when the OS setup is known to be missing.
+#[cfg(target_os = "linux")]
+pub fn large_pages_problem() -> Option<String> {
+	const THP_ENABLED: &str = "/sys/kernel/mm/transparent_hugepage/enabled";
+	match std::fs::read_to_string(THP_ENABLED) {
+		Ok(enabled) => match transparent_huge_pages_mode(&enabled) {
+			Some("always") | Some("madvise") => None,
+			Some(mode) => Some(format!(
+				"Transparent huge pages are set to \"{}\" in {}, set them to madvise",
+				mode, THP_ENABLED
+			)),
+			None => Some(format!(
+				"Unknown transparent huge pages mode in {}",
+				THP_ENABLED
+			)),
+		},
+		Err(_) => Some(String::from(
+			"The kernel doesn't support transparent huge pages",
+		)),
+	}
+}
+
+#[cfg(windows)]
+pub fn large_pages_problem() -> Option<String> {
+	// Can't tell without querying the privileges of the account
+	info!("Large pages need the \"Lock pages in memory\" privilege, granted in secpol.msc.");
+	None
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+pub fn large_pages_problem() -> Option<String> {
+	None
+}
+
+/// Selected mode of `/sys/kernel/mm/transparent_hugepage/enabled`, e.g. `always [madvise] never`.
+#[cfg(any(target_os = "linux", test))]
+fn transparent_huge_pages_mode(enabled: &str) -> Option<&str> {
+	let start = enabled.find('[')? + 1;
+	let end = start + enabled[start..].find(']')?;
+	Some(&enabled[start..end])
+}
+
+/// `-Xmx` and `-Xms` for the given sizes. A size already set in `jvm_args` is left out, otherwise
+/// the JVM would silently use whichever flag comes last.
+fn memory_args(min_mib: u64, max_mib: u64, jvm_args: &[String]) -> Vec<String> {
+	let mut args = Vec::new();
+	for (flag, mib) in [("-Xmx", max_mib), ("-Xms", min_mib)].iter() {
+		match jvm_args.iter().find(|arg| arg.starts_with(flag)) {
+			Some(user_arg) => warn!(
+				"Using \"{}\" from the jvm args instead of the computed {}{}.",
+				user_arg,
+				flag,
+				format_jvm_size(*mib)
+			),
+			None => args.push(format!("{}{}", flag, format_jvm_size(*mib))),
+		}
+	}
+	args
+}
+
+/// Garbage collector the server runs with, `g1` by default.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum GcProfile {
+	/// G1 with Aikar's tuning. The best fit for most servers, vanilla and modded, up to about
+	/// 12GB of heap.
+	G1,
+	/// ZGC keeps pauses under a millisecond whatever the heap size, at the cost of some CPU and
+	/// memory. For large modded servers with 16GB of heap or more, on java 15 or newer (11 to 14
+	/// only have it as an experimental option).
+	Zgc,
+	/// Shenandoah also collects concurrently with short pauses, with less overhead than ZGC on
+	/// mid-sized heaps (8GB and up). Java 12 or newer, and not every JDK build ships it (Oracle's
+	/// doesn't).
+	Shenandoah,
+}
+
+impl FromStr for GcProfile {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"g1" => Ok(GcProfile::G1),
+			"zgc" => Ok(GcProfile::Zgc),
+			"shenandoah" => Ok(GcProfile::Shenandoah),
+			_ => Err(format!(
+				"Unknown garbage collector \"{}\", expected g1, zgc or shenandoah",
+				s
+			)),
+		}
+	}
+}
+
+impl GcProfile {
+	/// Explains why the collector may not work on the given java version.
+	pub fn unsupported_reason(self, java_major: u32) -> Option<String> {
+		match self {
+			GcProfile::G1 => None,
+			GcProfile::Zgc if java_major < 11 => Some(format!(
+				"ZGC needs java 11 or newer, found java {}",
+				java_major
+			)),
+			GcProfile::Zgc if java_major < 15 => Some(format!(
+				"ZGC is experimental in java {}, it's production ready since java 15",
+				java_major
+			)),
+			GcProfile::Shenandoah if java_major < 12 => Some(format!(
+				"Shenandoah needs java 12 or newer (or a JDK build with it backported), found \
+				 java {}",
+				java_major
+			)),
+			_ => None,
+		}
+	}
+
+	fn flags(self) -> &'static [&'static str] {
+		match self {
+			GcProfile::G1 => G1_FLAGS,
+			GcProfile::Zgc => &[
+				"-Dsun.rmi.dgc.server.gcInterval=2147483646",
+				// Only needed before java 15
+				"-XX:+UnlockExperimentalVMOptions",
+				"-XX:+UseZGC",
+				"-XX:+ParallelRefProcEnabled",
+				"-server",
+			],
+			GcProfile::Shenandoah => &[
+				"-Dsun.rmi.dgc.server.gcInterval=2147483646",
+				"-XX:+UseShenandoahGC",
+				"-XX:+ParallelRefProcEnabled",
+				"-server",
+			],
+		}
+	}
+}
+
+/// Flags only accepted after `-XX:+UnlockExperimentalVMOptions`, the unlock flag included.
+const EXPERIMENTAL_FLAGS: &[&str] = &[
+	"-XX:+UnlockExperimentalVMOptions",
+	"-XX:G1NewSizePercent=30",
+	"-XX:G1MixedGCLiveThresholdPercent=90",
+];
+
+const G1_FLAGS: &[&str] = &[
+	"-Dsun.rmi.dgc.server.gcInterval=2147483646",
+	"-XX:+UseG1GC",
+	"-XX:+ParallelRefProcEnabled",
+	"-XX:MaxGCPauseMillis=50",
+	"-XX:+UnlockExperimentalVMOptions",
+	//"-XX:+DisableExplicitGC",
+	//"-XX:+AlwaysPreTouch",
+	"-XX:G1NewSizePercent=30",
+	//"-XX:G1MaxNewSizePercent=40",
+	"-XX:G1HeapRegionSize=32M",
+	"-XX:G1ReservePercent=20",
+	"-XX:G1HeapWastePercent=5",
+	"-XX:G1MixedGCCountTarget=4",
+	"-XX:InitiatingHeapOccupancyPercent=15",
+	"-XX:G1MixedGCLiveThresholdPercent=90",
+	"-XX:G1RSetUpdatingPauseTimePercent=5",
+	//"-XX:SurvivorRatio=32",
+	//"-XX:+PerfDisableSharedMem",
+	//"-XX:MaxTenuringThreshold=1",
+	"-server",
+];
+
+/// Tuning flags of the garbage collector. Without `experimental`, the flags depending on the
+/// experimental VM options are dropped.
+fn gc_flags(gc: GcProfile, experimental: bool) -> Vec<&'static str> {
+	let flags = gc.flags();
+	if experimental {
+		return flags.to_vec();
+	}
+	let (dropped, flags): (Vec<&str>, Vec<&str>) = flags
+		.iter()
+		.copied()
+		.partition(|flag| EXPERIMENTAL_FLAGS.contains(flag));
+	info!("Dropped experimental JVM flags: {}", dropped.join(" "));
+	flags
+}
+
+/// Formats a memory size for `-Xmx`/`-Xms`, using the `G` suffix when it's a whole number of GiB.
+pub fn format_jvm_size(mib: u64) -> String {
+	if mib != 0 && mib % 1024 == 0 {
+		format!("{}G", mib / 1024)
+	} else {
+		format!("{}M", mib)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn jvm_size_formatting() {
+		assert_eq!(format_jvm_size(1024), "1G");
+		assert_eq!(format_jvm_size(1536), "1536M");
+		assert_eq!(format_jvm_size(16384), "16G");
+	}
+
+	#[test]
+	fn computed_memory_flags() {
+		assert_eq!(memory_args(1024, 4096, &[]), vec!["-Xmx4G", "-Xms1G"]);
+	}
+
+	#[test]
+	fn user_memory_flags_take_precedence() {
+		let jvm_args = vec![String::from("-Xmx6G"), String::from("-Dfoo=bar")];
+		assert_eq!(memory_args(1024, 4096, &jvm_args), vec!["-Xms1G"]);
+
+		let jvm_args = vec![String::from("-Xms2G"), String::from("-Xmx8G")];
+		assert!(memory_args(1024, 4096, &jvm_args).is_empty());
+	}
+
+	#[test]
+	fn server_type_from_jar_name() {
+		let dir = Path::new("does-not-exist");
+		assert_eq!(
+			detect_server_type(dir, "paper-1.17.1-100.jar"),
+			ServerType::Paper
+		);
+		assert_eq!(
+			detect_server_type(dir, "spigot-1.16.5.jar"),
+			ServerType::Spigot
+		);
+		assert_eq!(detect_server_type(dir, "server.jar"), ServerType::Vanilla);
+	}
+
+	#[test]
+	fn main_class_launch_args() {
+		let launch = Launch::MainClass {
+			classpath: String::from("libraries/*"),
+			main_class: String::from("net.fabricmc.loader.Main"),
+		};
+		assert_eq!(
+			launch.args(),
+			vec!["-cp", "libraries/*", "net.fabricmc.loader.Main"]
+		);
+		assert_eq!(
+			Launch::Jar(String::from("server.jar")).args(),
+			vec!["-jar", "server.jar"]
+		);
+	}
+
+	#[test]
+	fn missing_classpath_entry_is_rejected() {
+		let dir = std::env::temp_dir();
+		assert!(Launch::main_class(&dir, "does-not-exist.jar", "Main").is_err());
+		assert!(Launch::main_class(&dir, "*", "Main").is_ok());
+	}
+
+	#[test]
+	fn jmx_flags() {
+		let args = jmx_args(9010, None, false);
+		assert!(args.contains(&String::from("-Dcom.sun.management.jmxremote.port=9010")));
+		assert!(args.contains(&String::from(
+			"-Dcom.sun.management.jmxremote.authenticate=false"
+		)));
+		let args = jmx_args(9010, Some("127.0.0.1".parse().unwrap()), true);
+		assert!(args.contains(&String::from(
+			"-Dcom.sun.management.jmxremote.host=127.0.0.1"
+		)));
+	}
+
+	#[test]
+	fn gc_log_flags_by_java_version() {
+		let path = Path::new("logs/gc.log");
+		assert_eq!(
+			gc_log_args(path, Some(17)),
+			vec!["-Xlog:gc*:file=logs/gc.log:time,uptime,level,tags"]
+		);
+		assert_eq!(gc_log_args(path, Some(8))[0], "-Xloggc:logs/gc.log");
+		assert_eq!(gc_log_args(path, None), gc_log_args(path, Some(11)));
+	}
+
+	#[test]
+	fn experimental_flags_can_be_dropped() {
+		let flags = gc_flags(GcProfile::G1, false);
+		assert!(flags.contains(&"-XX:+UseG1GC"));
+		assert!(!flags.iter().any(|flag| EXPERIMENTAL_FLAGS.contains(flag)));
+		assert!(EXPERIMENTAL_FLAGS
+			.iter()
+			.all(|flag| gc_flags(GcProfile::G1, true).contains(flag)));
+	}
+
+	#[test]
+	fn transparent_huge_pages_modes() {
+		assert_eq!(
+			transparent_huge_pages_mode("always [madvise] never\n"),
+			Some("madvise")
+		);
+		assert_eq!(
+			transparent_huge_pages_mode("always madvise [never]\n"),
+			Some("never")
+		);
+		assert_eq!(transparent_huge_pages_mode("madvise\n"), None);
+	}
+
+	#[test]
+	fn gc_profiles() {
+		let zgc = gc_flags(GcProfile::Zgc, true);
+		assert!(zgc.contains(&"-XX:+UseZGC"));
+		assert!(!zgc.contains(&"-XX:+UseG1GC"));
+		assert!(!gc_flags(GcProfile::Zgc, false).contains(&"-XX:+UnlockExperimentalVMOptions"));
+		assert!(gc_flags(GcProfile::Shenandoah, true).contains(&"-XX:+UseShenandoahGC"));
+
+		assert_eq!(GcProfile::G1.unsupported_reason(8), None);
+		assert!(GcProfile::Zgc.unsupported_reason(8).is_some());
+		assert!(GcProfile::Zgc.unsupported_reason(11).is_some());
+		assert_eq!(GcProfile::Zgc.unsupported_reason(17), None);
+		assert!(GcProfile::Shenandoah.unsupported_reason(11).is_some());
+		assert_eq!(GcProfile::Shenandoah.unsupported_reason(17), None);
+		assert_eq!("zgc".parse(), Ok(GcProfile::Zgc));
+		assert!("cms".parse::<GcProfile>().is_err());
+	}
+}
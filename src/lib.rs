@@ -0,0 +1,57 @@
+//! The reusable parts of minecraft_runner: finding java and the server jar, the config, and
+//! [`ServerManager`] owning the server process. The `minecraft_runner` binary is the command line
+//! interface built on top of them.
+//!
+//! ```no_run
+//! use minecraft_runner::{find_java, find_server_jar, FindServerJar, ServerManager};
+//! use std::path::Path;
+//! use std::time::Duration;
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let java = find_java(Duration::from_secs(10))?;
+//! let directory = Path::new("server");
+//! if let FindServerJar::ServerJar(jar) = find_server_jar(directory, &[])? {
+//! 	let args = vec![String::from("-jar"), jar.display().to_string(), String::from("nogui")];
+//! 	let server = ServerManager::new(java, args, directory.to_path_buf());
+//! 	server.spawn()?;
+//! 	server.wait()?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+#[cfg(feature = "async")]
+pub mod async_server;
+pub mod cgroup;
+pub mod color;
+pub mod config;
+pub mod crash;
+pub mod disk;
+pub mod docker;
+pub mod error;
+pub mod events;
+pub mod find_jar;
+pub mod hooks;
+pub mod java;
+pub mod lock;
+pub mod logs;
+pub mod mods;
+pub mod pidfile;
+pub mod ping;
+pub mod players;
+pub mod priority;
+#[cfg(unix)]
+pub mod privileges;
+pub mod properties;
+pub mod server;
+pub mod vanilla;
+pub mod webhook;
+pub mod world;
+
+#[cfg(feature = "async")]
+pub use crate::async_server::AsyncServerManager;
+pub use crate::config::MinecraftRunnerConfig;
+pub use crate::error::RunnerError;
+pub use crate::find_jar::{find_server_jar, FindServerJar};
+pub use crate::java::find_java;
+pub use crate::server::ServerManager;
@@ -0,0 +1,87 @@
+use anyhow::{Context, Result};
+use fs2::FileExt;
+use log::*;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+pub const LOCK_FILENAME: &str = "minecraft_runner.lock";
+
+/// Exclusive lock on the server directory, held for as long as the runner is alive so that two
+/// servers never write to the same world. The OS releases it when the file is closed, even if
+/// the runner crashes.
+pub struct InstanceLock {
+	file: File,
+}
+
+impl InstanceLock {
+	/// Locks the directory and writes the runner PID into the lock file. With `force`, a lock held
+	/// by another runner is only warned about.
+	pub fn acquire(working_directory: &Path, force: bool) -> Result<Option<Self>> {
+		let path = working_directory.join(LOCK_FILENAME);
+		let mut file = OpenOptions::new()
+			.read(true)
+			.write(true)
+			.create(true)
+			.open(&path)
+			.with_context(|| format!("Path: {:?}", path))?;
+
+		if file.try_lock_exclusive().is_err() {
+			let mut pid = String::new();
+			let _ = file.read_to_string(&mut pid);
+			let holder = match pid.trim() {
+				"" => String::from("another runner"),
+				pid => format!("the runner with PID {}", pid),
+			};
+			if force {
+				warn!(
+					"{} is locked by {}, launching anyway because of --force.",
+					working_directory.display(),
+					holder
+				);
+				return Ok(None);
+			}
+			anyhow::bail!(
+				"{} is already used by {}. Running two servers in the same directory corrupts \
+				 the world, pass --force if you are sure it isn't running.",
+				working_directory.display(),
+				holder
+			);
+		}
+
+		file.set_len(0)?;
+		file.seek(SeekFrom::Start(0))?;
+		write!(file, "{}", std::process::id())?;
+		file.flush()?;
+		Ok(Some(InstanceLock { file }))
+	}
+}
+
+impl Drop for InstanceLock {
+	fn drop(&mut self) {
+		let _ = self.file.set_len(0);
+		let _ = self.file.unlock();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn second_lock_is_rejected() {
+		let dir =
+			std::env::temp_dir().join(format!("minecraft_runner_lock_{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+
+		let lock = InstanceLock::acquire(&dir, false).unwrap();
+		assert!(lock.is_some());
+		let err = InstanceLock::acquire(&dir, false).err().unwrap();
+		assert!(err.to_string().contains(&std::process::id().to_string()));
+		assert!(InstanceLock::acquire(&dir, true).unwrap().is_none());
+
+		drop(lock);
+		assert!(InstanceLock::acquire(&dir, false).unwrap().is_some());
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -0,0 +1,78 @@
+use crate::color::ColorChoice;
+use env_logger::WriteStyle;
+use std::io::Write;
+use std::str::FromStr;
+
+/// Precision of the timestamp at the start of every log line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogTimestamps {
+	None,
+	Seconds,
+	Millis,
+}
+
+impl FromStr for LogTimestamps {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"none" => Ok(LogTimestamps::None),
+			"seconds" => Ok(LogTimestamps::Seconds),
+			"millis" => Ok(LogTimestamps::Millis),
+			_ => Err(format!(
+				"Unknown timestamp precision \"{}\", expected none, seconds or millis",
+				s
+			)),
+		}
+	}
+}
+
+/// Sets up logging with the server name in every line, so the output of several runners can be
+/// told apart. The level still comes from `RUST_LOG`, the level is colored unless `color` is
+/// disabled.
+pub fn init_logger(name: &str, timestamps: LogTimestamps, target: bool, color: ColorChoice) {
+	let name = name.to_string();
+	env_logger::Builder::from_default_env()
+		.write_style(if color.enabled() {
+			WriteStyle::Always
+		} else {
+			WriteStyle::Never
+		})
+		.format(move |buf, record| {
+			let timestamp = match timestamps {
+				LogTimestamps::None => String::new(),
+				LogTimestamps::Seconds => format!("{} ", buf.timestamp_seconds()),
+				LogTimestamps::Millis => format!("{} ", buf.timestamp_millis()),
+			};
+			let target = if target {
+				format!(" {}", record.target())
+			} else {
+				String::new()
+			};
+			// The style is applied while writing to `buf`, so the level can't be formatted into a
+			// string first
+			let level = buf.default_styled_level(record.level());
+			writeln!(
+				buf,
+				"[{}{:<5}{}] [{}] {}",
+				timestamp,
+				level,
+				target,
+				name,
+				record.args()
+			)
+		})
+		.init();
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_timestamps() {
+		assert_eq!("none".parse(), Ok(LogTimestamps::None));
+		assert_eq!("millis".parse(), Ok(LogTimestamps::Millis));
+		assert!("minutes".parse::<LogTimestamps>().is_err());
+	}
+}
@@ -0,0 +1,88 @@
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+const CHUNK_SIZE: u64 = 8 * 1024;
+
+/// Log the vanilla server writes for the current (or, before launching, the previous) session.
+pub fn latest_log_path(working_directory: &Path) -> PathBuf {
+	working_directory.join("logs").join("latest.log")
+}
+
+/// Returns the last `lines` lines of the file. The file is read backwards in chunks, so only the
+/// tail is loaded into memory.
+pub fn tail(path: &Path, lines: usize) -> io::Result<Vec<String>> {
+	let mut file = std::fs::File::open(path)?;
+	read_tail(&mut file, lines, CHUNK_SIZE)
+}
+
+fn read_tail<R: Read + Seek>(
+	reader: &mut R,
+	lines: usize,
+	chunk_size: u64,
+) -> io::Result<Vec<String>> {
+	if lines == 0 {
+		return Ok(Vec::new());
+	}
+
+	let mut pos = reader.seek(SeekFrom::End(0))?;
+	let mut buf: Vec<u8> = Vec::new();
+
+	while pos > 0 {
+		let read_size = chunk_size.min(pos);
+		pos -= read_size;
+		reader.seek(SeekFrom::Start(pos))?;
+		let mut chunk = vec![0; read_size as usize];
+		reader.read_exact(&mut chunk)?;
+		chunk.extend_from_slice(&buf);
+		buf = chunk;
+
+		// A trailing newline doesn't start a new line. With `lines` newlines before the end, the
+		// last `lines` lines are complete.
+		let content = buf.strip_suffix(b"\n").unwrap_or(&buf);
+		if content.iter().filter(|b| **b == b'\n').count() >= lines {
+			break;
+		}
+	}
+
+	let text = String::from_utf8_lossy(&buf);
+	let all_lines: Vec<&str> = text.lines().collect();
+	let start = all_lines.len().saturating_sub(lines);
+	Ok(all_lines[start..]
+		.iter()
+		.map(|line| line.to_string())
+		.collect())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::io::Cursor;
+
+	fn tail_of(text: &str, lines: usize, chunk_size: u64) -> Vec<String> {
+		read_tail(&mut Cursor::new(text.as_bytes()), lines, chunk_size).unwrap()
+	}
+
+	#[test]
+	fn last_lines() {
+		let text = "one\ntwo\nthree\nfour\n";
+		for chunk_size in 1..=text.len() as u64 {
+			assert_eq!(tail_of(text, 2, chunk_size), vec!["three", "four"]);
+		}
+	}
+
+	#[test]
+	fn without_trailing_newline() {
+		assert_eq!(tail_of("one\ntwo\nthree", 2, 3), vec!["two", "three"]);
+	}
+
+	#[test]
+	fn shorter_than_requested() {
+		assert_eq!(tail_of("one\ntwo\n", 10, 4), vec!["one", "two"]);
+		assert!(tail_of("", 10, 4).is_empty());
+	}
+
+	#[test]
+	fn zero_lines() {
+		assert!(tail_of("one\ntwo\n", 0, 4).is_empty());
+	}
+}
@@ -1,77 +1,1631 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use log::*;
+use std::collections::HashMap;
 use std::env::{current_exe, set_current_dir};
 use std::ffi::OsStr;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::ExitStatus;
+use std::sync::Arc;
+use std::time::Duration;
 use structopt::StructOpt;
 
-mod find_jar;
-#[cfg(target_feature = "webserver")]
+mod config_watch;
+mod console;
+#[cfg(unix)]
+mod control;
+mod doctor;
+mod env_info;
+mod idle;
+mod jvm;
+mod logger;
+#[cfg(feature = "webserver")]
+mod metrics;
+mod multi;
+mod prelaunch;
+#[cfg(feature = "webserver")]
+mod rate_limit;
+mod settings;
+#[cfg(unix)]
+mod signals;
+#[cfg(windows)]
+mod title;
+#[cfg(unix)]
+mod tmux;
+#[cfg(feature = "webserver")]
 mod webserver;
 
+#[cfg(unix)]
+use minecraft_runner::privileges;
+use minecraft_runner::{
+	cgroup, color, config, crash, disk, docker, error, events, find_jar, hooks, java, lock, logs,
+	mods, pidfile, ping, players, priority, properties, server, vanilla, webhook, world,
+};
+
+use crate::config::MinecraftRunnerConfig;
+use crate::error::RunnerError;
 use crate::find_jar::FindServerJar;
+use crate::server::ServerManager;
+use crate::settings::{EffectiveSettings, EnvSettings};
 
-#[cfg(target_feature = "webserver")]
+#[cfg(feature = "webserver")]
 use webserver::*;
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "minecraft_runner", author = "aQaTL")]
 struct Opt {
+	/// Initial JVM heap size, or a percentage of the memory available (the cgroup limit in a
+	/// container, the host RAM otherwise), e.g. 25% [default: 1GiB]
+	#[structopt(long)]
+	min: Option<settings::MemorySize>,
+	/// Maximum JVM heap size, or a percentage of the memory available, e.g. 75% [default: 16GiB]
+	#[structopt(long)]
+	max: Option<settings::MemorySize>,
+	/// Name of the config profile to use settings from
+	#[structopt(long)]
+	profile: Option<String>,
+	/// Path to the config file (.ron or .toml) [default: minecraft_runner_config.ron next to the
+	/// executable]
+	#[structopt(long, parse(from_os_str))]
+	config: Option<PathBuf>,
+	/// Read the config (RON or TOML) from stdin instead of a file. Nothing is written back, and
+	/// multiple jars can't be chosen from on the console
+	#[structopt(long, conflicts_with = "config")]
+	config_stdin: bool,
+	/// Reload the config when it changes. Commands are updated right away, the memory and JVM
+	/// arguments on the next restart
+	#[structopt(long, conflicts_with = "config-stdin")]
+	watch_config: bool,
+	/// Name of the server shown in logs, notifications and the status endpoint [default: name
+	/// of the working directory]
+	#[structopt(long)]
+	name: Option<String>,
+	/// Port the server listens on
+	#[structopt(long)]
+	port: Option<u16>,
+	/// Address of the interface the server binds to (written to server.properties as server-ip).
+	/// An empty value binds all interfaces
+	#[structopt(long, value_name = "ADDR")]
+	bind_ip: Option<String>,
+	/// Let the server listen on a random free port (written to server.properties)
+	#[structopt(long, conflicts_with = "port")]
+	random_port: bool,
+	/// Garbage collector: g1 (the default, fits most servers), zgc (large heaps, java 15+) or
+	/// shenandoah (mid-sized heaps, java 12+) [default: g1]
+	#[structopt(long, value_name = "GC")]
+	gc: Option<jvm::GcProfile>,
+	/// Back the heap with large pages: transparent huge pages on Linux (set to madvise in
+	/// /sys/kernel/mm/transparent_hugepage/enabled), -XX:+UseLargePages elsewhere. Needs OS setup
+	#[structopt(long)]
+	large_pages: bool,
+	/// Leave out -XX:+UnlockExperimentalVMOptions and the flags depending on it, for JVMs that
+	/// reject them
+	#[structopt(long)]
+	no_experimental_flags: bool,
+	/// Don't add the flags for Paper and Spigot servers, e.g. for forks that don't want them
+	#[structopt(long)]
+	no_server_type_flags: bool,
+	/// World directory to launch (written to server.properties as level-name)
+	#[structopt(long, value_name = "NAME")]
+	world: Option<String>,
+	/// Launch a single jar not named server.jar without asking, when there's no terminal to
+	/// confirm it on
+	#[structopt(long, default_value = "true", value_name = "BOOL", parse(try_from_str))]
+	allow_unknown_jar: bool,
+	/// Seed of the world generated on the first run (written to server.properties as level-seed)
+	#[structopt(long)]
+	seed: Option<String>,
+	/// Warn before launching when there's less free disk space than this
 	#[structopt(long, default_value = "1GiB")]
-	min: human_size::SpecificSize,
-	#[structopt(long, default_value = "16GiB")]
-	max: human_size::SpecificSize,
+	min_free_space: human_size::SpecificSize,
+	/// Refuse to launch when the free disk space check fails
+	#[structopt(long)]
+	strict_disk: bool,
+	/// How many seconds to wait for `java -version` when looking for java
+	#[structopt(long, default_value = "10")]
+	java_probe_timeout: u64,
+	/// Launch snapshot and pre-release jars without asking for confirmation
+	#[structopt(long)]
+	allow_snapshot: bool,
+	/// Kill the server when it doesn't finish loading within SECS of being launched, 0 disables
+	/// the timeout
+	#[structopt(long, value_name = "SECS", default_value = "0")]
+	startup_timeout: u64,
+	/// Wait SECS after the server finishes loading before sending the startup commands
+	#[structopt(long, value_name = "SECS", default_value = "0")]
+	startup_delay: u64,
+	/// Console commands written to the server once it finishes loading, one per line. Blank lines
+	/// and lines starting with # are skipped. The console is still forwarded afterwards
+	#[structopt(long, value_name = "PATH", parse(from_os_str))]
+	stdin_file: Option<PathBuf>,
+	/// Milliseconds between the commands of --stdin-file
+	#[structopt(long, value_name = "MILLIS", default_value = "100")]
+	stdin_file_delay: u64,
+	/// Launch the server again when it's killed by --startup-timeout instead of exiting
+	#[structopt(long)]
+	auto_restart: bool,
+	/// Tell the players why the server restarts (scheduled, health check, manual or crash) with
+	/// a say before stopping it. The messages can be changed with restart_messages in the config
+	#[structopt(long)]
+	announce_restart_reason: bool,
+	/// Run the server exactly once and exit with its exit code, refusing any restart. Meant for
+	/// CI and test harnesses managing the lifecycle themselves
+	#[structopt(long)]
+	once: bool,
+	/// Upgrade all the chunks of the world to the format of the server version (vanilla
+	/// --forceUpgrade). The server exiting afterwards isn't treated as a crash
+	#[structopt(long)]
+	force_upgrade: bool,
+	/// Erase the cached lighting and other data, best combined with --force-upgrade (vanilla
+	/// --eraseCache)
+	#[structopt(long)]
+	erase_cache: bool,
+	/// Launch the server in demo mode (vanilla --demo)
+	#[structopt(long)]
+	demo: bool,
+	/// Stop the server once nobody has been online for DURATION, e.g. 30m. A stopped server stays
+	/// down, wake the machine up (e.g. with Wake-on-LAN) and launch the runner again to bring it
+	/// back
+	#[structopt(long, value_name = "DURATION", parse(try_from_str = idle::parse_duration))]
+	idle_shutdown: Option<Duration>,
+	/// Refuse to launch when the java version is known to be incompatible with the server jar
+	#[structopt(long)]
+	strict_java: bool,
+	/// Look for java again instead of using the path cached in the config
+	#[structopt(long)]
+	refresh_java: bool,
+	/// Log the installed plugins and mods before launching
+	#[structopt(long)]
+	list_mods: bool,
+	/// Print the last N lines of the previous session's log before launching
+	#[structopt(long, value_name = "N")]
+	tail: Option<usize>,
+	/// Discord/Slack compatible webhook notified when the server crashes
+	#[structopt(long, value_name = "WEBHOOK")]
+	notify_url: Option<String>,
+	/// Also notify the webhook when a player joins or leaves
+	#[structopt(long, requires = "notify-url")]
+	notify_players: bool,
+	/// When the server crashes, copy its crash reports and the end of its log into
+	/// diagnostics/<timestamp>/
+	#[structopt(long)]
+	collect_crashes: bool,
+	/// When there are no jars, download the vanilla server jar of the given version (latest
+	/// release by default)
+	#[structopt(long, value_name = "VERSION")]
+	download_vanilla: Option<Option<String>>,
+	/// How many seconds to wait for a jar to be chosen over the webserver when there's no
+	/// terminal to ask on
+	#[cfg(feature = "webserver")]
+	#[structopt(long, default_value = "600")]
+	jar_selection_timeout: u64,
+	/// Read console input with a prompt, command history and tab completion. Ctrl-D stops the
+	/// server
+	#[structopt(long)]
+	console: bool,
+	/// Command run before the server is launched, with the jar in MINECRAFT_JAR and the working
+	/// directory in MINECRAFT_WORKING_DIRECTORY. The launch is aborted when it fails
+	#[structopt(long, value_name = "CMD")]
+	on_start: Option<String>,
+	/// Launch the server even when the --on-start hook fails
+	#[structopt(long)]
+	ignore_hook_failure: bool,
+	/// Command run after the server exits, with the exit code in MINECRAFT_EXIT_CODE
+	#[structopt(long, value_name = "CMD")]
+	on_exit: Option<String>,
+	/// Also write the server's stdout to this file
+	#[structopt(long, value_name = "FILE", parse(from_os_str))]
+	stdout_log: Option<PathBuf>,
+	/// Capture the server's stderr separately and also write it to this file
+	#[structopt(long, value_name = "FILE", parse(from_os_str))]
+	stderr_log: Option<PathBuf>,
+	/// Log the server output through the runner's logger at the level of each line (INFO, WARN,
+	/// ERROR, ...) instead of passing it through as is
+	#[structopt(long)]
+	parse_server_levels: bool,
+	/// Prefix the server output lines that don't start with a bracketed time with the current
+	/// time, in the log files only (log) or also on the console (all) [default: log]
+	#[structopt(long, value_name = "OUTPUT")]
+	timestamp_output: Option<Option<server::TimestampOutput>>,
+	/// Limit the webserver endpoints that control the server per client IP, e.g. 10/min
+	#[cfg(feature = "webserver")]
+	#[structopt(long, value_name = "N/UNIT")]
+	web_rate_limit: Option<rate_limit::RateLimit>,
+	/// PEM certificate chain to serve the webserver over TLS with
+	#[cfg(feature = "webserver")]
+	#[structopt(
+		long,
+		value_name = "FILE",
+		parse(from_os_str),
+		requires = "web-tls-key"
+	)]
+	web_tls_cert: Option<PathBuf>,
+	/// PEM private key of the webserver certificate
+	#[cfg(feature = "webserver")]
+	#[structopt(
+		long,
+		value_name = "FILE",
+		parse(from_os_str),
+		requires = "web-tls-cert"
+	)]
+	web_tls_key: Option<PathBuf>,
+	/// Extra arguments forwarded verbatim to AutoIpMinecraft.jar after server.properties, split
+	/// like a shell would. Overrides auto_ip_args from the config
+	#[structopt(long, value_name = "ARGS")]
+	auto_ip_args: Option<String>,
+	/// Console command sent once the server finishes loading (can be repeated)
+	#[structopt(long = "startup-command", number_of_values = 1)]
+	startup_commands: Vec<String>,
+	/// Console command stopping the server instead of `stop`. Repeat it to send several commands
+	/// in order, e.g. `save-all` before `stop`
+	#[structopt(long = "stop-command", number_of_values = 1)]
+	stop_commands: Vec<String>,
+	/// Niceness of the server process, from -20 (highest priority) to 19 (lowest) (Unix only)
+	#[cfg(unix)]
+	#[structopt(long, allow_hyphen_values = true)]
+	nice: Option<priority::Nice>,
+	/// Priority class of the server process: idle, below-normal, normal, above-normal or high
+	/// (Windows only)
+	#[cfg(windows)]
+	#[structopt(long)]
+	priority: Option<priority::PriorityClass>,
+	/// Launch the server as the given user, e.g. after binding a privileged port as root (Unix
+	/// only)
+	#[cfg(unix)]
+	#[structopt(long, value_name = "USER")]
+	run_as: Option<String>,
+	/// Launch every server found in the subdirectories of DIR (directories with a jar or an
+	/// eula.txt)
+	#[structopt(long, value_name = "DIR", parse(from_os_str))]
+	multi: Option<PathBuf>,
+	/// Launch java in a container of the Docker IMAGE instead of directly. The image has to
+	/// provide java, the server directory is mounted as the working directory and the port is
+	/// published. The server is stopped with `docker stop`
+	#[structopt(long, value_name = "IMAGE", conflicts_with = "multi")]
+	docker: Option<String>,
+	/// Launch the server in a detached tmux session with the given name instead of as a child
+	/// process. The runner only forwards the console to it, so /restart can't reach it and the
+	/// flags managing the server process are refused (Unix only)
+	#[cfg(unix)]
+	#[structopt(
+		long,
+		value_name = "SESSION",
+		conflicts_with_all = &[
+			"console", "startup-command", "stop-command", "nice", "run-as", "notify-url", "docker",
+			"stdin-file", "auto-restart", "announce-restart-reason", "once", "idle-shutdown",
+			"on-exit", "stdout-log", "stderr-log", "parse-server-levels", "timestamp-output",
+			"collect-crashes", "watch-config", "control-fifo", "control-socket",
+		]
+	)]
+	tmux: Option<String>,
+	/// Create the minecraft_runner.control FIFO in the server directory. Lines written to it are
+	/// forwarded to the server's console, e.g. by the say subcommand (Unix only)
+	#[cfg(unix)]
+	#[structopt(long)]
+	control_fifo: bool,
+	/// Show the server name and status (starting, running, players online) in the console
+	/// window title (Windows only)
+	#[cfg(windows)]
+	#[structopt(long)]
+	set_title: bool,
+	/// Listen on a Unix domain socket at PATH. Lines received are forwarded to the server's
+	/// console and the server's output is streamed back, e.g. `nc -U PATH` (Unix only)
+	#[cfg(unix)]
+	#[structopt(long, value_name = "PATH", parse(from_os_str))]
+	control_socket: Option<PathBuf>,
+	/// Console command sent to the server when the runner receives SIGHUP (Unix only)
+	#[cfg(unix)]
+	#[structopt(long, default_value = "reload")]
+	reload_command: String,
+	/// Print the output of subcommands as JSON
+	#[structopt(long, global = true)]
+	json: bool,
+	/// Timestamp precision of the runner's log lines: none, seconds or millis. Use none when the
+	/// output already gets timestamped, e.g. by journald
+	#[structopt(long, value_name = "PRECISION", default_value = "seconds")]
+	log_timestamps: logger::LogTimestamps,
+	/// Whether to color the output: auto, always or never. Auto colors it when it goes to a
+	/// terminal and NO_COLOR isn't set
+	#[structopt(long, value_name = "WHEN", default_value = "auto", global = true)]
+	color: color::ColorChoice,
+	/// Whether the log lines include the module they come from
+	#[structopt(long, value_name = "BOOL", default_value = "true", parse(try_from_str))]
+	log_target: bool,
+	/// Expose JMX on the given port to attach VisualVM or JConsole
+	#[structopt(long, value_name = "PORT")]
+	jmx_port: Option<u16>,
+	/// Require a JMX login, with the users from the jmxremote.password file of the java
+	/// installation
+	#[structopt(long, requires = "jmx-port")]
+	jmx_auth: bool,
+	/// Write the GC log of the server to the given file
+	#[structopt(long, value_name = "PATH", parse(from_os_str))]
+	gc_log: Option<PathBuf>,
+	/// Write a heap dump into DIR (heap-dumps by default) when the server runs out of memory
+	#[structopt(long, value_name = "DIR", parse(from_os_str))]
+	heap_dump_on_oom: Option<Option<PathBuf>>,
+	/// Processors the JVM sizes its GC and thread pools for. Detected from the cgroup CPU quota on
+	/// Linux when not given
+	#[structopt(long, value_name = "N")]
+	cpu_count: Option<std::num::NonZeroU32>,
+	/// Classpath to launch MAIN_CLASS from instead of a jar, separated like the PATH variable.
+	/// Skips the jar discovery
+	#[structopt(long, value_name = "PATHS", requires = "main-class")]
+	classpath: Option<String>,
+	/// Main class launched from --classpath
+	#[structopt(long, value_name = "MAIN_CLASS", requires = "classpath")]
+	main_class: Option<String>,
+	/// How many times the server directory is listed when no jar is found or listing it fails,
+	/// e.g. on a network mount that hasn't settled yet
+	#[structopt(long, value_name = "N", default_value = "3")]
+	jar_list_attempts: u32,
+	/// Seconds between the --jar-list-attempts
+	#[structopt(long, value_name = "SECS", default_value = "1")]
+	jar_list_retry_delay: u64,
+	/// Launch even when another runner holds the lock on the server directory
+	#[structopt(long)]
+	force: bool,
+	/// Terminate the server JVM left running by a runner that was killed, found through the
+	/// pidfile, before launching. No pidfile is written with --docker
+	#[structopt(long)]
+	kill_orphans: bool,
+	/// Don't write anything to the server directory or the config: no lock file, no remembered
+	/// jar or java path. Turned on by itself when the server directory isn't writable
+	#[structopt(long)]
+	read_only: bool,
+	/// Don't remember the jar chosen when there are several, ask again next time
+	#[structopt(long)]
+	no_save_jar: bool,
+	#[structopt(subcommand)]
+	cmd: Option<SubCommand>,
+}
+
+#[derive(Debug, StructOpt)]
+enum SubCommand {
+	/// Print the effective settings after merging defaults, config, env vars and flags
+	PrintConfig,
+	/// List the jars in the server directory
+	ListJars,
+	/// List the worlds (directories with a level.dat) in the server directory. The one of
+	/// level-name is marked with *
+	ListWorlds,
+	/// Print the runner, java and system details to include in bug reports
+	EnvInfo,
+	/// Check java, the jar, the EULA, disk space, memory, the port and the config without
+	/// launching. Exits with 1 when any check fails
+	Doctor,
+	/// Print the full command used to launch the server, one argument per line
+	ShowCommand {
+		/// Print a single line, quoted for bash
+		#[structopt(long)]
+		shell: bool,
+	},
+	/// Ping the server with the Server List Ping, like the multiplayer menu, and print its
+	/// version, players and MOTD
+	Ping {
+		/// Address of the server [default: the one in this directory]
+		#[structopt(long)]
+		address: Option<SocketAddr>,
+		/// Seconds to wait for the server to answer
+		#[structopt(long, default_value = "5")]
+		timeout: u64,
+	},
+	/// Manage the players in whitelist.json
+	Whitelist {
+		#[structopt(subcommand)]
+		action: PlayerListAction,
+	},
+	/// Manage the players in ops.json
+	Op {
+		#[structopt(subcommand)]
+		action: PlayerListAction,
+	},
+	/// Send a message to the players of the running server, over its control FIFO. Only
+	/// available on Unix
+	#[cfg(unix)]
+	Say {
+		#[structopt(required = true)]
+		message: Vec<String>,
+	},
+	/// Print the SHA-256 of a jar, to pin it with jar_sha256 in the config
+	Hash {
+		#[structopt(parse(from_os_str))]
+		jar: PathBuf,
+	},
+	/// Manage the config file
+	Config {
+		#[structopt(subcommand)]
+		action: ConfigAction,
+	},
+}
+
+#[derive(Debug, StructOpt)]
+enum ConfigAction {
+	/// Write a commented config with every field set to its default
+	Init {
+		/// Overwrite the existing config
+		#[structopt(long)]
+		force: bool,
+	},
+}
+
+#[derive(Debug, StructOpt)]
+enum PlayerListAction {
+	/// Add the player, looking up their UUID
+	Add { name: String },
+	/// Remove the player
+	Remove { name: String },
+	/// List the players
+	List,
 }
 
-fn main() -> Result<()> {
+fn main() {
+	match run() {
+		Ok(0) => (),
+		Ok(code) => std::process::exit(code),
+		Err(e) => {
+			eprintln!("Error: {:?}", e);
+			std::process::exit(error::exit_code(&e));
+		}
+	}
+}
+
+/// Returns the exit code the runner exits with.
+fn run() -> Result<i32> {
 	if std::env::var_os("RUST_LOG").is_none() {
 		std::env::set_var("RUST_LOG", "minecraft_runner=info,warn,error");
 	}
-	env_logger::init();
+
+	let opt: Opt = Opt::from_args();
+	// Resolve the config path before changing the directory, so relative paths work as expected
+	let config_path = match &opt.config {
+		_ if opt.config_stdin => None,
+		Some(path) => Some(std::env::current_dir()?.join(path)),
+		None => Some(config::default_config_path(
+			current_exe()?.parent().unwrap(),
+		)),
+	};
+	let multi_root = match &opt.multi {
+		Some(path) => Some(std::env::current_dir()?.join(path)),
+		None => None,
+	};
+
 	set_current_dir(current_exe()?.parent().unwrap())?;
 	let current_dir = std::env::current_dir()?;
 
-	let opt: Opt = Opt::from_args();
+	let name = match &opt.name {
+		Some(name) => name.clone(),
+		None => current_dir
+			.file_name()
+			.map(|name| name.to_string_lossy().into_owned())
+			.unwrap_or_default(),
+	};
+	logger::init_logger(&name, opt.log_timestamps, opt.log_target, opt.color);
+
+	if let Some(SubCommand::Config {
+		action: ConfigAction::Init { force },
+	}) = opt.cmd
+	{
+		let config_path = match &config_path {
+			Some(v) => v,
+			None => {
+				anyhow::bail!("config init writes a file, it can't be used with --config-stdin")
+			}
+		};
+		config::write_template(config_path, force)?;
+		info!("Wrote the config template to {}.", config_path.display());
+		return Ok(0);
+	}
+
+	if let Some(SubCommand::Hash { jar }) = &opt.cmd {
+		let sha256 = find_jar::sha256(jar)?;
+		if opt.json {
+			let hash = serde_json::json!({ "path": jar, "sha256": sha256 });
+			println!("{}", serde_json::to_string_pretty(&hash)?);
+		} else {
+			println!("{}  {}", sha256, jar.display());
+		}
+		return Ok(0);
+	}
+
+	let config = match &config_path {
+		Some(config_path) => config::load_config(config_path)?,
+		None => config::read_config_stdin()?,
+	};
+	let mut settings = settings::resolve_settings(&opt, &config, &EnvSettings::from_env())?;
+
+	if let Some(SubCommand::PrintConfig) = opt.cmd {
+		if opt.json {
+			println!("{}", serde_json::to_string_pretty(&settings)?);
+		} else {
+			let pretty = ron::ser::PrettyConfig::new();
+			println!("{}", ron::ser::to_string_pretty(&settings, pretty)?);
+		}
+		return Ok(0);
+	}
+
+	if let Some(SubCommand::ListJars) = opt.cmd {
+		let jars = find_jar::describe_jars(
+			&current_dir,
+			settings
+				.jar_preference
+				.as_ref()
+				.map(|jar| jar.value.as_path()),
+		)?;
+		if opt.json {
+			println!("{}", serde_json::to_string_pretty(&jars)?);
+		} else {
+			for jar in jars {
+				println!("{}", jar);
+			}
+		}
+		return Ok(0);
+	}
+
+	if let Some(SubCommand::Ping { address, timeout }) = opt.cmd {
+		let address = match address {
+			Some(address) => address,
+			None => {
+				let port = match &settings.port {
+					Some(port) => port.value,
+					None => properties::get_property(&current_dir, "server-port")?
+						.and_then(|port| port.parse().ok())
+						.unwrap_or(DEFAULT_SERVER_PORT),
+				};
+				ping_address(&current_dir, port)?
+			}
+		};
+		let result = ping::ping(address, Duration::from_secs(timeout))
+			.with_context(|| format!("Failed to ping {}", address))?;
+		if opt.json {
+			println!("{}", serde_json::to_string_pretty(&result)?);
+		} else {
+			println!(
+				"{} ({}ms): {}, {}/{} players",
+				address,
+				result.latency_ms,
+				result.version.as_deref().unwrap_or("unknown version"),
+				result.players_online,
+				result.players_max
+			);
+			if !result.description.is_empty() {
+				println!("{}", result.description);
+			}
+		}
+		return Ok(0);
+	}
+
+	if let Some(SubCommand::ListWorlds) = opt.cmd {
+		let worlds = world::list_worlds(&current_dir)
+			.with_context(|| format!("Failed to list the worlds in {:?}", current_dir))?;
+		if opt.json {
+			println!("{}", serde_json::to_string_pretty(&worlds)?);
+		} else if worlds.is_empty() {
+			println!("No worlds in {}.", current_dir.display());
+		} else {
+			let width = worlds
+				.iter()
+				.map(|world| world.name.len())
+				.max()
+				.unwrap_or(0)
+				.max("NAME".len());
+			println!(
+				"  {:<width$}  {:>10}  LAST SAVED",
+				"NAME",
+				"SIZE",
+				width = width
+			);
+			for world in worlds {
+				println!(
+					"{} {:<width$}  {:>10}  {}",
+					if world.active { "*" } else { " " },
+					world.name,
+					disk::format_size(world.size),
+					world.last_modified.as_deref().unwrap_or("-"),
+					width = width
+				);
+			}
+		}
+		return Ok(0);
+	}
+
+	if let Some(SubCommand::EnvInfo) = opt.cmd {
+		let java = resolve_java(&opt, &config, None).ok();
+		let java_major = java
+			.as_ref()
+			.and_then(|java| java::java_major(java, Duration::from_secs(opt.java_probe_timeout)));
+		let jars = find_jar::describe_jars(
+			&current_dir,
+			settings
+				.jar_preference
+				.as_ref()
+				.map(|jar| jar.value.as_path()),
+		)?;
+		let env_info = env_info::EnvInfo::new(java, java_major, current_dir, jars, &settings);
+		if opt.json {
+			println!("{}", serde_json::to_string_pretty(&env_info)?);
+		} else {
+			env_info.print()?;
+		}
+		return Ok(0);
+	}
+
+	if let Some(SubCommand::Doctor) = opt.cmd {
+		let report = doctor::run(
+			&opt,
+			&config,
+			config_path.as_deref(),
+			&current_dir,
+			&settings,
+		);
+		if opt.json {
+			println!("{}", serde_json::to_string_pretty(&report)?);
+		} else {
+			report.print(opt.color);
+		}
+		if !report.passed {
+			anyhow::bail!("Some checks failed.");
+		}
+		return Ok(0);
+	}
+
+	match &opt.cmd {
+		Some(SubCommand::Whitelist { action }) => {
+			manage_player_list(&opt, &current_dir, players::PlayerList::Whitelist, action)?;
+			return Ok(0);
+		}
+		Some(SubCommand::Op { action }) => {
+			manage_player_list(&opt, &current_dir, players::PlayerList::Ops, action)?;
+			return Ok(0);
+		}
+		_ => (),
+	}
+
+	#[cfg(unix)]
+	if let Some(SubCommand::Say { message }) = &opt.cmd {
+		control::send(&current_dir, &format!("say {}", message.join(" ")))?;
+		return Ok(0);
+	}
+
+	if let Some(profile) = &settings.profile {
+		info!("Using profile \"{}\".", profile);
+	}
+
+	if let Some(SubCommand::ShowCommand { shell }) = opt.cmd {
+		let java = resolve_java(&opt, &config, None)?;
+		let launch = match (&opt.classpath, &opt.main_class) {
+			(Some(classpath), Some(main_class)) => {
+				jvm::Launch::main_class(&current_dir, classpath, main_class)?
+			}
+			_ => {
+				let server_jar = find_jar::find_server_jar_with_retry(
+					&current_dir,
+					&settings.jar_preferences(),
+					jar_list_retry(&opt),
+				)?;
+				let server_jar = select_server_jar(
+					server_jar,
+					&opt,
+					&current_dir,
+					None,
+					&|jars: &[PathBuf]| Ok(find_jar::ask_which_jar_to_use(jars, opt.color)?),
+				)?;
+				let server_jar = server_jar.file_name().and_then(OsStr::to_str).unwrap();
+				jvm::Launch::Jar(server_jar.to_string())
+			}
+		};
+
+		if let Some(jar) = launch.jar() {
+			apply_jar_memory(&mut settings, &config, jar)?;
+		}
+
+		let mut command = vec![java.to_string_lossy().into_owned()];
+		command.extend(jvm::server_args(&settings, &current_dir, &launch, &[]));
+		if shell {
+			println!("{}", shell_words::join(&command));
+		} else {
+			for arg in command {
+				println!("{}", arg);
+			}
+		}
+		return Ok(0);
+	}
 
-	let min_jvm_size = opt.min.into::<human_size::Mebibyte>().value().floor() as u64;
-	let min_jvm_size = format!("{}M", min_jvm_size);
+	let read_only = if opt.read_only {
+		info!("Read-only mode, nothing is written to the server directory or the config.");
+		true
+	} else if !disk::is_writable(&current_dir) {
+		warn!(
+			"=== {} isn't writable. Launching without the lock file and without saving the jar \
+			 choice or the java path, pass --read-only to silence this ===",
+			current_dir.display()
+		);
+		true
+	} else {
+		false
+	};
+	// Where the jar choice and the java path are saved, nowhere in read-only mode
+	let writable_config_path = config_path.as_deref().filter(|_| !read_only);
 
-	let max_jvm_size = opt.max.into::<human_size::Mebibyte>().value().floor() as u64;
-	let max_jvm_size = format!("{}M", max_jvm_size);
+	let _instance_lock = match read_only {
+		true => None,
+		false => lock::InstanceLock::acquire(&current_dir, opt.force)?,
+	};
+	if opt.kill_orphans {
+		// Holding the lock, any server in the pidfile was left behind by a runner that's gone
+		pidfile::kill_orphan(&current_dir).context("Failed to kill the orphaned server")?;
+	}
 
-	info!("Min JVM size: {}", min_jvm_size);
-	info!("Max JVM size: {}", max_jvm_size);
+	info!(
+		"Min JVM size: {}",
+		jvm::format_jvm_size(settings.min_mib.value)
+	);
+	info!(
+		"Max JVM size: {}",
+		jvm::format_jvm_size(settings.max_mib.value)
+	);
 
-	let java = match find_java() {
-		Some(v) => v,
-		None => panic!("Java not found"),
+	let java = match &opt.docker {
+		Some(image) => {
+			let version = docker::check_installed()?;
+			info!(
+				"Launching the server in the Docker image \"{}\" ({}).",
+				image, version
+			);
+			PathBuf::from("java")
+		}
+		None => resolve_java(&opt, &config, writable_config_path)?,
 	};
 
 	info!("Java path: {}", java.display());
 
-	let status = Command::new(&java)
-		.args(&["-jar", "AutoIpMinecraft.jar", "server.properties"])
-		.status();
-	if let Err(e) = status {
-		error!("Failed to open AutoIpMinecraft.jar: {:?}", e);
+	let auto_ip_args = match &opt.auto_ip_args {
+		Some(args) => shell_words::split(args)
+			.with_context(|| format!("Invalid --auto-ip-args \"{}\"", args))?,
+		None => config.auto_ip_args.clone(),
+	};
+	let pre_launch_jars: Vec<_> = std::iter::once(prelaunch::auto_ip(auto_ip_args))
+		.chain(config.pre_launch_jars.iter().cloned())
+		.collect();
+	if multi_root.is_none() {
+		prelaunch::run_all(&java, &pre_launch_jars, &current_dir)?;
 	}
 
 	#[cfg(windows)]
 	let sender = rivatiker::start_state_setter(rivatiker::State::NoSystemSleep);
 
-	let server_jar = find_jar::find_server_jar(&current_dir)?;
+	if let Some(root) = &multi_root {
+		let result = multi::run_multi(root, &java, &settings, &pre_launch_jars, opt.force);
+		#[cfg(windows)]
+		sender.send(rivatiker::State::Default).unwrap();
+		return result.map(|()| 0);
+	}
+
+	#[cfg(feature = "webserver")]
+	let web_state = {
+		let tls_config = match (&opt.web_tls_cert, &opt.web_tls_key) {
+			(Some(cert), Some(key)) => Some(load_tls_config(cert, key)?),
+			_ => None,
+		};
+		let mut web_state = WebState::default();
+		if let Some(rate_limit) = opt.web_rate_limit {
+			web_state = web_state.with_rate_limit(rate_limit);
+		}
+		let web_state = Arc::new(web_state);
+		web_state.set_name(name.clone());
+		start_web_server(Arc::clone(&web_state), "localhost:8080", tls_config);
+		web_state
+	};
+
+	let launch = match (&opt.classpath, &opt.main_class) {
+		(Some(classpath), Some(main_class)) => {
+			let launch = jvm::Launch::main_class(&current_dir, classpath, main_class)?;
+			info!("Launching {} instead of a jar.", launch);
+			launch
+		}
+		_ => {
+			let server_jar = find_jar::find_server_jar_with_retry(
+				&current_dir,
+				&settings.jar_preferences(),
+				jar_list_retry(&opt),
+			)?;
+
+			#[cfg(feature = "webserver")]
+			let ask_which_jar_to_use = |jars: &[PathBuf]| -> Result<PathBuf> {
+				if stdin_is_interactive(&opt) {
+					Ok(find_jar::ask_which_jar_to_use(jars, opt.color)?)
+				} else {
+					let timeout = Duration::from_secs(opt.jar_selection_timeout);
+					web_state.select_jar(jars, timeout)
+				}
+			};
+			#[cfg(not(feature = "webserver"))]
+			let ask_which_jar_to_use = |jars: &[PathBuf]| -> Result<PathBuf> {
+				if opt.config_stdin {
+					anyhow::bail!(
+						"Multiple jars found and stdin is taken by the config. Set jar_preference \
+						 in the config or MINECRAFT_RUNNER_JAR to pick one."
+					);
+				}
+				Ok(find_jar::ask_which_jar_to_use(jars, opt.color)?)
+			};
+
+			let server_jar = select_server_jar(
+				server_jar,
+				&opt,
+				&current_dir,
+				writable_config_path,
+				&ask_which_jar_to_use,
+			)?;
+			// The java of the image can't be probed from here
+			if opt.docker.is_none() {
+				check_java_version(&opt, &java, &server_jar)?;
+			}
+			check_snapshot(&opt, &config, &server_jar)?;
+			if let Some(expected) = &config.jar_sha256 {
+				check_jar_hash(&server_jar, expected)?;
+			}
+
+			let server_jar = server_jar.file_name().and_then(OsStr::to_str).unwrap();
+			info!("Stripped the jar path a filename: \"{}\"", server_jar);
+			jvm::Launch::Jar(server_jar.to_string())
+		}
+	};
+
+	if let Some(jar) = launch.jar() {
+		apply_jar_memory(&mut settings, &config, jar)?;
+	}
+
+	check_eula(&current_dir)?;
+
+	if let Some(bind_ip) = &opt.bind_ip {
+		apply_bind_ip(&current_dir, bind_ip)?;
+	}
+
+	let port = resolve_port(&opt, &mut settings, &current_dir)?;
+	debug!("Server port: {}", port);
+
+	let gc = settings.gc.value;
+	if gc != jvm::GcProfile::G1 && opt.docker.is_none() {
+		match java::java_major(&java, Duration::from_secs(opt.java_probe_timeout)) {
+			Some(java_major) => {
+				if let Some(reason) = gc.unsupported_reason(java_major) {
+					warn!("=== {}, the JVM may refuse to start ===", reason);
+				}
+			}
+			None => warn!(
+				"Unknown java version, can't tell whether it supports {:?}.",
+				gc
+			),
+		}
+	}
+
+	let mut extra_jvm_args = Vec::new();
+	if let Some(jmx_port) = opt.jmx_port {
+		let bind_ip = server_bind_ip(&current_dir)?;
+		check_port_free(bind_ip, jmx_port).context("The JMX port is not available")?;
+		if !opt.jmx_auth && !bind_ip.is_loopback() {
+			warn!(
+				"=== JMX on port {} has no authentication and is reachable from the network. \
+				 Anyone who can connect controls the server, use --jmx-auth or --bind-ip 127.0.0.1 ===",
+				jmx_port
+			);
+		}
+		let host = Some(bind_ip).filter(|ip| !ip.is_unspecified());
+		extra_jvm_args.extend(jvm::jmx_args(jmx_port, host, opt.jmx_auth));
+		info!("JMX is exposed on port {}.", jmx_port);
+	}
+
+	if let Some(gc_log) = &opt.gc_log {
+		if let Some(parent) = gc_log
+			.parent()
+			.filter(|parent| !parent.as_os_str().is_empty())
+		{
+			std::fs::create_dir_all(parent).with_context(|| format!("Path: {:?}", parent))?;
+		}
+		let java_major = java::java_major(&java, Duration::from_secs(opt.java_probe_timeout));
+		extra_jvm_args.extend(jvm::gc_log_args(gc_log, java_major));
+		info!("Writing the GC log to {}.", gc_log.display());
+	}
+
+	let heap_dump_dir = opt.heap_dump_on_oom.as_ref().map(|dir| {
+		dir.clone()
+			.unwrap_or_else(|| PathBuf::from(DEFAULT_HEAP_DUMP_DIR))
+	});
+	if let Some(dir) = &heap_dump_dir {
+		std::fs::create_dir_all(dir).with_context(|| format!("Path: {:?}", dir))?;
+		extra_jvm_args.extend(jvm::heap_dump_args(dir));
+		info!("Heap dumps on OutOfMemoryError go to {}.", dir.display());
+	}
+
+	if opt.large_pages {
+		if let Some(problem) = jvm::large_pages_problem() {
+			warn!(
+				"{}. The JVM falls back to regular pages without large pages set up.",
+				problem
+			);
+		}
+		extra_jvm_args.extend(jvm::large_pages_args());
+		info!("Backing the heap with large pages.");
+	}
+
+	let detected_cpu_count = cgroup::cpu_limit();
+	if let Some(cpu_count) = detected_cpu_count {
+		info!("Detected a cgroup CPU limit of {} processors.", cpu_count);
+	}
+	if let Some(cpu_count) = opt
+		.cpu_count
+		.map(std::num::NonZeroU32::get)
+		.or(detected_cpu_count)
+	{
+		extra_jvm_args.push(format!("-XX:ActiveProcessorCount={}", cpu_count));
+		info!("The JVM will use {} processors.", cpu_count);
+	}
+
+	warn_over_allocation(settings.max_mib.value);
+
+	let args = jvm::server_args(&settings, &current_dir, &launch, &extra_jvm_args);
+
+	if let Some(world) = &opt.world {
+		properties::set_property(&current_dir, "level-name", world)?;
+		info!("Using the world \"{}\".", world);
+	}
+
+	if let Some(seed) = &opt.seed {
+		apply_seed(&current_dir, seed)?;
+	}
+
+	let world_size_at_start = world_size(&current_dir);
+	if let Some(size) = world_size_at_start {
+		info!("World size: {}", disk::format_size(size));
+	}
+
+	let min_free_space = opt
+		.min_free_space
+		.clone()
+		.into::<human_size::Byte>()
+		.value() as u64;
+	disk::check_free_space(&current_dir, min_free_space, opt.strict_disk)?;
+
+	if opt.list_mods {
+		log_mods(&current_dir);
+	}
+
+	if let Some(lines) = opt.tail {
+		print_previous_log(&current_dir, lines);
+	}
+
+	if let Some(on_start) = &opt.on_start {
+		let envs = [
+			(
+				"MINECRAFT_JAR",
+				launch.jar().unwrap_or_default().to_string(),
+			),
+			(
+				"MINECRAFT_WORKING_DIRECTORY",
+				current_dir.to_string_lossy().into_owned(),
+			),
+		];
+		let result =
+			hooks::run_hook(on_start, &current_dir, &envs, Level::Info).and_then(|status| {
+				match status.success() {
+					true => Ok(()),
+					false => Err(anyhow::anyhow!("Start hook failed with {}", status)),
+				}
+			});
+		match result {
+			Err(e) if opt.ignore_hook_failure => warn!("{:?}. Launching anyway.", e),
+			result => result?,
+		}
+	}
 
+	#[cfg(unix)]
+	if let Some(session_name) = &opt.tmux {
+		if opt.startup_timeout > 0 || opt.startup_delay > 0 {
+			anyhow::bail!("--startup-timeout and --startup-delay can't be combined with --tmux.");
+		}
+		if !settings.startup_commands.value.is_empty() {
+			anyhow::bail!(
+				"Startup commands can't be sent to a tmux session, remove them from the config"
+			);
+		}
+		#[cfg(feature = "webserver")]
+		web_state.set_port(port);
+
+		let session = Arc::new(tmux::TmuxSession::launch(
+			session_name,
+			&java,
+			&args,
+			&current_dir,
+		)?);
+		info!(
+			"Server launched in tmux session \"{0}\". Attach with `tmux attach -t {0}`.",
+			session_name
+		);
+		{
+			let session = Arc::clone(&session);
+			std::thread::spawn(move || tmux::forward_console(&session));
+		}
+		session.wait();
+		info!("tmux session ended.");
+		return Ok(0);
+	}
+
+	let mut minecraft_server = ServerManager::new(java, args, current_dir.clone())
+		.with_startup_commands(settings.startup_commands.value.clone())
+		.with_stop_commands(settings.stop_commands.value.clone())
+		.with_output_logs(opt.stdout_log.clone(), opt.stderr_log.clone());
+	// The PID under --docker is the docker CLI's, killing it would leave the container running
+	if !read_only && opt.docker.is_none() {
+		minecraft_server =
+			minecraft_server.with_pidfile(current_dir.join(pidfile::PIDFILE_FILENAME));
+	}
+	if opt.parse_server_levels {
+		minecraft_server = minecraft_server.with_parsed_levels();
+	}
+	if let Some(timestamps) = opt.timestamp_output {
+		if timestamps != Some(server::TimestampOutput::All)
+			&& opt.stdout_log.is_none()
+			&& opt.stderr_log.is_none()
+		{
+			warn!("--timestamp-output log has no effect without --stdout-log or --stderr-log.");
+		}
+		minecraft_server =
+			minecraft_server.with_timestamps(timestamps.unwrap_or(server::TimestampOutput::Log));
+	}
+	if let Some(pattern) = &config.done_line_pattern {
+		let done_line = regex::Regex::new(pattern)
+			.with_context(|| format!("Invalid done_line_pattern \"{}\"", pattern))?;
+		minecraft_server = minecraft_server.with_done_line(done_line);
+	}
+	let player_patterns = events::PlayerPatterns::new(
+		config.player_join_pattern.as_deref(),
+		config.player_leave_pattern.as_deref(),
+	)?;
+	if opt.once && opt.auto_restart {
+		warn!("--auto-restart is ignored with --once.");
+	}
+	if opt.force_upgrade {
+		info!("=== Upgrading the world with --forceUpgrade, this can take a while ===");
+		if opt.startup_timeout > 0 {
+			warn!("--startup-timeout is ignored while upgrading the world.");
+		}
+	} else if opt.startup_timeout > 0 {
+		minecraft_server = minecraft_server.with_startup_timeout(
+			Duration::from_secs(opt.startup_timeout),
+			opt.auto_restart && !opt.once,
+		);
+	}
+	if opt.startup_delay > 0 {
+		minecraft_server =
+			minecraft_server.with_startup_delay(Duration::from_secs(opt.startup_delay));
+	}
+	if let Some(stdin_file) = &opt.stdin_file {
+		let script = std::fs::read_to_string(stdin_file)
+			.with_context(|| format!("Failed to read the stdin file {:?}", stdin_file))?;
+		minecraft_server = minecraft_server.with_stdin_script(
+			server::parse_stdin_script(&script),
+			Duration::from_millis(opt.stdin_file_delay),
+		);
+	}
+	if opt.announce_restart_reason {
+		let messages = restart_messages(&config)?;
+		minecraft_server = minecraft_server.with_restart_announcements(messages);
+	}
+	if opt.once {
+		minecraft_server = minecraft_server.with_single_run();
+	}
+	if let Some(image) = &opt.docker {
+		let ports = std::iter::once(port).chain(opt.jmx_port).collect();
+		minecraft_server =
+			minecraft_server.with_docker(docker::DockerLaunch::new(image.clone(), ports));
+	}
+	#[cfg(unix)]
+	let priority = opt.nice;
+	#[cfg(windows)]
+	let priority = opt.priority;
+	if let Some(priority) = priority {
+		minecraft_server = minecraft_server.with_priority(priority);
+	}
+	#[cfg(unix)]
+	if let Some(user) = &opt.run_as {
+		let run_as = privileges::resolve_run_as(user)?;
+		info!(
+			"Launching the server as \"{}\" (uid {}, gid {}).",
+			user, run_as.uid, run_as.gid
+		);
+		minecraft_server = minecraft_server.with_run_as(run_as);
+	}
+	let minecraft_server = Arc::new(minecraft_server);
+	let launched_at = std::time::SystemTime::now();
+	minecraft_server.spawn()?;
+
+	{
+		let minecraft_server = Arc::clone(&minecraft_server);
+		if opt.console {
+			let history_path = current_dir.join(console::HISTORY_FILENAME);
+			std::thread::spawn(move || console::run_console(&minecraft_server, &history_path));
+		} else {
+			std::thread::spawn(move || server::forward_console(&minecraft_server));
+		}
+	}
+
+	// Shared with the config watcher thread
+	let opt = Arc::new(opt);
+	if let (true, Some(config_path)) = (opt.watch_config, &config_path) {
+		let opt = Arc::clone(&opt);
+		let minecraft_server = Arc::clone(&minecraft_server);
+		let current_dir = current_dir.clone();
+		let launch = launch.clone();
+		let extra_jvm_args = extra_jvm_args.clone();
+		let port = settings.port.clone();
+		config_watch::watch(config_path, move |config| {
+			let result = apply_config_change(
+				&opt,
+				&config,
+				&minecraft_server,
+				&current_dir,
+				&launch,
+				&extra_jvm_args,
+				port.clone(),
+			);
+			if let Err(e) = result {
+				warn!(
+					"Ignoring the changed config, keeping the last one: {:?}.",
+					e
+				);
+			}
+		})?;
+		info!("Watching {} for changes.", config_path.display());
+	}
+
+	if let Some(idle_timeout) = opt.idle_shutdown {
+		let address = ping_address(&current_dir, port)?;
+		let minecraft_server = Arc::clone(&minecraft_server);
+		std::thread::spawn(move || idle::watch(&minecraft_server, address, idle_timeout));
+	}
+
+	#[cfg(windows)]
+	if opt.set_title {
+		let address = ping_address(&current_dir, port)?;
+		let minecraft_server = Arc::clone(&minecraft_server);
+		let name = name.clone();
+		std::thread::spawn(move || title::update(&minecraft_server, &name, address));
+	}
+
+	#[cfg(unix)]
+	let _control_fifo = match opt.control_fifo {
+		true => {
+			let fifo = control::ControlFifo::create(&current_dir, Arc::clone(&minecraft_server))?;
+			info!("Forwarding the lines written to {}.", fifo.path().display());
+			Some(fifo)
+		}
+		false => None,
+	};
+	#[cfg(unix)]
+	let _control_socket = match &opt.control_socket {
+		Some(path) => {
+			let socket = control::ControlSocket::bind(path, Arc::clone(&minecraft_server))?;
+			info!("Listening for console commands on {}.", path.display());
+			Some(socket)
+		}
+		None => None,
+	};
+
+	#[cfg(unix)]
+	signals::forward_sighup(Arc::clone(&minecraft_server), opt.reload_command.clone())?;
+
+	#[cfg_attr(not(feature = "webserver"), allow(unused_variables))]
+	let (online_players, player_events) = events::track(&minecraft_server, player_patterns);
+	if let (true, Some(url)) = (opt.notify_players, opt.notify_url.clone()) {
+		let name = name.clone();
+		std::thread::spawn(move || {
+			for event in player_events {
+				let notification = webhook::PlayerNotification::new(&name, event);
+				if let Err(e) = webhook::send(&url, &notification) {
+					warn!("Failed to send the player notification: {:?}.", e);
+				}
+			}
+		});
+	}
+
+	#[cfg(feature = "webserver")]
+	{
+		web_state.set_port(port);
+		web_state.set_minecraft_server(Arc::clone(&minecraft_server));
+		web_state.set_players(online_players);
+	}
+
+	let exit_status = match minecraft_server.wait() {
+		Ok(status) => {
+			info!("Minecraft exited with status: {}", status);
+			let heap_dumps = heap_dump_dir
+				.as_ref()
+				.map(|dir| crash::find_heap_dumps(dir, launched_at))
+				.unwrap_or_default();
+			for dump in &heap_dumps {
+				let size = std::fs::metadata(dump)
+					.map(|meta| meta.len())
+					.unwrap_or_default();
+				warn!(
+					"Heap dump: {} ({})",
+					dump.display(),
+					disk::format_size(size)
+				);
+			}
+			if opt.force_upgrade {
+				info!("The upgrade run finished.");
+			} else if !status.success() {
+				let crash_reports = crash::find_crash_reports(&current_dir, launched_at);
+				for report in &crash_reports {
+					warn!("Crash report: {}", report.display());
+				}
+				if opt.collect_crashes {
+					match crash::collect(&current_dir, &crash_reports, &heap_dumps) {
+						Ok(directory) => info!("Saved diagnostics to {}.", directory.display()),
+						Err(e) => warn!("Failed to collect the crash diagnostics: {:?}.", e),
+					}
+				}
+				if let Some(url) = &opt.notify_url {
+					let crash_report = crash_reports
+						.last()
+						.and_then(|report| crash::summary(report));
+					notify_crash(url, &name, &current_dir, status, crash_report);
+				}
+			}
+			Some(status)
+		}
+		Err(e) => {
+			error!("Minecraft exited with error: {:?}", e);
+			None
+		}
+	};
+	let exit_code = exit_status.and_then(|status| status.code());
+
+	if let (Some(before), Some(after)) = (world_size_at_start, world_size(&current_dir)) {
+		let delta = if after >= before {
+			format!("+{}", disk::format_size(after - before))
+		} else {
+			format!("-{}", disk::format_size(before - after))
+		};
+		info!(
+			"World size: {} ({} this session)",
+			disk::format_size(after),
+			delta
+		);
+	}
+
+	if let Some(on_exit) = &opt.on_exit {
+		let exit_code = exit_code.map(|code| code.to_string()).unwrap_or_default();
+		match hooks::run_hook(
+			on_exit,
+			&current_dir,
+			&[("MINECRAFT_EXIT_CODE", exit_code)],
+			Level::Debug,
+		) {
+			Ok(status) if !status.success() => warn!("Exit hook failed with {}.", status),
+			Ok(_) => (),
+			Err(e) => warn!("Failed to run the exit hook: {:?}.", e),
+		}
+	}
+
+	#[cfg(windows)]
+	sender.send(rivatiker::State::Default).unwrap();
+
+	if minecraft_server.startup_timed_out() {
+		anyhow::bail!(
+			"The server didn't finish loading within {}s.",
+			opt.startup_timeout
+		);
+	}
+
+	if opt.once {
+		return Ok(exit_status
+			.map(server_exit_code)
+			.unwrap_or(error::exit_codes::UNKNOWN));
+	}
+
+	Ok(0)
+}
+
+/// The code the server exited with, or 128 plus the signal that killed it, like shells report it.
+fn server_exit_code(status: ExitStatus) -> i32 {
+	#[cfg(unix)]
+	{
+		use std::os::unix::process::ExitStatusExt;
+		if let Some(signal) = status.signal() {
+			return 128 + signal;
+		}
+	}
+	status.code().unwrap_or(error::exit_codes::UNKNOWN)
+}
+
+/// Edits the player list file. The server only reads it on launch, so with `--tmux` the matching
+/// console command is sent to the running server as well.
+fn manage_player_list(
+	opt: &Opt,
+	current_dir: &Path,
+	list: players::PlayerList,
+	action: &PlayerListAction,
+) -> Result<()> {
+	let (add, name) = match action {
+		PlayerListAction::Add { name } => (true, name),
+		PlayerListAction::Remove { name } => (false, name),
+		PlayerListAction::List => {
+			let players = players::read_list(&list.path(current_dir))?;
+			if opt.json {
+				println!("{}", serde_json::to_string_pretty(&players)?);
+			} else {
+				for player in players {
+					println!("{} {}", player.name, player.uuid);
+				}
+			}
+			return Ok(());
+		}
+	};
+
+	let changed = if add {
+		players::add(current_dir, list, name)?
+	} else {
+		players::remove(current_dir, list, name)?
+	};
+	let path = list.path(current_dir);
+	match (changed, add) {
+		(true, true) => info!("Added \"{}\" to {}.", name, path.display()),
+		(true, false) => info!("Removed \"{}\" from {}.", name, path.display()),
+		(false, true) => info!("\"{}\" is already in {}.", name, path.display()),
+		(false, false) => info!("\"{}\" is not in {}.", name, path.display()),
+	}
+
+	#[cfg(unix)]
+	if let Some(session) = opt.tmux.as_deref().and_then(tmux::TmuxSession::existing) {
+		session.send_command(&list.console_command(add, name))?;
+		info!("Applied the change to the running server.");
+	}
+	Ok(())
+}
+
+/// Warns when the max memory doesn't fit into the memory of the machine, or of the container
+/// when it has a memory limit.
+fn warn_over_allocation(max_mib: u64) {
+	let ceiling = cgroup::memory_ceiling();
+	if max_mib * 1024 * 1024 > ceiling {
+		let limited = match cgroup::memory_limit() {
+			Some(_) => " (container limit)",
+			None => "",
+		};
+		warn!(
+			"Max JVM size {} is more than the {} of memory available{}.",
+			jvm::format_jvm_size(max_mib),
+			disk::format_size(ceiling),
+			limited
+		);
+	}
+}
+
+/// Applies the `jar_memory` entry of the jar from the config, see [`settings::apply_jar_memory`].
+fn apply_jar_memory(
+	settings: &mut EffectiveSettings,
+	config: &MinecraftRunnerConfig,
+	jar: &str,
+) -> Result<()> {
+	if settings::apply_jar_memory(settings, config, jar)? {
+		info!(
+			"Using the memory configured for \"{}\": min {}, max {}.",
+			jar,
+			jvm::format_jvm_size(settings.min_mib.value),
+			jvm::format_jvm_size(settings.max_mib.value)
+		);
+	}
+	Ok(())
+}
+
+/// Applies a changed config to the running server. Stop commands are used right away, startup
+/// commands, memory and JVM arguments once the server is restarted. The port stays the one the
+/// server was launched with.
+fn apply_config_change(
+	opt: &Opt,
+	config: &MinecraftRunnerConfig,
+	minecraft_server: &ServerManager,
+	current_dir: &Path,
+	launch: &jvm::Launch,
+	extra_jvm_args: &[String],
+	port: Option<settings::Sourced<u16>>,
+) -> Result<()> {
+	let mut settings = settings::resolve_settings(opt, config, &EnvSettings::from_env())?;
+	if let Some(jar) = launch.jar() {
+		settings::apply_jar_memory(&mut settings, config, jar)?;
+	}
+	settings.port = port;
+
+	if minecraft_server.set_stop_commands(settings.stop_commands.value.clone()) {
+		info!("Reloaded the stop commands.");
+	}
+	if minecraft_server.set_startup_commands(settings.startup_commands.value.clone()) {
+		info!("Reloaded the startup commands, they're sent after the next restart.");
+	}
+	let args = jvm::server_args(&settings, current_dir, launch, extra_jvm_args);
+	if minecraft_server.set_args(args) {
+		info!(
+			"Reloaded the JVM arguments (min {}, max {}), they take effect on the next restart.",
+			jvm::format_jvm_size(settings.min_mib.value),
+			jvm::format_jvm_size(settings.max_mib.value)
+		);
+	}
+	Ok(())
+}
+
+/// Finds java, trying the path cached in the config first. When `cache_path` is given, a newly
+/// found java is cached there.
+fn resolve_java(
+	opt: &Opt,
+	config: &MinecraftRunnerConfig,
+	cache_path: Option<&Path>,
+) -> Result<PathBuf, RunnerError> {
+	let probe_timeout = Duration::from_secs(opt.java_probe_timeout);
+	if let Some(java) = &config.java_path_cache {
+		if !opt.refresh_java {
+			if let Some(java) = java::find_java_in(java, probe_timeout) {
+				debug!("Using cached java path.");
+				return Ok(java);
+			}
+		}
+	}
+
+	let java = java::find_java(probe_timeout)?;
+	if let Some(cache_path) = cache_path {
+		if let Err(e) = config::save_java_path_cache(&java, cache_path) {
+			warn!("Failed to cache the java path: {:?}.", e);
+		}
+	}
+	Ok(java)
+}
+
+/// Warns when the java is too old for the Minecraft version of the jar, or bails with
+/// `--strict-java`. Jars without a version are skipped.
+fn check_java_version(opt: &Opt, java: &Path, server_jar: &Path) -> Result<()> {
+	let minecraft_version = match find_jar::read_jar_version(server_jar) {
+		Ok(v) => v,
+		Err(e) => {
+			debug!("Failed to read the jar version: {:?}", e);
+			return Ok(());
+		}
+	};
+	let probe_timeout = Duration::from_secs(opt.java_probe_timeout);
+	let (java_major, required) = match (
+		java::java_major(java, probe_timeout),
+		java::minimum_java_major(&minecraft_version),
+	) {
+		(Some(java_major), Some(required)) => (java_major, required),
+		_ => return Ok(()),
+	};
+	debug!(
+		"Java {}, Minecraft {} (needs java {}+).",
+		java_major, minecraft_version, required
+	);
+	if java_major >= required {
+		return Ok(());
+	}
+
+	let message = format!(
+		"Minecraft {} needs java {} or newer, but \"{}\" is java {}.",
+		minecraft_version,
+		required,
+		java.display(),
+		java_major
+	);
+	if opt.strict_java {
+		anyhow::bail!(message);
+	}
+	warn!("{}", message);
+	Ok(())
+}
+
+/// Refuses to launch when `eula.txt` doesn't accept the EULA, the server would exit right away.
+/// Without the file the server is launched anyway, to write it on its first run.
+fn check_eula(current_dir: &Path) -> Result<()> {
+	let path = current_dir.join(doctor::EULA_FILENAME);
+	if !path.exists() {
+		return Ok(());
+	}
+	let eula = properties::ServerProperties::read(&path)?;
+	if eula.get("eula").as_deref() != Some("true") {
+		return Err(RunnerError::EulaNotAccepted { path }.into());
+	}
+	Ok(())
+}
+
+/// Warns about launching a development build and asks for a confirmation, unless snapshots are
+/// allowed with `--allow-snapshot` or in the config. Without a terminal to ask on it bails.
+fn check_snapshot(opt: &Opt, config: &MinecraftRunnerConfig, server_jar: &Path) -> Result<()> {
+	let version = match find_jar::read_jar_version(server_jar) {
+		Ok(v) => v,
+		Err(_) => return Ok(()),
+	};
+	if !vanilla::is_snapshot(&version) {
+		return Ok(());
+	}
+
+	warn!(
+		"=== \"{}\" is the development build {}, don't use it on a world you care about ===",
+		server_jar.display(),
+		version
+	);
+	if opt.allow_snapshot || config.allow_snapshots {
+		return Ok(());
+	}
+	if !stdin_is_interactive(opt) {
+		anyhow::bail!(
+			"Refusing to launch the development build {} without confirmation. Pass \
+			 --allow-snapshot to launch it anyway.",
+			version
+		);
+	}
+	info!("Launch it anyway? [y/N]");
+	let mut line = String::new();
+	std::io::stdin().read_line(&mut line)?;
+	match line.trim().to_lowercase().as_str() {
+		"y" | "yes" => Ok(()),
+		_ => anyhow::bail!("Not launching the development build {}.", version),
+	}
+}
+
+fn check_jar_hash(server_jar: &Path, expected: &str) -> Result<()> {
+	let actual = find_jar::sha256(server_jar)?;
+	if !actual.eq_ignore_ascii_case(expected.trim()) {
+		anyhow::bail!(
+			"SHA-256 mismatch of \"{}\": expected {}, got {}. Refusing to launch a jar that \
+			 isn't the pinned one, update jar_sha256 if it was replaced on purpose.",
+			server_jar.display(),
+			expected,
+			actual
+		);
+	}
+	info!("SHA-256 of \"{}\" matches.", server_jar.display());
+	Ok(())
+}
+
+/// Returns whether confirmations can be asked for on stdin. It's never the case when the config
+/// was read from it.
+fn stdin_is_interactive(opt: &Opt) -> bool {
+	!opt.config_stdin && atty::is(atty::Stream::Stdin)
+}
+
+/// Decides which jar to launch, using `ask_which_jar_to_use` when there are multiple candidates.
+/// Without a `config_path` nothing is written to the disk: the choice isn't remembered and the
+/// vanilla jar isn't downloaded.
+fn select_server_jar(
+	server_jar: FindServerJar,
+	opt: &Opt,
+	current_dir: &Path,
+	config_path: Option<&Path>,
+	ask_which_jar_to_use: &dyn Fn(&[PathBuf]) -> Result<PathBuf>,
+) -> Result<PathBuf> {
 	let server_jar = match server_jar {
 		FindServerJar::ServerJar(path) => path,
 		FindServerJar::OneUnknownJar(path) => {
+			if stdin_is_interactive(opt) {
+				if !find_jar::confirm_unknown_jar(&path)? {
+					anyhow::bail!("Not launching \"{}\".", path.display());
+				}
+			} else if !opt.allow_unknown_jar {
+				anyhow::bail!(
+					"Refusing to launch \"{}\" without confirmation. Pass --allow-unknown-jar true \
+					 to launch it anyway.",
+					path.display()
+				);
+			}
 			info!("Trying to launch the server using \"{}\".", path.display());
 			path
 		}
 		FindServerJar::MultipleJars(paths) => {
-			let chosen_jar = find_jar::ask_which_jar_to_use(&paths)?;
-			if let Err(e) = find_jar::save_jar_preference(&chosen_jar, &current_dir) {
-				warn!("Failed to store chosen jar preference: {:?}.", e);
+			let chosen_jar = ask_which_jar_to_use(&paths)?;
+			if let (false, Some(config_path)) = (opt.no_save_jar, config_path) {
+				if let Err(e) =
+					config::save_jar_preference(&chosen_jar, config_path, opt.profile.as_deref())
+				{
+					warn!("Failed to store chosen jar preference: {:?}.", e);
+				}
 			}
 			info!("Using \"{}\" to launch the server.", chosen_jar.display());
 			chosen_jar
@@ -83,152 +1637,190 @@ fn main() -> Result<()> {
 			);
 			preferred_jar
 		}
-		FindServerJar::None => {
-			anyhow::bail!(
-				"No server jars found laying around in the current directory (\"{}\").",
-				current_dir.display()
-			);
-		}
+		FindServerJar::None => match (&opt.download_vanilla, config_path) {
+			(Some(version), Some(_)) => {
+				vanilla::download_server_jar(version.as_deref(), current_dir)?
+			}
+			_ => return Err(find_jar::no_jars_error(current_dir).into()),
+		},
 	};
+	Ok(server_jar)
+}
 
-	let server_jar = server_jar.file_name().and_then(OsStr::to_str).unwrap();
-	info!("Stripped the jar path a filename: \"{}\"", server_jar);
-
-	let mut minecraft_process = Command::new(&java)
-		.args(&[
-			&format!("-Xmx{}", max_jvm_size),
-			&format!("-Xms{}", min_jvm_size),
-			"-Dsun.rmi.dgc.server.gcInterval=2147483646",
-			"-XX:+UseG1GC",
-			"-XX:+ParallelRefProcEnabled",
-			"-XX:MaxGCPauseMillis=50",
-			"-XX:+UnlockExperimentalVMOptions",
-			//"-XX:+DisableExplicitGC",
-			//"-XX:+AlwaysPreTouch",
-			"-XX:G1NewSizePercent=30",
-			//"-XX:G1MaxNewSizePercent=40",
-			"-XX:G1HeapRegionSize=32M",
-			"-XX:G1ReservePercent=20",
-			"-XX:G1HeapWastePercent=5",
-			"-XX:G1MixedGCCountTarget=4",
-			"-XX:InitiatingHeapOccupancyPercent=15",
-			"-XX:G1MixedGCLiveThresholdPercent=90",
-			"-XX:G1RSetUpdatingPauseTimePercent=5",
-			//"-XX:SurvivorRatio=32",
-			//"-XX:+PerfDisableSharedMem",
-			//"-XX:MaxTenuringThreshold=1",
-			"-server",
-			"-jar",
-			server_jar,
-			"nogui",
-		])
-		.spawn()
-		.unwrap();
-
-	#[cfg(target_feature = "webserver")]
-	{
-		let server_stdin = minecraft_process.stdin.take().unwrap();
-		start_web_server(server_stdin, "localhost:8080");
+fn jar_list_retry(opt: &Opt) -> find_jar::ListRetry {
+	find_jar::ListRetry {
+		attempts: opt.jar_list_attempts,
+		delay: Duration::from_secs(opt.jar_list_retry_delay),
 	}
+}
 
-	match minecraft_process.wait() {
-		Ok(status) => info!("Minecraft exited with status: {}", status),
-		Err(e) => error!("Minecraft exited with error: {:?}", e),
+const DEFAULT_SERVER_PORT: u16 = 25565;
+const DEFAULT_HEAP_DUMP_DIR: &str = "heap-dumps";
+
+/// Figures out the port the server will listen on. With `--random-port`, a free port is picked
+/// and written to `server.properties`. It also replaces the port from the config or the
+/// environment, so the server isn't launched with `--port` pointing elsewhere.
+fn resolve_port(opt: &Opt, settings: &mut EffectiveSettings, current_dir: &Path) -> Result<u16> {
+	if opt.random_port {
+		let bind_ip = server_bind_ip(current_dir)?;
+		let port = TcpListener::bind((bind_ip, 0))?.local_addr()?.port();
+		properties::set_property(current_dir, "server-port", &port.to_string())?;
+		info!("=== Server will listen on the random port {} ===", port);
+		settings.port = Some(settings::Sourced {
+			value: port,
+			source: settings::Source::Flag,
+		});
+		return Ok(port);
 	}
 
-	#[cfg(windows)]
-	sender.send(rivatiker::State::Default).unwrap();
+	if let Some(port) = &settings.port {
+		return Ok(port.value);
+	}
 
-	Ok(())
+	let port = properties::get_property(current_dir, "server-port")?;
+	Ok(port
+		.and_then(|port| port.parse().ok())
+		.unwrap_or(DEFAULT_SERVER_PORT))
 }
 
-#[cfg(not(windows))]
-const JAVA: &str = "java";
-#[cfg(windows)]
-const JAVA: &str = "java.exe";
-
-#[cfg(not(windows))]
-fn find_java() -> Option<PathBuf> {
-	let java = find_java_in(JAVA.as_ref());
-	if java.is_some() {
-		return java;
-	}
+/// Where the runner pings its own server, localhost when it binds all interfaces.
+fn ping_address(current_dir: &Path, port: u16) -> Result<SocketAddr> {
+	let bind_ip = server_bind_ip(current_dir)?;
+	let ip = if bind_ip.is_unspecified() {
+		IpAddr::V4(Ipv4Addr::LOCALHOST)
+	} else {
+		bind_ip
+	};
+	Ok((ip, port).into())
+}
 
-	let path: PathBuf = ["/usr/bin", JAVA].iter().collect();
-	find_java_in(&path)
+/// The `server-ip` the server binds, all interfaces when it isn't set.
+fn server_bind_ip(current_dir: &Path) -> Result<IpAddr> {
+	Ok(properties::get_property(current_dir, "server-ip")?
+		.and_then(|ip| ip.parse().ok())
+		.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED)))
 }
 
-#[cfg(windows)]
-fn find_java() -> Option<PathBuf> {
-	use winapi::um::knownfolders::*;
+/// Fails when something already listens on the port.
+fn check_port_free(ip: IpAddr, port: u16) -> Result<(), RunnerError> {
+	TcpListener::bind((ip, port))
+		.map(drop)
+		.map_err(|source| RunnerError::PortInUse { ip, port, source })
+}
 
-	let java = find_java_in(JAVA.as_ref());
-	if java.is_some() {
-		return java;
+fn world_size(working_directory: &Path) -> Option<u64> {
+	match world::world_size(working_directory) {
+		Ok(v) => Some(v),
+		Err(e) => {
+			warn!("Failed to compute the world size: {:?}.", e);
+			None
+		}
 	}
+}
 
-	let x86_program_files = winutils::get_known_folder(&FOLDERID_ProgramFilesX86)
-		.unwrap_or_else(|| String::from(r"C:\Progam Files (x86)"));
-
-	let bundled_jre: PathBuf = [
-		&x86_program_files,
-		"Minecraft Launcher",
-		"runtime",
-		"jre-x64",
-		"bin",
-		JAVA,
-	]
-	.iter()
-	.collect();
+/// Sets `server-ip`, or clears it when `bind_ip` is empty.
+fn apply_bind_ip(current_dir: &Path, bind_ip: &str) -> Result<()> {
+	if bind_ip.is_empty() {
+		properties::set_property(current_dir, "server-ip", "")?;
+		info!("Server will bind all interfaces.");
+		return Ok(());
+	}
+	let ip: IpAddr = bind_ip
+		.parse()
+		.with_context(|| format!("Invalid --bind-ip \"{}\"", bind_ip))?;
+	properties::set_property(current_dir, "server-ip", &ip.to_string())?;
+	info!("Server will bind {}.", ip);
+	Ok(())
+}
 
-	find_java_in(&bundled_jre)
+/// Sets `level-seed`, unless the world already exists and the seed would have no effect.
+fn apply_seed(current_dir: &Path, seed: &str) -> Result<()> {
+	let level_name = world::level_name(current_dir);
+	if current_dir.join(&level_name).exists() {
+		warn!(
+			"The world \"{}\" already exists, the seed \"{}\" has no effect.",
+			level_name, seed
+		);
+		return Ok(());
+	}
+	properties::set_property(current_dir, "level-seed", seed)?;
+	info!(
+		"World \"{}\" will be generated with seed \"{}\".",
+		level_name, seed
+	);
+	Ok(())
 }
 
-fn find_java_in(place: &Path) -> Option<PathBuf> {
-	match Command::new(place).arg("-version").output() {
-		Ok(output) => {
-			if output.status.success() {
-				Some(PathBuf::from(place))
-			} else {
-				debug!("{}", String::from_utf8_lossy(&output.stdout));
-				debug!("{}", String::from_utf8_lossy(&output.stderr));
-				None
+fn print_previous_log(working_directory: &Path, lines: usize) {
+	let log_path = logs::latest_log_path(working_directory);
+	match logs::tail(&log_path, lines) {
+		Ok(tail) => {
+			info!(
+				"Last lines of the previous session ({}):",
+				log_path.display()
+			);
+			for line in tail {
+				println!("{}", line);
 			}
 		}
-		Err(e) => {
-			debug!("{:?}", e);
-			None
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+			debug!("No previous log at {}.", log_path.display())
 		}
+		Err(e) => warn!("Failed to read {}: {:?}.", log_path.display(), e),
 	}
 }
 
-#[cfg(windows)]
-mod winutils {
-	use winapi::{ctypes::c_void, shared::guiddef::GUID, um::shlobj::*};
-
-	pub fn get_known_folder(folder_id: &GUID) -> Option<String> {
-		let mut path: *mut u16 = std::ptr::null_mut();
-		let result = unsafe {
-			SHGetKnownFolderPath(
-				folder_id,
-				KF_FLAG_DEFAULT,
-				std::ptr::null_mut::<c_void>(),
-				&mut path as *mut *mut u16,
-			)
-		};
-		let path = unsafe {
-			let mut len = 0usize;
-			while *path.add(len) != 0 {
-				len += 1;
-			}
-			let path_str = String::from_utf16_lossy(std::slice::from_raw_parts_mut(path, len));
-			winapi::um::combaseapi::CoTaskMemFree(path as *mut c_void);
-			path_str
-		};
-		if result == 0 {
-			Some(path)
-		} else {
-			None
+fn log_mods(current_dir: &Path) {
+	for dir in mods::MOD_DIRECTORIES {
+		let mods = mods::scan(&current_dir.join(dir));
+		if mods.is_empty() {
+			continue;
 		}
+		info!("{} jars in {}/:", mods.len(), dir);
+		for mod_info in mods {
+			info!("  {}", mod_info);
+		}
+	}
+}
+
+const NOTIFICATION_LOG_LINES: usize = 10;
+
+/// Parses the reasons `restart_messages` of the config is keyed by.
+fn restart_messages(
+	config: &MinecraftRunnerConfig,
+) -> Result<HashMap<server::RestartReason, String>> {
+	config
+		.restart_messages
+		.iter()
+		.map(|(reason, message)| {
+			let reason = reason
+				.parse()
+				.map_err(|e: String| anyhow::anyhow!("Invalid restart_messages: {}", e))?;
+			Ok((reason, message.clone()))
+		})
+		.collect()
+}
+
+fn notify_crash(
+	url: &str,
+	server_name: &str,
+	working_directory: &Path,
+	status: ExitStatus,
+	crash_report: Option<String>,
+) {
+	let last_lines = logs::tail(
+		&logs::latest_log_path(working_directory),
+		NOTIFICATION_LOG_LINES,
+	)
+	.unwrap_or_default();
+	let notification = webhook::Notification::crash(
+		server_name,
+		status.code(),
+		status.to_string(),
+		last_lines,
+		crash_report,
+	);
+	if let Err(e) = webhook::send(url, &notification) {
+		warn!("Failed to send the crash notification: {:?}.", e);
 	}
 }
@@ -0,0 +1,59 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+/// Gauges exported at `GET /metrics` in the Prometheus text format. Subsystems set their values
+/// whenever they change, the text is rendered on demand.
+#[derive(Default)]
+pub struct Metrics {
+	gauges: Mutex<BTreeMap<String, Gauge>>,
+}
+
+struct Gauge {
+	help: &'static str,
+	value: f64,
+}
+
+impl Metrics {
+	pub fn set(&self, name: &str, help: &'static str, value: f64) {
+		self.gauges
+			.lock()
+			.unwrap()
+			.insert(name.to_string(), Gauge { help, value });
+	}
+
+	pub fn render(&self) -> String {
+		let mut text = String::new();
+		for (name, gauge) in self.gauges.lock().unwrap().iter() {
+			let _ = writeln!(text, "# HELP {} {}", name, gauge.help);
+			let _ = writeln!(text, "# TYPE {} gauge", name);
+			let _ = writeln!(text, "{} {}", name, gauge.value);
+		}
+		text
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn renders_text_format() {
+		let metrics = Metrics::default();
+		metrics.set(
+			"minecraft_ready",
+			"Whether the server finished loading",
+			1.0,
+		);
+		metrics.set("minecraft_restarts_total", "Restarts since launch", 2.0);
+		assert_eq!(
+			metrics.render(),
+			"# HELP minecraft_ready Whether the server finished loading\n\
+			 # TYPE minecraft_ready gauge\n\
+			 minecraft_ready 1\n\
+			 # HELP minecraft_restarts_total Restarts since launch\n\
+			 # TYPE minecraft_restarts_total gauge\n\
+			 minecraft_restarts_total 2\n"
+		);
+	}
+}
@@ -0,0 +1,178 @@
+use anyhow::{Context, Result};
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Directories plugins (Bukkit, Spigot, Paper) and mods (Fabric, Forge) are installed to.
+pub const MOD_DIRECTORIES: &[&str] = &["plugins", "mods"];
+
+/// A plugin or mod jar, with the name and version from its metadata when it has any.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ModInfo {
+	pub path: PathBuf,
+	pub name: Option<String>,
+	pub version: Option<String>,
+}
+
+impl std::fmt::Display for ModInfo {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match &self.name {
+			Some(name) => write!(f, "{}", name)?,
+			None => write!(f, "{}", self.path.display())?,
+		}
+		if let Some(version) = &self.version {
+			write!(f, " {}", version)?;
+		}
+		Ok(())
+	}
+}
+
+/// Plugins and mods in all the [`MOD_DIRECTORIES`] of the server.
+pub fn scan_server(working_directory: &Path) -> Vec<ModInfo> {
+	MOD_DIRECTORIES
+		.iter()
+		.flat_map(|dir| scan(&working_directory.join(dir)))
+		.collect()
+}
+
+/// Jars in `dir`, sorted by file name. A missing directory has no mods.
+pub fn scan(dir: &Path) -> Vec<ModInfo> {
+	let entries = match std::fs::read_dir(dir) {
+		Ok(v) => v,
+		Err(_) => return Vec::new(),
+	};
+	let mut jars: Vec<PathBuf> = entries
+		.filter_map(|entry| entry.ok())
+		.map(|entry| entry.path())
+		.filter(|path| path.is_file())
+		.filter(|path| path.extension().map(|ext| ext == "jar").unwrap_or_default())
+		.collect();
+	jars.sort();
+	jars.into_iter()
+		.map(|path| match read_metadata(&path) {
+			Ok((name, version)) => ModInfo {
+				path,
+				name,
+				version,
+			},
+			Err(e) => {
+				debug!("Failed to read the metadata of {:?}: {:?}", path, e);
+				ModInfo {
+					path,
+					name: None,
+					version: None,
+				}
+			}
+		})
+		.collect()
+}
+
+/// Name and version from `fabric.mod.json`, `plugin.yml`, `META-INF/mods.toml` (Forge) or, as a
+/// last resort, the manifest.
+fn read_metadata(jar: &Path) -> Result<(Option<String>, Option<String>)> {
+	let file = std::fs::File::open(jar).with_context(|| format!("Path: {:?}", jar))?;
+	let mut archive = zip::ZipArchive::new(file)?;
+	let mut read_entry = |name: &str| -> Option<String> {
+		let mut content = String::new();
+		archive
+			.by_name(name)
+			.ok()?
+			.read_to_string(&mut content)
+			.ok()?;
+		Some(content)
+	};
+
+	let manifest = read_entry("META-INF/MANIFEST.MF").unwrap_or_default();
+	let implementation_version = manifest_attribute(&manifest, "Implementation-Version");
+
+	if let Some(content) = read_entry("fabric.mod.json") {
+		let info: FabricModJson = serde_json::from_str(&content)?;
+		return Ok((Some(info.name.unwrap_or(info.id)), info.version));
+	}
+	if let Some(content) = read_entry("plugin.yml") {
+		return Ok((
+			yaml_value(&content, "name"),
+			yaml_value(&content, "version"),
+		));
+	}
+	if let Some(content) = read_entry("META-INF/mods.toml") {
+		let info: ForgeModsToml = toml::from_str(&content)?;
+		if let Some(info) = info.mods.into_iter().next() {
+			// Forge fills this placeholder in from the manifest
+			let version = match info.version.as_deref() {
+				Some("${file.jarVersion}") => implementation_version,
+				_ => info.version,
+			};
+			return Ok((Some(info.display_name.unwrap_or(info.mod_id)), version));
+		}
+	}
+	Ok((
+		manifest_attribute(&manifest, "Implementation-Title"),
+		implementation_version,
+	))
+}
+
+#[derive(Deserialize)]
+struct FabricModJson {
+	id: String,
+	name: Option<String>,
+	version: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ForgeModsToml {
+	#[serde(default)]
+	mods: Vec<ForgeMod>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ForgeMod {
+	mod_id: String,
+	display_name: Option<String>,
+	version: Option<String>,
+}
+
+fn manifest_attribute(manifest: &str, attribute: &str) -> Option<String> {
+	manifest
+		.lines()
+		.find_map(|line| line.strip_prefix(attribute)?.strip_prefix(':'))
+		.map(|value| value.trim().to_string())
+}
+
+/// Value of a top level key of a flat YAML document, which is all `plugin.yml` needs.
+fn yaml_value(yaml: &str, key: &str) -> Option<String> {
+	yaml.lines()
+		.find_map(|line| line.strip_prefix(key)?.strip_prefix(':'))
+		.map(|value| {
+			value
+				.trim()
+				.trim_matches(|c| c == '"' || c == '\'')
+				.to_string()
+		})
+		.filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn plugin_yml_values() {
+		let yaml =
+			"name: EssentialsX\nversion: '2.19.0'\nmain: com.earth2me.essentials.Essentials\n";
+		assert_eq!(yaml_value(yaml, "name").as_deref(), Some("EssentialsX"));
+		assert_eq!(yaml_value(yaml, "version").as_deref(), Some("2.19.0"));
+		assert_eq!(yaml_value(yaml, "author"), None);
+	}
+
+	#[test]
+	fn manifest_values() {
+		let manifest = "Manifest-Version: 1.0\r\nImplementation-Version: 1.2.3\r\n";
+		assert_eq!(
+			manifest_attribute(manifest, "Implementation-Version").as_deref(),
+			Some("1.2.3")
+		);
+	}
+}
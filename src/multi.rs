@@ -0,0 +1,194 @@
+use crate::config::PreLaunchJar;
+use crate::find_jar::{self, FindServerJar};
+use crate::server::ServerManager;
+use crate::settings::EffectiveSettings;
+use crate::{jvm, lock, prelaunch};
+use anyhow::Result;
+use log::*;
+use std::ffi::OsStr;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+
+/// Finds the servers in the immediate subdirectories of `root`: directories containing a jar or an
+/// `eula.txt`.
+pub fn find_servers(root: &Path) -> Result<Vec<PathBuf>> {
+	let mut servers = Vec::new();
+	for entry in std::fs::read_dir(root)? {
+		let path = entry?.path();
+		if !path.is_dir() {
+			continue;
+		}
+		let is_server = std::fs::read_dir(&path)?
+			.filter_map(|entry| entry.ok())
+			.map(|entry| entry.path())
+			.any(|file| {
+				file.extension() == Some(OsStr::new("jar"))
+					|| file.file_name() == Some(OsStr::new("eula.txt"))
+			});
+		if is_server {
+			servers.push(path);
+		}
+	}
+	servers.sort();
+	Ok(servers)
+}
+
+/// Launches every server found in `root` and waits until all of them exit. Their output is
+/// prefixed with the directory name. Typing `stop`, or SIGINT/SIGTERM on Unix, stops all of them.
+///
+/// Each server directory is locked like a single server's, and gets the pre-launch jars run in it
+/// and the startup and stop commands.
+pub fn run_multi(
+	root: &Path,
+	java: &Path,
+	settings: &EffectiveSettings,
+	pre_launch_jars: &[PreLaunchJar],
+	force: bool,
+) -> Result<()> {
+	if settings.port.is_some() {
+		anyhow::bail!("A port can't be set with --multi, every server uses its server.properties");
+	}
+
+	let mut servers = Vec::new();
+	// Held until every server exited
+	let mut locks = Vec::new();
+	for directory in find_servers(root)? {
+		let name = directory
+			.file_name()
+			.map(|name| name.to_string_lossy().into_owned())
+			.unwrap_or_default();
+		match lock::InstanceLock::acquire(&directory, force) {
+			Ok(lock) => locks.push(lock),
+			Err(e) => {
+				warn!("Skipping \"{}\": {:?}", name, e);
+				continue;
+			}
+		}
+		let server_jar = match find_jar::find_server_jar(&directory, &[])? {
+			FindServerJar::ServerJar(jar)
+			| FindServerJar::OneUnknownJar(jar)
+			| FindServerJar::PreferredJar(jar, _) => jar,
+			FindServerJar::MultipleJars(_) => {
+				warn!(
+					"Skipping \"{}\", it has multiple jars to choose from.",
+					name
+				);
+				continue;
+			}
+			FindServerJar::None => {
+				warn!("Skipping \"{}\", it has no jar.", name);
+				continue;
+			}
+		};
+		let server_jar = match server_jar.file_name().and_then(OsStr::to_str) {
+			Some(server_jar) => server_jar,
+			None => {
+				warn!(
+					"Skipping \"{}\", the name of {:?} isn't valid UTF-8.",
+					name, server_jar
+				);
+				continue;
+			}
+		};
+		let args = jvm::server_args(
+			settings,
+			&directory,
+			&jvm::Launch::Jar(server_jar.to_string()),
+			&[],
+		);
+
+		if let Err(e) = prelaunch::run_all(java, pre_launch_jars, &directory) {
+			error!("Skipping \"{}\": {:?}", name, e);
+			continue;
+		}
+
+		let minecraft_server = Arc::new(
+			ServerManager::new(java.to_path_buf(), args, directory.clone())
+				.with_startup_commands(settings.startup_commands.value.clone())
+				.with_stop_commands(settings.stop_commands.value.clone())
+				.with_output_prefix(format!("[{}]", name)),
+		);
+		info!("Launching \"{}\" with \"{}\".", name, server_jar);
+		if let Err(e) = minecraft_server.spawn() {
+			error!("Failed to launch \"{}\": {:?}", name, e);
+			continue;
+		}
+		servers.push((name, minecraft_server));
+	}
+	if servers.is_empty() {
+		anyhow::bail!("No servers found in {:?}", root);
+	}
+
+	{
+		let servers: Vec<Arc<ServerManager>> = servers
+			.iter()
+			.map(|(_, server)| Arc::clone(server))
+			.collect();
+		let (stop_sender, stop_receiver) = mpsc::channel();
+		#[cfg(unix)]
+		crate::signals::notify_termination(stop_sender.clone())?;
+		std::thread::spawn(move || read_stop(stop_sender));
+		std::thread::spawn(move || {
+			if stop_receiver.recv().is_ok() {
+				stop_all(&servers);
+			}
+		});
+	}
+
+	let handles: Vec<_> = servers
+		.into_iter()
+		.map(|(name, minecraft_server)| {
+			std::thread::spawn(move || match minecraft_server.wait() {
+				Ok(status) => info!("\"{}\" exited with status: {}", name, status),
+				Err(e) => error!("\"{}\" exited with error: {:?}", name, e),
+			})
+		})
+		.collect();
+	for handle in handles {
+		let _ = handle.join();
+	}
+	Ok(())
+}
+
+/// Asks to stop once `stop` is typed into the console. When the console input is closed, e.g.
+/// under systemd or nohup, the servers are left running.
+fn read_stop(stop: mpsc::Sender<()>) {
+	let stdin = std::io::stdin();
+	for line in stdin.lock().lines() {
+		match line {
+			Ok(line) if line.trim() == "stop" => {
+				let _ = stop.send(());
+				return;
+			}
+			Ok(line) => warn!(
+				"Only \"stop\" is supported with multiple servers, ignoring \"{}\".",
+				line
+			),
+			Err(e) => {
+				debug!("Console input closed: {:?}", e);
+				return;
+			}
+		}
+	}
+	debug!("Console input closed, \"stop\" can't be typed anymore.");
+}
+
+/// Stops all servers in parallel.
+fn stop_all(servers: &[Arc<ServerManager>]) {
+	info!("Stopping all servers.");
+	let handles: Vec<_> = servers
+		.iter()
+		.map(|server| {
+			let server = Arc::clone(server);
+			std::thread::spawn(move || {
+				if let Err(e) = server.stop() {
+					error!("Failed to stop the server: {:?}", e);
+				}
+			})
+		})
+		.collect();
+	for handle in handles {
+		let _ = handle.join();
+	}
+}
@@ -0,0 +1,161 @@
+//! PID of the server JVM, kept in the server directory while it runs. A pidfile left behind by a
+//! runner that was killed points to a JVM that may still hold the world and the port.
+use anyhow::{Context, Result};
+use log::*;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use sysinfo::{Pid, ProcessExt, Signal, System, SystemExt};
+
+pub const PIDFILE_FILENAME: &str = "minecraft_server.pid";
+
+/// How long an orphaned server gets to exit after being asked to terminate.
+const TERMINATE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub fn write(path: &Path, pid: u32) -> Result<()> {
+	std::fs::write(path, pid.to_string()).with_context(|| format!("Path: {:?}", path))
+}
+
+pub fn remove(path: &Path) {
+	if let Err(e) = std::fs::remove_file(path) {
+		if e.kind() != std::io::ErrorKind::NotFound {
+			warn!("Failed to remove the pidfile {:?}: {:?}.", path, e);
+		}
+	}
+}
+
+pub fn read(path: &Path) -> Result<Option<u32>> {
+	match std::fs::read_to_string(path) {
+		Ok(pid) => pid
+			.trim()
+			.parse()
+			.map(Some)
+			.with_context(|| format!("Invalid pidfile {:?}", path)),
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+		Err(e) => Err(e).with_context(|| format!("Path: {:?}", path)),
+	}
+}
+
+/// Terminates the server left running by a previous runner, according to the pidfile in
+/// `working_directory`. A process that reused the PID is left alone: it has to be java running in
+/// `working_directory`.
+pub fn kill_orphan(working_directory: &Path) -> Result<()> {
+	let path = working_directory.join(PIDFILE_FILENAME);
+	let pid = match read(&path)? {
+		Some(v) => v,
+		None => {
+			debug!("No pidfile, no orphaned server to kill.");
+			return Ok(());
+		}
+	};
+
+	let mut system = System::new();
+	let process_pid = pid as Pid;
+	if !system.refresh_process(process_pid) {
+		info!(
+			"The server of the previous run (PID {}) isn't running anymore.",
+			pid
+		);
+		remove(&path);
+		return Ok(());
+	}
+	let process = match system.process(process_pid) {
+		Some(v) => v,
+		None => return Ok(()),
+	};
+	if !is_server_process(process.cmd(), process.cwd(), working_directory) {
+		info!(
+			"PID {} of the pidfile now belongs to an unrelated process ({}), leaving it alone.",
+			pid,
+			process.name()
+		);
+		remove(&path);
+		return Ok(());
+	}
+
+	warn!(
+		"=== The server of a previous run is still running (PID {}), terminating it ===",
+		pid
+	);
+	// Windows can only kill
+	if !process.kill(Signal::Term) {
+		process.kill(Signal::Kill);
+	}
+	let deadline = Instant::now() + TERMINATE_TIMEOUT;
+	while system.refresh_process(process_pid) {
+		if Instant::now() >= deadline {
+			warn!(
+				"The orphaned server didn't exit within {}s, killing it.",
+				TERMINATE_TIMEOUT.as_secs()
+			);
+			if let Some(process) = system.process(process_pid) {
+				process.kill(Signal::Kill);
+			}
+			std::thread::sleep(Duration::from_secs(1));
+			if system.refresh_process(process_pid) {
+				anyhow::bail!("Failed to kill the orphaned server (PID {})", pid);
+			}
+			break;
+		}
+		std::thread::sleep(Duration::from_millis(200));
+	}
+	info!("Killed the orphaned server (PID {}).", pid);
+	remove(&path);
+	Ok(())
+}
+
+/// Whether the command line is java's and the working directory of the process is the server
+/// directory. A process whose working directory is unknown is never taken for the server.
+fn is_server_process(cmd: &[String], cwd: &Path, working_directory: &Path) -> bool {
+	let is_java = cmd
+		.first()
+		.and_then(|program| Path::new(program).file_stem())
+		.and_then(|stem| stem.to_str())
+		.map(|stem| stem == "java" || stem == "javaw")
+		.unwrap_or_default();
+	let same_directory = !cwd.as_os_str().is_empty()
+		&& match (cwd.canonicalize(), working_directory.canonicalize()) {
+			(Ok(cwd), Ok(working_directory)) => cwd == working_directory,
+			_ => cwd == working_directory,
+		};
+	is_java && same_directory
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn cmd(cmd: &[&str]) -> Vec<String> {
+		cmd.iter().map(|arg| arg.to_string()).collect()
+	}
+
+	#[test]
+	fn only_java_in_the_server_directory_is_killed() {
+		let server = Path::new("/srv/minecraft");
+		let java = cmd(&["/usr/bin/java", "-Xmx4096M", "-jar", "server.jar", "nogui"]);
+		assert!(is_server_process(&java, server, server));
+		assert!(is_server_process(
+			&cmd(&["C:\\Java\\bin\\javaw.exe", "-jar", "server.jar"]),
+			server,
+			server
+		));
+		assert!(!is_server_process(&java, Path::new(""), server));
+		assert!(!is_server_process(&java, Path::new("/srv/other"), server));
+		assert!(!is_server_process(
+			&cmd(&["/usr/bin/vim", "server.properties"]),
+			server,
+			server
+		));
+		assert!(!is_server_process(&[], server, server));
+	}
+
+	#[test]
+	fn pidfile_round_trip() {
+		let path =
+			std::env::temp_dir().join(format!("minecraft_runner_pid_{}", std::process::id()));
+		assert_eq!(read(&path).unwrap(), None);
+		write(&path, 1234).unwrap();
+		assert_eq!(read(&path).unwrap(), Some(1234));
+		remove(&path);
+		assert_eq!(read(&path).unwrap(), None);
+	}
+}
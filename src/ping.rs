@@ -0,0 +1,174 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+
+/// The protocol version clients send when they only want the status, whatever the server version.
+const STATUS_PROTOCOL_VERSION: i32 = -1;
+
+#[derive(Deserialize)]
+struct Status {
+	version: Option<Version>,
+	players: Players,
+	#[serde(default)]
+	description: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct Version {
+	name: String,
+	protocol: i32,
+}
+
+#[derive(Deserialize)]
+struct Players {
+	online: u32,
+	max: u32,
+}
+
+/// What the server answered to the Server List Ping.
+#[derive(Serialize, Debug)]
+pub struct PingResult {
+	pub address: SocketAddr,
+	pub latency_ms: u64,
+	pub version: Option<String>,
+	pub protocol: Option<i32>,
+	pub players_online: u32,
+	pub players_max: u32,
+	/// The MOTD, without formatting
+	pub description: String,
+}
+
+/// Asks the server how many players are online, with the Server List Ping the multiplayer menu
+/// uses.
+pub fn online_players(address: SocketAddr, timeout: Duration) -> Result<u32> {
+	ping(address, timeout).map(|result| result.players_online)
+}
+
+/// Sends the Server List Ping the multiplayer menu uses and returns the server's status.
+pub fn ping(address: SocketAddr, timeout: Duration) -> Result<PingResult> {
+	let started = Instant::now();
+	let mut stream = TcpStream::connect_timeout(&address, timeout)?;
+	stream.set_read_timeout(Some(timeout))?;
+	stream.set_write_timeout(Some(timeout))?;
+
+	let host = address.ip().to_string();
+	let mut handshake = Vec::new();
+	write_varint(&mut handshake, 0x00);
+	write_varint(&mut handshake, STATUS_PROTOCOL_VERSION);
+	write_varint(&mut handshake, host.len() as i32);
+	handshake.extend_from_slice(host.as_bytes());
+	handshake.extend_from_slice(&address.port().to_be_bytes());
+	// Next state: status
+	write_varint(&mut handshake, 1);
+
+	let mut packets = Vec::new();
+	write_varint(&mut packets, handshake.len() as i32);
+	packets.extend_from_slice(&handshake);
+	// Status request: an empty packet with id 0
+	packets.extend_from_slice(&[0x01, 0x00]);
+	stream.write_all(&packets)?;
+
+	let _length = read_varint(&mut stream)?;
+	let packet_id = read_varint(&mut stream)?;
+	if packet_id != 0x00 {
+		anyhow::bail!("Unexpected status response packet {:#x}", packet_id);
+	}
+	let json_length = read_varint(&mut stream)?;
+	if json_length < 0 {
+		anyhow::bail!("Invalid status response length {}", json_length);
+	}
+	let mut json = vec![0; json_length as usize];
+	stream.read_exact(&mut json)?;
+	let latency = started.elapsed();
+	let status: Status = serde_json::from_slice(&json)?;
+	Ok(PingResult {
+		address,
+		latency_ms: latency.as_millis() as u64,
+		version: status.version.as_ref().map(|version| version.name.clone()),
+		protocol: status.version.map(|version| version.protocol),
+		players_online: status.players.online,
+		players_max: status.players.max,
+		description: description_text(&status.description),
+	})
+}
+
+/// Flattens the chat component of the description, either a plain string or `{"text": ..,
+/// "extra": [..]}`.
+fn description_text(description: &serde_json::Value) -> String {
+	match description {
+		serde_json::Value::String(text) => text.clone(),
+		serde_json::Value::Array(parts) => parts.iter().map(description_text).collect(),
+		serde_json::Value::Object(component) => {
+			let mut text = component
+				.get("text")
+				.map(description_text)
+				.unwrap_or_default();
+			if let Some(extra) = component.get("extra") {
+				text.push_str(&description_text(extra));
+			}
+			text
+		}
+		_ => String::new(),
+	}
+}
+
+fn write_varint(buf: &mut Vec<u8>, value: i32) {
+	let mut value = value as u32;
+	loop {
+		let byte = (value & 0x7f) as u8;
+		value >>= 7;
+		if value == 0 {
+			buf.push(byte);
+			return;
+		}
+		buf.push(byte | 0x80);
+	}
+}
+
+fn read_varint(reader: &mut impl Read) -> Result<i32> {
+	let mut value = 0u32;
+	for i in 0..5 {
+		let mut byte = [0u8];
+		reader.read_exact(&mut byte)?;
+		value |= ((byte[0] & 0x7f) as u32) << (7 * i);
+		if byte[0] & 0x80 == 0 {
+			return Ok(value as i32);
+		}
+	}
+	anyhow::bail!("VarInt is longer than 5 bytes")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn varint_encoding() {
+		for (value, bytes) in [
+			(0, vec![0x00]),
+			(300, vec![0xac, 0x02]),
+			(-1, vec![0xff, 0xff, 0xff, 0xff, 0x0f]),
+		]
+		.iter()
+		{
+			let mut buf = Vec::new();
+			write_varint(&mut buf, *value);
+			assert_eq!(&buf, bytes);
+			assert_eq!(read_varint(&mut buf.as_slice()).unwrap(), *value);
+		}
+	}
+
+	#[test]
+	fn descriptions() {
+		assert_eq!(description_text(&serde_json::json!("A server")), "A server");
+		assert_eq!(
+			description_text(&serde_json::json!({
+				"text": "A ",
+				"extra": [{"text": "Minecraft", "bold": true}, " server"]
+			})),
+			"A Minecraft server"
+		);
+	}
+}
@@ -0,0 +1,180 @@
+use crate::properties;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const PROFILE_URL: &str = "https://api.mojang.com/users/profiles/minecraft/";
+
+/// The player lists the server reads on launch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlayerList {
+	Whitelist,
+	Ops,
+}
+
+impl PlayerList {
+	pub fn path(self, working_directory: &Path) -> PathBuf {
+		working_directory.join(match self {
+			PlayerList::Whitelist => "whitelist.json",
+			PlayerList::Ops => "ops.json",
+		})
+	}
+
+	/// Console command applying the same change to a running server.
+	pub fn console_command(self, add: bool, name: &str) -> String {
+		match (self, add) {
+			(PlayerList::Whitelist, true) => format!("whitelist add {}", name),
+			(PlayerList::Whitelist, false) => format!("whitelist remove {}", name),
+			(PlayerList::Ops, true) => format!("op {}", name),
+			(PlayerList::Ops, false) => format!("deop {}", name),
+		}
+	}
+}
+
+/// An entry of `whitelist.json` or `ops.json`. Fields only some lists have (like the op `level`)
+/// are kept as they are.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Player {
+	pub uuid: String,
+	pub name: String,
+	#[serde(flatten)]
+	pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+pub fn read_list(path: &Path) -> Result<Vec<Player>> {
+	match std::fs::read_to_string(path) {
+		Ok(str) => serde_json::from_str(&str).with_context(|| format!("Path: {:?}", path)),
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+		Err(e) => Err(e).with_context(|| format!("Path: {:?}", path)),
+	}
+}
+
+fn write_list(path: &Path, players: &[Player]) -> Result<()> {
+	let str = serde_json::to_string_pretty(players)?;
+	std::fs::write(path, str).with_context(|| format!("Path: {:?}", path))
+}
+
+/// Adds the player to the list. Returns false if they were already on it.
+pub fn add(working_directory: &Path, list: PlayerList, name: &str) -> Result<bool> {
+	let path = list.path(working_directory);
+	let mut players = read_list(&path)?;
+	if players
+		.iter()
+		.any(|player| player.name.eq_ignore_ascii_case(name))
+	{
+		return Ok(false);
+	}
+
+	let mut player = resolve_player(working_directory, name)?;
+	if list == PlayerList::Ops {
+		let level = properties::get_property(working_directory, "op-permission-level")?
+			.and_then(|level| level.parse::<u8>().ok())
+			.unwrap_or(4);
+		player.extra.insert(String::from("level"), level.into());
+		player
+			.extra
+			.insert(String::from("bypassesPlayerLimit"), false.into());
+	}
+	players.push(player);
+	write_list(&path, &players)?;
+	Ok(true)
+}
+
+/// Removes the player from the list. Returns false if they weren't on it.
+pub fn remove(working_directory: &Path, list: PlayerList, name: &str) -> Result<bool> {
+	let path = list.path(working_directory);
+	let mut players = read_list(&path)?;
+	let len = players.len();
+	players.retain(|player| !player.name.eq_ignore_ascii_case(name));
+	if players.len() == len {
+		return Ok(false);
+	}
+	write_list(&path, &players)?;
+	Ok(true)
+}
+
+#[derive(Deserialize)]
+struct Profile {
+	id: String,
+	name: String,
+}
+
+/// Looks up the UUID the server will know the player by: the Mojang account UUID, or the name
+/// based one when the server runs with `online-mode=false`.
+fn resolve_player(working_directory: &Path, name: &str) -> Result<Player> {
+	let online_mode = properties::get_property(working_directory, "online-mode")?
+		.map(|online_mode| online_mode != "false")
+		.unwrap_or(true);
+	if !online_mode {
+		return Ok(Player {
+			uuid: offline_uuid(name),
+			name: name.to_string(),
+			extra: Default::default(),
+		});
+	}
+
+	let response = match ureq::get(&format!("{}{}", PROFILE_URL, name)).call() {
+		// Unknown names get an empty or a not found response
+		Ok(response) if response.status() == 204 => None,
+		Ok(response) => Some(response),
+		Err(ureq::Error::Status(404, _)) => None,
+		Err(e) => {
+			return Err(e).with_context(|| format!("Failed to look up the player \"{}\"", name))
+		}
+	};
+	let response = match response {
+		Some(v) => v,
+		None => anyhow::bail!("There is no player named \"{}\".", name),
+	};
+	let profile: Profile = serde_json::from_str(&response.into_string()?)?;
+	Ok(Player {
+		uuid: hyphenate_uuid(&profile.id)?,
+		name: profile.name,
+		extra: Default::default(),
+	})
+}
+
+/// The UUID an offline mode server gives the player: a version 3 UUID of `OfflinePlayer:<name>`.
+pub fn offline_uuid(name: &str) -> String {
+	let mut bytes = md5::compute(format!("OfflinePlayer:{}", name)).0;
+	bytes[6] = (bytes[6] & 0x0f) | 0x30;
+	bytes[8] = (bytes[8] & 0x3f) | 0x80;
+	let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+	hyphenate_uuid(&hex).unwrap()
+}
+
+/// Mojang returns UUIDs without hyphens, the server files have them.
+fn hyphenate_uuid(hex: &str) -> Result<String> {
+	if hex.len() != 32 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+		anyhow::bail!("Invalid UUID \"{}\"", hex);
+	}
+	Ok(format!(
+		"{}-{}-{}-{}-{}",
+		&hex[0..8],
+		&hex[8..12],
+		&hex[12..16],
+		&hex[16..20],
+		&hex[20..32]
+	))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn offline_uuid_matches_the_server() {
+		assert_eq!(
+			offline_uuid("Notch"),
+			"b50ad385-829d-3141-a216-7e7d7539ba7f"
+		);
+	}
+
+	#[test]
+	fn op_fields_are_kept() {
+		let json = r#"[{"uuid":"069a79f4-44e9-4726-a5be-fca90e38aaf5","name":"Notch","bypassesPlayerLimit":false,"level":4}]"#;
+		let players: Vec<Player> = serde_json::from_str(json).unwrap();
+		assert_eq!(players[0].extra["level"], 4);
+		assert_eq!(serde_json::to_string(&players).unwrap(), json);
+	}
+}
@@ -0,0 +1,51 @@
+use crate::config::{FailurePolicy, PreLaunchJar};
+use anyhow::{Context, Result};
+use log::*;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+pub const AUTO_IP_JAR: &str = "AutoIpMinecraft.jar";
+
+/// The AutoIpMinecraft.jar step the runner always started with, now the first pre-launch jar.
+/// Most servers don't have the jar, so its failures are ignored.
+pub fn auto_ip(extra_args: Vec<String>) -> PreLaunchJar {
+	PreLaunchJar {
+		jar: PathBuf::from(AUTO_IP_JAR),
+		args: std::iter::once(String::from("server.properties"))
+			.chain(extra_args)
+			.collect(),
+		on_failure: FailurePolicy::Ignore,
+	}
+}
+
+/// Runs the jars in order with `java -jar`, in `working_directory`. Stops at the first failing jar
+/// with [`FailurePolicy::Abort`].
+pub fn run_all(java: &Path, jars: &[PreLaunchJar], working_directory: &Path) -> Result<()> {
+	for jar in jars {
+		let result = run(java, jar, working_directory);
+		match (result, jar.on_failure) {
+			(Ok(()), _) => (),
+			(Err(e), FailurePolicy::Ignore) => debug!("{:?}", e),
+			(Err(e), FailurePolicy::Warn) => warn!("{:?}", e),
+			(Err(e), FailurePolicy::Abort) => {
+				return Err(e.context("Launch aborted by a failed pre-launch jar"))
+			}
+		}
+	}
+	Ok(())
+}
+
+fn run(java: &Path, jar: &PreLaunchJar, working_directory: &Path) -> Result<()> {
+	info!("Running the pre-launch jar \"{}\".", jar.jar.display());
+	let status = Command::new(java)
+		.arg("-jar")
+		.arg(&jar.jar)
+		.args(&jar.args)
+		.current_dir(working_directory)
+		.status()
+		.with_context(|| format!("Failed to open {}", jar.jar.display()))?;
+	if !status.success() {
+		anyhow::bail!("{} exited with {}", jar.jar.display(), status);
+	}
+	Ok(())
+}
@@ -0,0 +1,126 @@
+use anyhow::Result;
+use std::fmt;
+use std::process::Child;
+use std::str::FromStr;
+
+/// Scheduling priority of the server process. Nice value on Unix, priority class on Windows.
+#[cfg(unix)]
+pub type Priority = Nice;
+#[cfg(windows)]
+pub type Priority = PriorityClass;
+
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Nice(i32);
+
+#[cfg(unix)]
+impl FromStr for Nice {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let nice: i32 = s
+			.parse()
+			.map_err(|_| format!("\"{}\" is not a number", s))?;
+		if !(-20..=19).contains(&nice) {
+			return Err(format!("Nice value {} is outside of -20..=19", nice));
+		}
+		Ok(Nice(nice))
+	}
+}
+
+#[cfg(unix)]
+impl fmt::Display for Nice {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "nice {}", self.0)
+	}
+}
+
+#[cfg(unix)]
+pub fn set_priority(child: &Child, priority: Priority) -> Result<()> {
+	let ret =
+		unsafe { libc::setpriority(libc::PRIO_PROCESS, child.id() as libc::id_t, priority.0) };
+	if ret == -1 {
+		return Err(std::io::Error::last_os_error().into());
+	}
+	Ok(())
+}
+
+#[cfg(windows)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PriorityClass {
+	Idle,
+	BelowNormal,
+	Normal,
+	AboveNormal,
+	High,
+}
+
+#[cfg(windows)]
+impl FromStr for PriorityClass {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"idle" => Ok(PriorityClass::Idle),
+			"below-normal" => Ok(PriorityClass::BelowNormal),
+			"normal" => Ok(PriorityClass::Normal),
+			"above-normal" => Ok(PriorityClass::AboveNormal),
+			"high" => Ok(PriorityClass::High),
+			_ => Err(format!(
+				"Unknown priority class \"{}\", expected idle, below-normal, normal, above-normal or high",
+				s
+			)),
+		}
+	}
+}
+
+#[cfg(windows)]
+impl fmt::Display for PriorityClass {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let name = match self {
+			PriorityClass::Idle => "idle",
+			PriorityClass::BelowNormal => "below-normal",
+			PriorityClass::Normal => "normal",
+			PriorityClass::AboveNormal => "above-normal",
+			PriorityClass::High => "high",
+		};
+		write!(f, "{} priority class", name)
+	}
+}
+
+#[cfg(windows)]
+pub fn set_priority(child: &Child, priority: Priority) -> Result<()> {
+	use std::os::windows::io::AsRawHandle;
+	use winapi::um::processthreadsapi::SetPriorityClass;
+	use winapi::um::winbase::{
+		ABOVE_NORMAL_PRIORITY_CLASS, BELOW_NORMAL_PRIORITY_CLASS, HIGH_PRIORITY_CLASS,
+		IDLE_PRIORITY_CLASS, NORMAL_PRIORITY_CLASS,
+	};
+
+	let class = match priority {
+		PriorityClass::Idle => IDLE_PRIORITY_CLASS,
+		PriorityClass::BelowNormal => BELOW_NORMAL_PRIORITY_CLASS,
+		PriorityClass::Normal => NORMAL_PRIORITY_CLASS,
+		PriorityClass::AboveNormal => ABOVE_NORMAL_PRIORITY_CLASS,
+		PriorityClass::High => HIGH_PRIORITY_CLASS,
+	};
+	let ret = unsafe { SetPriorityClass(child.as_raw_handle() as _, class) };
+	if ret == 0 {
+		return Err(std::io::Error::last_os_error().into());
+	}
+	Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn nice_range_is_validated() {
+		assert_eq!("-20".parse::<Nice>(), Ok(Nice(-20)));
+		assert_eq!("19".parse::<Nice>(), Ok(Nice(19)));
+		assert!("20".parse::<Nice>().is_err());
+		assert!("-21".parse::<Nice>().is_err());
+		assert!("low".parse::<Nice>().is_err());
+	}
+}
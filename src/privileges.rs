@@ -0,0 +1,28 @@
+use anyhow::Result;
+
+/// User and group the server process runs as.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RunAs {
+	pub uid: u32,
+	pub gid: u32,
+}
+
+/// Looks up `user` to launch the server as. Switching to another user requires the runner to be
+/// started as root.
+pub fn resolve_run_as(user: &str) -> Result<RunAs> {
+	let target = match users::get_user_by_name(user) {
+		Some(v) => v,
+		None => anyhow::bail!("User \"{}\" doesn't exist", user),
+	};
+	let current_uid = users::get_current_uid();
+	if current_uid != 0 && current_uid != target.uid() {
+		anyhow::bail!(
+			"Running the server as \"{}\" requires starting the runner as root",
+			user
+		);
+	}
+	Ok(RunAs {
+		uid: target.uid(),
+		gid: target.primary_group_id(),
+	})
+}
@@ -0,0 +1,159 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+pub const PROPERTIES_FILENAME: &str = "server.properties";
+
+pub fn properties_path(working_directory: &Path) -> PathBuf {
+	working_directory.join(PROPERTIES_FILENAME)
+}
+
+/// `server.properties` kept line by line, so editing a value preserves the comments and the order
+/// of the other entries.
+#[derive(Debug, Default)]
+pub struct ServerProperties {
+	lines: Vec<String>,
+}
+
+impl ServerProperties {
+	pub fn parse(str: &str) -> Self {
+		ServerProperties {
+			lines: str.lines().map(ToOwned::to_owned).collect(),
+		}
+	}
+
+	/// Reads the properties, treating a missing file as empty (the server creates it on the first
+	/// run).
+	pub fn read(path: &Path) -> Result<Self> {
+		match std::fs::read_to_string(path) {
+			Ok(str) => Ok(ServerProperties::parse(&str)),
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(ServerProperties::default()),
+			Err(e) => Err(e).with_context(|| format!("Path: {:?}", path)),
+		}
+	}
+
+	pub fn write(&self, path: &Path) -> Result<()> {
+		let mut str = self.lines.join("\n");
+		str.push('\n');
+		std::fs::write(path, str).with_context(|| format!("Path: {:?}", path))
+	}
+
+	pub fn get(&self, key: &str) -> Option<String> {
+		self.lines
+			.iter()
+			.filter_map(|line| split_entry(line))
+			.find(|(k, _)| *k == key)
+			.map(|(_, value)| unescape(value))
+	}
+
+	/// Sets the value of `key`, replacing the existing entry or appending a new one.
+	pub fn set(&mut self, key: &str, value: &str) {
+		let entry = format!("{}={}", key, escape(value));
+		let existing = self
+			.lines
+			.iter_mut()
+			.find(|line| matches!(split_entry(line), Some((k, _)) if k == key));
+		match existing {
+			Some(line) => *line = entry,
+			None => self.lines.push(entry),
+		}
+	}
+}
+
+/// Reads, edits and writes back the `server.properties` in the working directory.
+pub fn set_property(working_directory: &Path, key: &str, value: &str) -> Result<()> {
+	let path = properties_path(working_directory);
+	let mut properties = ServerProperties::read(&path)?;
+	properties.set(key, value);
+	properties.write(&path)
+}
+
+pub fn get_property(working_directory: &Path, key: &str) -> Result<Option<String>> {
+	Ok(ServerProperties::read(&properties_path(working_directory))?.get(key))
+}
+
+fn split_entry(line: &str) -> Option<(&str, &str)> {
+	let line = line.trim_start();
+	if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+		return None;
+	}
+	let separator = line.find(|c| c == '=' || c == ':')?;
+	Some((
+		line[..separator].trim_end(),
+		line[separator + 1..].trim_start(),
+	))
+}
+
+fn escape(value: &str) -> String {
+	let mut escaped = String::with_capacity(value.len());
+	for c in value.chars() {
+		match c {
+			'\\' | ':' | '=' | '#' | '!' => {
+				escaped.push('\\');
+				escaped.push(c);
+			}
+			_ => escaped.push(c),
+		}
+	}
+	escaped
+}
+
+fn unescape(value: &str) -> String {
+	let mut unescaped = String::with_capacity(value.len());
+	let mut chars = value.chars();
+	while let Some(c) = chars.next() {
+		if c != '\\' {
+			unescaped.push(c);
+			continue;
+		}
+		match chars.next() {
+			Some('t') => unescaped.push('\t'),
+			Some('n') => unescaped.push('\n'),
+			Some('r') => unescaped.push('\r'),
+			Some(c) => unescaped.push(c),
+			None => (),
+		}
+	}
+	unescaped
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const PROPERTIES: &str = "#Minecraft server properties\n\
+		#Sat Aug 07 12:00:00 CEST 2021\n\
+		server-port=25565\n\
+		motd=A Minecraft Server\n\
+		server-ip=\n";
+
+	#[test]
+	fn get_values() {
+		let properties = ServerProperties::parse(PROPERTIES);
+		assert_eq!(properties.get("server-port").as_deref(), Some("25565"));
+		assert_eq!(
+			properties.get("motd").as_deref(),
+			Some("A Minecraft Server")
+		);
+		assert_eq!(properties.get("server-ip").as_deref(), Some(""));
+		assert_eq!(properties.get("level-name"), None);
+	}
+
+	#[test]
+	fn set_preserves_other_lines() {
+		let mut properties = ServerProperties::parse(PROPERTIES);
+		properties.set("server-port", "25570");
+		properties.set("level-seed", "a:b");
+		assert_eq!(
+			properties.lines,
+			vec![
+				"#Minecraft server properties",
+				"#Sat Aug 07 12:00:00 CEST 2021",
+				"server-port=25570",
+				"motd=A Minecraft Server",
+				"server-ip=",
+				"level-seed=a\\:b",
+			]
+		);
+		assert_eq!(properties.get("level-seed").as_deref(), Some("a:b"));
+	}
+}
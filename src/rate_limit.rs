@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How many requests are allowed per time unit, e.g. `10/min`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+	pub requests: u32,
+	pub per: Duration,
+}
+
+impl FromStr for RateLimit {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (requests, unit) = match s.split_once('/') {
+			Some(v) => v,
+			None => return Err(format!("Invalid rate limit \"{}\", expected N/unit", s)),
+		};
+		let requests: u32 = requests
+			.trim()
+			.parse()
+			.map_err(|_| format!("Invalid request count \"{}\"", requests))?;
+		if requests == 0 {
+			return Err(String::from("The request count has to be at least 1"));
+		}
+		let per = match unit.trim() {
+			"s" | "sec" | "second" => Duration::from_secs(1),
+			"m" | "min" | "minute" => Duration::from_secs(60),
+			"h" | "hour" => Duration::from_secs(60 * 60),
+			unit => {
+				return Err(format!(
+					"Unknown unit \"{}\", expected sec, min or hour",
+					unit
+				))
+			}
+		};
+		Ok(RateLimit { requests, per })
+	}
+}
+
+struct Bucket {
+	tokens: f64,
+	updated: Instant,
+}
+
+/// Token bucket per client IP. Every client starts with a full bucket of `requests` tokens, which
+/// refills continuously over `per`.
+pub struct RateLimiter {
+	limit: RateLimit,
+	buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+	pub fn new(limit: RateLimit) -> Self {
+		RateLimiter {
+			limit,
+			buckets: Mutex::new(HashMap::new()),
+		}
+	}
+
+	/// Takes a token for `ip`. When there's none left, returns how long until the next one.
+	pub fn check(&self, ip: IpAddr) -> Result<(), Duration> {
+		self.check_at(ip, Instant::now())
+	}
+
+	fn check_at(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+		let capacity = f64::from(self.limit.requests);
+		let refill_per_sec = capacity / self.limit.per.as_secs_f64();
+
+		let mut buckets = self.buckets.lock().unwrap();
+		// Idle for longer than `per`, a bucket is full again and no different from a new one
+		let per = self.limit.per;
+		buckets.retain(|_, bucket| now.saturating_duration_since(bucket.updated) < per);
+		let bucket = buckets.entry(ip).or_insert(Bucket {
+			tokens: capacity,
+			updated: now,
+		});
+		let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+		bucket.tokens = (bucket.tokens + elapsed * refill_per_sec).min(capacity);
+		bucket.updated = now;
+
+		if bucket.tokens >= 1.0 {
+			bucket.tokens -= 1.0;
+			Ok(())
+		} else {
+			Err(Duration::from_secs_f64(
+				(1.0 - bucket.tokens) / refill_per_sec,
+			))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_rate_limit() {
+		assert_eq!(
+			"10/min".parse(),
+			Ok(RateLimit {
+				requests: 10,
+				per: Duration::from_secs(60)
+			})
+		);
+		assert!("10".parse::<RateLimit>().is_err());
+		assert!("0/min".parse::<RateLimit>().is_err());
+		assert!("10/day".parse::<RateLimit>().is_err());
+	}
+
+	#[test]
+	fn bucket_refills() {
+		let limiter = RateLimiter::new("2/min".parse().unwrap());
+		let ip = IpAddr::from([127, 0, 0, 1]);
+		let other_ip = IpAddr::from([127, 0, 0, 2]);
+		let start = Instant::now();
+
+		assert!(limiter.check_at(ip, start).is_ok());
+		assert!(limiter.check_at(ip, start).is_ok());
+		let retry_after = limiter.check_at(ip, start).unwrap_err();
+		assert_eq!(retry_after.as_secs_f64().round(), 30.0);
+		assert!(limiter.check_at(other_ip, start).is_ok());
+		assert!(limiter
+			.check_at(ip, start + Duration::from_secs(31))
+			.is_ok());
+	}
+
+	#[test]
+	fn idle_buckets_are_evicted() {
+		let limiter = RateLimiter::new("1/min".parse().unwrap());
+		let start = Instant::now();
+
+		for ip in 0..10u8 {
+			assert!(limiter
+				.check_at(IpAddr::from([10, 0, 0, ip]), start)
+				.is_ok());
+		}
+		assert_eq!(limiter.buckets.lock().unwrap().len(), 10);
+
+		let ip = IpAddr::from([127, 0, 0, 1]);
+		assert!(limiter
+			.check_at(ip, start + Duration::from_secs(61))
+			.is_ok());
+		assert_eq!(limiter.buckets.lock().unwrap().len(), 1);
+	}
+}
@@ -0,0 +1,1262 @@
+use crate::docker::{self, DockerLaunch};
+use crate::logs;
+use crate::pidfile;
+use crate::priority::{self, Priority};
+#[cfg(unix)]
+use crate::privileges::RunAs;
+use anyhow::{Context, Result};
+use log::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long a server gets to stop after the stop commands before it's killed.
+pub const STOP_TIMEOUT: Duration = Duration::from_secs(60);
+pub const DEFAULT_STOP_COMMAND: &str = "stop";
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Seconds before a stop or restart at which [`ServerManager::announce_countdown`] warns the
+/// players, besides the start of the countdown.
+const COUNTDOWN_WARNINGS: &[u64] = &[600, 300, 120, 60, 30, 10, 5, 4, 3, 2, 1];
+/// Lines of stdout replayed to clients starting to follow the output.
+const RECENT_OUTPUT_LINES: usize = 100;
+
+/// Vanilla prints `Done (12.345s)! For help, type "help"` once the server finishes loading.
+const DEFAULT_DONE_LINE_PATTERN: &str = r"Done \([0-9.,]+s\)!";
+
+/// Matches lines starting with a bracketed time, e.g. `[12:34:56]` or `[2021-08-01 12:34:56]`.
+const TIMESTAMPED_LINE_PATTERN: &str = r"^\[[^\]]*\d{1,2}:\d{2}";
+
+/// Level of a server log line, e.g. `[12:00:00] [Server thread/WARN]: ...` (vanilla) or
+/// `[12:00:00 WARN]: ...` (Paper).
+const SERVER_LEVEL_PATTERN: &str =
+	r"^(?:\[[^\]]*\]\s*)*?\[(?:[^\]]*[/ ])?(TRACE|DEBUG|INFO|WARN|WARNING|ERROR|FATAL|SEVERE)\]";
+
+/// Log target of the server lines re-emitted by [`ServerManager::with_parsed_levels`].
+const SERVER_OUTPUT_TARGET: &str = "minecraft_runner::server_output";
+
+/// Where [`ServerManager::with_timestamps`] prefixes the server output with the current time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimestampOutput {
+	/// Only the `--stdout-log` and `--stderr-log` files
+	Log,
+	/// The log files and the echoed output
+	All,
+}
+
+impl std::str::FromStr for TimestampOutput {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"log" => Ok(TimestampOutput::Log),
+			"all" => Ok(TimestampOutput::All),
+			_ => Err(format!("Unknown output \"{}\", expected log or all", s)),
+		}
+	}
+}
+
+/// What triggered a restart, see [`ServerManager::with_restart_announcements`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum RestartReason {
+	/// Planned ahead, e.g. a nightly restart requested by a cron job
+	Scheduled,
+	/// The server stopped responding, e.g. it didn't finish loading within --startup-timeout
+	HealthCheck,
+	/// Requested by an operator, e.g. over the webserver
+	Manual,
+	/// The server exited on its own
+	Crash,
+}
+
+impl RestartReason {
+	pub const ALL: &'static [RestartReason] = &[
+		RestartReason::Scheduled,
+		RestartReason::HealthCheck,
+		RestartReason::Manual,
+		RestartReason::Crash,
+	];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			RestartReason::Scheduled => "scheduled",
+			RestartReason::HealthCheck => "health_check",
+			RestartReason::Manual => "manual",
+			RestartReason::Crash => "crash",
+		}
+	}
+
+	/// Broadcast when the config doesn't override the message of the reason.
+	pub fn default_message(self) -> &'static str {
+		match self {
+			RestartReason::Scheduled => "Restarting for scheduled maintenance",
+			RestartReason::HealthCheck => "Restarting, the server stopped responding",
+			RestartReason::Manual => "Restarting at an operator's request",
+			RestartReason::Crash => "Recovering from a crash",
+		}
+	}
+}
+
+impl std::str::FromStr for RestartReason {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		RestartReason::ALL
+			.iter()
+			.copied()
+			.find(|reason| reason.name() == s)
+			.ok_or_else(|| {
+				let names: Vec<_> = RestartReason::ALL
+					.iter()
+					.map(|reason| reason.name())
+					.collect();
+				format!(
+					"Unknown restart reason \"{}\", expected one of {}",
+					s,
+					names.join(", ")
+				)
+			})
+	}
+}
+
+/// Owns the minecraft server process and knows how to (re)launch it.
+///
+/// The child handle is kept behind a mutex, so the server can be stopped or restarted from other
+/// threads (e.g. the webserver) while the main thread is waiting on it. The server's stdin and
+/// stdout are captured: commands are written with [`ServerManager::send_command`] and the output
+/// is echoed to the runner's own stdout.
+pub struct ServerManager {
+	java: PathBuf,
+	args: Mutex<Vec<String>>,
+	working_directory: PathBuf,
+	startup_commands: Mutex<Vec<String>>,
+	stdin_script: Vec<String>,
+	stdin_script_delay: Duration,
+	stop_commands: Mutex<Vec<String>>,
+	done_line: Regex,
+	output_prefix: Option<String>,
+	timestamps: Option<TimestampOutput>,
+	parse_levels: bool,
+	stdout_log: Option<PathBuf>,
+	stderr_log: Option<PathBuf>,
+	pidfile: Option<PathBuf>,
+	priority: Option<Priority>,
+	#[cfg(unix)]
+	run_as: Option<RunAs>,
+	startup_delay: Duration,
+	startup_timeout: Option<Duration>,
+	restart_on_startup_timeout: bool,
+	single_run: bool,
+	/// Messages overriding [`RestartReason::default_message`], set when restarts are announced
+	restart_announcements: Option<HashMap<RestartReason, String>>,
+	docker: Option<DockerLaunch>,
+	child: Arc<Mutex<Option<Child>>>,
+	readiness: Arc<Readiness>,
+	output: Arc<Mutex<OutputBroadcast>>,
+	restart_count: AtomicU64,
+	last_restart: Mutex<LastRestart>,
+}
+
+/// How the previous process ended, for telling apart a server stuck in a crash-restart cycle.
+#[derive(Default, Clone, Copy)]
+pub struct LastRestart {
+	pub exit_code: Option<i32>,
+	pub time: Option<SystemTime>,
+	pub reason: Option<RestartReason>,
+}
+
+impl ServerManager {
+	pub fn new(java: PathBuf, args: Vec<String>, working_directory: PathBuf) -> Self {
+		ServerManager {
+			java,
+			args: Mutex::new(args),
+			working_directory,
+			startup_commands: Mutex::new(Vec::new()),
+			stdin_script: Vec::new(),
+			stdin_script_delay: Duration::from_secs(0),
+			stop_commands: Mutex::new(vec![String::from(DEFAULT_STOP_COMMAND)]),
+			done_line: Regex::new(DEFAULT_DONE_LINE_PATTERN).unwrap(),
+			output_prefix: None,
+			timestamps: None,
+			parse_levels: false,
+			stdout_log: None,
+			stderr_log: None,
+			pidfile: None,
+			priority: None,
+			#[cfg(unix)]
+			run_as: None,
+			startup_delay: Duration::from_secs(0),
+			startup_timeout: None,
+			restart_on_startup_timeout: false,
+			single_run: false,
+			restart_announcements: None,
+			docker: None,
+			child: Arc::new(Mutex::new(None)),
+			readiness: Arc::new(Readiness::default()),
+			output: Arc::new(Mutex::new(OutputBroadcast::default())),
+			restart_count: AtomicU64::new(0),
+			last_restart: Mutex::new(LastRestart::default()),
+		}
+	}
+
+	/// Commands written to the server once it finishes loading, after every (re)launch.
+	pub fn with_startup_commands(mut self, startup_commands: Vec<String>) -> Self {
+		self.startup_commands = Mutex::new(startup_commands);
+		self
+	}
+
+	/// Commands of a script written to the server one by one, `delay` apart, after the startup
+	/// commands of every (re)launch. The console keeps being forwarded as usual.
+	pub fn with_stdin_script(mut self, commands: Vec<String>, delay: Duration) -> Self {
+		self.stdin_script = commands;
+		self.stdin_script_delay = delay;
+		self
+	}
+
+	/// Waits `startup_delay` after the server finishes loading before sending the startup
+	/// commands, for plugins that keep initializing after the done line.
+	pub fn with_startup_delay(mut self, startup_delay: Duration) -> Self {
+		self.startup_delay = startup_delay;
+		self
+	}
+
+	/// Commands written to the server, in order, to stop it gracefully. `stop` by default, some
+	/// modded servers need something else or a `save-all` first.
+	pub fn with_stop_commands(mut self, stop_commands: Vec<String>) -> Self {
+		self.stop_commands = Mutex::new(stop_commands);
+		self
+	}
+
+	/// Overrides the regex used to detect that the server finished loading.
+	pub fn with_done_line(mut self, done_line: Regex) -> Self {
+		self.done_line = done_line;
+		self
+	}
+
+	/// Prefixes every line of the echoed server output, to tell apart several servers sharing
+	/// one console.
+	pub fn with_output_prefix(mut self, output_prefix: String) -> Self {
+		self.output_prefix = Some(output_prefix);
+		self
+	}
+
+	/// Prefixes the server output lines lacking a timestamp with the current time (RFC 3339), for
+	/// servers that print lines without one.
+	pub fn with_timestamps(mut self, timestamps: TimestampOutput) -> Self {
+		self.timestamps = Some(timestamps);
+		self
+	}
+
+	/// Logs the server output at the level of each line instead of echoing it as is. Lines without
+	/// a recognizable level are logged as info. The log files still get the raw output.
+	pub fn with_parsed_levels(mut self) -> Self {
+		self.parse_levels = true;
+		self
+	}
+
+	/// Copies the server's stdout and stderr to these files, in addition to echoing them.
+	pub fn with_output_logs(
+		mut self,
+		stdout_log: Option<PathBuf>,
+		stderr_log: Option<PathBuf>,
+	) -> Self {
+		self.stdout_log = stdout_log;
+		self.stderr_log = stderr_log;
+		self
+	}
+
+	/// Keeps the PID of the running server in this file, see [`pidfile::kill_orphan`].
+	pub fn with_pidfile(mut self, pidfile: PathBuf) -> Self {
+		self.pidfile = Some(pidfile);
+		self
+	}
+
+	/// Scheduling priority applied to the server process after every (re)launch.
+	pub fn with_priority(mut self, priority: Priority) -> Self {
+		self.priority = Some(priority);
+		self
+	}
+
+	/// Launches the server as another user instead of the one running the runner.
+	#[cfg(unix)]
+	pub fn with_run_as(mut self, run_as: RunAs) -> Self {
+		self.run_as = Some(run_as);
+		self
+	}
+
+	/// Kills the server when it doesn't print the done line within `timeout` of being launched,
+	/// e.g. when it hangs during world generation. With `restart`, it's launched again instead of
+	/// letting [`ServerManager::wait`] return.
+	pub fn with_startup_timeout(mut self, timeout: Duration, restart: bool) -> Self {
+		self.startup_timeout = Some(timeout);
+		self.restart_on_startup_timeout = restart;
+		self
+	}
+
+	/// Runs the server exactly once: restarts are refused and [`ServerManager::wait`] returns as
+	/// soon as the first process exits, whatever else was configured.
+	pub fn with_single_run(mut self) -> Self {
+		self.single_run = true;
+		self
+	}
+
+	/// Tells the players why the server is restarting with a `say` before stopping it. `messages`
+	/// override the default message of their reason.
+	pub fn with_restart_announcements(mut self, messages: HashMap<RestartReason, String>) -> Self {
+		self.restart_announcements = Some(messages);
+		self
+	}
+
+	/// Returns whether restarts are refused because of [`ServerManager::with_single_run`].
+	pub fn is_single_run(&self) -> bool {
+		self.single_run
+	}
+
+	/// Launches java in a Docker container, and stops it with `docker stop` instead of the stop
+	/// commands.
+	pub fn with_docker(mut self, docker: DockerLaunch) -> Self {
+		self.docker = Some(docker);
+		self
+	}
+
+	/// Replaces the arguments the server is launched with, starting with the next restart.
+	/// Returns whether they changed.
+	pub fn set_args(&self, args: Vec<String>) -> bool {
+		replace_if_changed(&self.args, args)
+	}
+
+	/// Replaces the commands sent once the server finishes loading, starting with the next
+	/// restart. Returns whether they changed.
+	pub fn set_startup_commands(&self, startup_commands: Vec<String>) -> bool {
+		replace_if_changed(&self.startup_commands, startup_commands)
+	}
+
+	/// Replaces the commands stopping the server, used by the next stop. Returns whether they
+	/// changed.
+	pub fn set_stop_commands(&self, stop_commands: Vec<String>) -> bool {
+		replace_if_changed(&self.stop_commands, stop_commands)
+	}
+
+	/// Launches the server and returns its PID.
+	pub fn spawn(&self) -> Result<u32> {
+		let mut child = self.child.lock().unwrap();
+		self.spawn_locked(&mut child)
+	}
+
+	fn spawn_locked(&self, child: &mut Option<Child>) -> Result<u32> {
+		let stdout_log = self.stdout_log.as_deref().map(open_log).transpose()?;
+		let stderr_log = self.stderr_log.as_deref().map(open_log).transpose()?;
+
+		let mut command = match &self.docker {
+			Some(docker) => {
+				let mut command = Command::new("docker");
+				command
+					.args(docker.run_args(&self.working_directory))
+					.arg(&self.java);
+				command
+			}
+			None => Command::new(&self.java),
+		};
+		command
+			.args(&*self.args.lock().unwrap())
+			.current_dir(&self.working_directory)
+			.stdin(Stdio::piped())
+			.stdout(Stdio::piped());
+		if stderr_log.is_some() {
+			command.stderr(Stdio::piped());
+		}
+		#[cfg(unix)]
+		if let Some(run_as) = self.run_as {
+			use std::os::unix::process::CommandExt;
+			command.uid(run_as.uid).gid(run_as.gid);
+		}
+		let mut process = command
+			.spawn()
+			.with_context(|| format!("Failed to launch \"{}\"", self.java.display()))?;
+		let pid = process.id();
+		if let Some(path) = &self.pidfile {
+			if let Err(e) = pidfile::write(path, pid) {
+				warn!("Failed to write the pidfile: {:?}.", e);
+			}
+		}
+		if let Some(priority) = self.priority {
+			match priority::set_priority(&process, priority) {
+				Ok(()) => info!("Set the server priority to {}.", priority),
+				Err(e) => warn!(
+					"Failed to set the server priority to {}: {:?}.",
+					priority, e
+				),
+			}
+		}
+		let generation = self.readiness.reset();
+		let server_level = match self.parse_levels {
+			true => Some(Regex::new(SERVER_LEVEL_PATTERN).unwrap()),
+			false => None,
+		};
+		let timestamps = self.timestamps.map(|target| Timestamps {
+			target,
+			timestamped_line: Regex::new(TIMESTAMPED_LINE_PATTERN).unwrap(),
+		});
+
+		if let Some(stdout) = process.stdout.take() {
+			let watcher = OutputWatcher {
+				stream: OutputStream::Stdout,
+				readiness: Arc::clone(&self.readiness),
+				done_line: Some(self.done_line.clone()),
+				prefix: self.output_prefix.clone(),
+				timestamps: timestamps.clone(),
+				server_level: server_level.clone(),
+				log: stdout_log,
+				output: Some(Arc::clone(&self.output)),
+				generation,
+			};
+			std::thread::spawn(move || watcher.run(stdout));
+		}
+		if let Some(stderr) = process.stderr.take() {
+			let watcher = OutputWatcher {
+				stream: OutputStream::Stderr,
+				readiness: Arc::clone(&self.readiness),
+				done_line: None,
+				prefix: self.output_prefix.clone(),
+				timestamps,
+				server_level,
+				log: stderr_log,
+				output: None,
+				generation,
+			};
+			std::thread::spawn(move || watcher.run(stderr));
+		}
+
+		let startup_commands = self.startup_commands.lock().unwrap().clone();
+		if !startup_commands.is_empty() || !self.stdin_script.is_empty() {
+			let child = Arc::clone(&self.child);
+			let readiness = Arc::clone(&self.readiness);
+			let startup_delay = self.startup_delay;
+			let stdin_script = self.stdin_script.clone();
+			let stdin_script_delay = self.stdin_script_delay;
+			std::thread::spawn(move || {
+				if !readiness.wait(generation) {
+					return;
+				}
+				if startup_delay > Duration::from_secs(0) {
+					info!(
+						"Waiting {}s before sending the startup commands.",
+						startup_delay.as_secs()
+					);
+					std::thread::sleep(startup_delay);
+					// Restarted in the meantime, the new process sends its own
+					if readiness.generation() != generation {
+						return;
+					}
+				}
+				send_startup_commands(&child, &startup_commands);
+				if !stdin_script.is_empty() {
+					send_stdin_script(
+						&child,
+						&readiness,
+						generation,
+						&stdin_script,
+						stdin_script_delay,
+					);
+				}
+			});
+		}
+
+		if let Some(timeout) = self.startup_timeout {
+			let child = Arc::clone(&self.child);
+			let readiness = Arc::clone(&self.readiness);
+			let working_directory = self.working_directory.clone();
+			std::thread::spawn(move || {
+				if readiness.time_out(generation, timeout) {
+					kill_hung_server(&child, &readiness, generation, timeout, &working_directory);
+				}
+			});
+		}
+
+		*child = Some(process);
+		Ok(pid)
+	}
+
+	/// PID of the current server process.
+	pub fn pid(&self) -> Option<u32> {
+		self.child.lock().unwrap().as_ref().map(Child::id)
+	}
+
+	/// Returns whether the server process is currently alive.
+	pub fn is_running(&self) -> bool {
+		match self.child.lock().unwrap().as_mut() {
+			Some(child) => matches!(child.try_wait(), Ok(None)),
+			None => false,
+		}
+	}
+
+	/// Returns whether the current server process finished loading.
+	pub fn is_ready(&self) -> bool {
+		self.readiness.state.lock().unwrap().ready
+	}
+
+	/// Returns whether the current server process was killed for not starting in time.
+	pub fn startup_timed_out(&self) -> bool {
+		self.readiness.state.lock().unwrap().timed_out
+	}
+
+	/// How long ago the current server process was launched.
+	pub fn uptime(&self) -> Option<Duration> {
+		let spawned_at = self.readiness.state.lock().unwrap().spawned_at;
+		spawned_at.map(|spawned_at| spawned_at.elapsed())
+	}
+
+	/// How many times the server was restarted.
+	pub fn restart_count(&self) -> u64 {
+		self.restart_count.load(Ordering::Relaxed)
+	}
+
+	/// Exit code of the process replaced by the latest restart, and when it was restarted.
+	pub fn last_restart(&self) -> LastRestart {
+		*self.last_restart.lock().unwrap()
+	}
+
+	fn record_restart(&self, status: Option<ExitStatus>, reason: RestartReason) {
+		self.restart_count.fetch_add(1, Ordering::Relaxed);
+		*self.last_restart.lock().unwrap() = LastRestart {
+			exit_code: status.and_then(|status| status.code()),
+			time: Some(SystemTime::now()),
+			reason: Some(reason),
+		};
+	}
+
+	/// Returns the latest lines of the server's stdout and a receiver of the lines printed from
+	/// now on, across restarts. Dropping the receiver stops following.
+	pub fn follow_output(&self) -> (Vec<Vec<u8>>, mpsc::Receiver<Vec<u8>>) {
+		let (sender, receiver) = mpsc::channel();
+		let mut output = self.output.lock().unwrap();
+		output.followers.push(sender);
+		(output.recent.iter().cloned().collect(), receiver)
+	}
+
+	/// Writes a console command to the server's stdin.
+	pub fn send_command(&self, command: &str) -> Result<()> {
+		match self.child.lock().unwrap().as_mut() {
+			Some(child) => write_command(child, command),
+			None => anyhow::bail!("Server is not running"),
+		}
+	}
+
+	/// Counts down `grace` with `say` messages, e.g. "Server restarting in 30 seconds", blocking
+	/// until it's over. Players are warned at the start, then 10, 5 and 2 minutes, 1 minute, 30
+	/// and 10 seconds before, and every second for the last 5.
+	pub fn announce_countdown(&self, grace: Duration, action: &str) {
+		let start = Instant::now();
+		let grace = grace.as_secs();
+		let warnings = std::iter::once(grace).chain(
+			COUNTDOWN_WARNINGS
+				.iter()
+				.copied()
+				.filter(|remaining| *remaining < grace),
+		);
+		for remaining in warnings {
+			let at = start + Duration::from_secs(grace - remaining);
+			std::thread::sleep(at.saturating_duration_since(Instant::now()));
+			let message = format!("say Server {} in {}", action, format_countdown(remaining));
+			if let Err(e) = self.send_command(&message) {
+				warn!("Failed to warn the players: {:?}.", e);
+			}
+		}
+		let end = start + Duration::from_secs(grace);
+		std::thread::sleep(end.saturating_duration_since(Instant::now()));
+	}
+
+	/// Asks the server to stop and waits for it to exit, killing it if it doesn't stop in time.
+	///
+	/// The lock is only held to send the stop commands and to check on the process, so the console,
+	/// the webserver and [`ServerManager::wait`] aren't blocked while the server saves the world.
+	pub fn stop(&self) -> Result<Option<ExitStatus>> {
+		let pid = {
+			let mut child = self.child.lock().unwrap();
+			let child = match child.as_mut() {
+				Some(child) => child,
+				None => return Ok(None),
+			};
+			if let Some(status) = child.try_wait()? {
+				return Ok(Some(status));
+			}
+			if self.docker.is_none() {
+				send_stop_commands(child, &self.stop_commands.lock().unwrap());
+			}
+			child.id()
+		};
+
+		if let Some(docker) = &self.docker {
+			if let Err(e) = docker::stop(&docker.container, STOP_TIMEOUT) {
+				warn!("{:?}. Killing the docker process.", e);
+				if let Some(child) = self.child.lock().unwrap().as_mut() {
+					if child.id() == pid {
+						child.kill()?;
+					}
+				}
+			}
+		}
+
+		let deadline = Instant::now() + STOP_TIMEOUT;
+		loop {
+			{
+				let mut child = self.child.lock().unwrap();
+				let child = match child.as_mut() {
+					Some(child) if child.id() == pid => child,
+					// Replaced by a restart in the meantime, the stopped process is gone
+					_ => return Ok(None),
+				};
+				if let Some(status) = child.try_wait()? {
+					return Ok(Some(status));
+				}
+				if Instant::now() >= deadline {
+					warn!(
+						"Server didn't stop within {}s, killing it.",
+						STOP_TIMEOUT.as_secs()
+					);
+					child.kill()?;
+					return Ok(Some(child.wait()?));
+				}
+			}
+			std::thread::sleep(POLL_INTERVAL);
+		}
+	}
+
+	fn stop_locked(&self, child: &mut Child) -> Result<ExitStatus> {
+		match &self.docker {
+			Some(docker) => stop_container(child, &docker.container),
+			None => stop_child(child, &self.stop_commands.lock().unwrap()),
+		}
+	}
+
+	/// Stops the server (if it's running) and launches it again with the same arguments. Returns
+	/// the PID of the new process.
+	///
+	/// The lock is held for the whole operation, so [`ServerManager::wait`] never observes the
+	/// gap between the old process exiting and the new one being spawned.
+	pub fn restart(&self, reason: RestartReason) -> Result<u32> {
+		if self.single_run {
+			anyhow::bail!("Restarts are disabled, the server runs only once");
+		}
+		let mut child = self.child.lock().unwrap();
+		let status = match child.as_mut() {
+			Some(child) => {
+				if let Some(message) = self.restart_announcement(reason) {
+					if let Err(e) = write_command(child, &format!("say {}", message)) {
+						warn!("Failed to announce the restart: {:?}.", e);
+					}
+				}
+				Some(self.stop_locked(child)?)
+			}
+			None => None,
+		};
+		info!("Restarting the server ({}).", reason.name());
+		let pid = self.spawn_locked(&mut child)?;
+		self.record_restart(status, reason);
+		Ok(pid)
+	}
+
+	/// The message announcing a restart for `reason`, unless restarts aren't announced.
+	fn restart_announcement(&self, reason: RestartReason) -> Option<&str> {
+		let messages = self.restart_announcements.as_ref()?;
+		Some(
+			messages
+				.get(&reason)
+				.map(String::as_str)
+				.unwrap_or_else(|| reason.default_message()),
+		)
+	}
+
+	/// Blocks until the server exits. Restarts performed in the meantime are followed, i.e. this
+	/// only returns once the latest process exits.
+	pub fn wait(&self) -> Result<ExitStatus> {
+		loop {
+			let mut child = self.child.lock().unwrap();
+			let status = match child.as_mut() {
+				Some(child) => child.try_wait()?,
+				None => anyhow::bail!("Server was never started"),
+			};
+			if let Some(status) = status {
+				if self.single_run || !(self.restart_on_startup_timeout && self.startup_timed_out())
+				{
+					if let Some(path) = &self.pidfile {
+						pidfile::remove(path);
+					}
+					return Ok(status);
+				}
+				info!("Restarting the server after the startup timeout.");
+				self.spawn_locked(&mut child)?;
+				self.record_restart(Some(status), RestartReason::HealthCheck);
+			}
+			drop(child);
+			std::thread::sleep(POLL_INTERVAL);
+		}
+	}
+}
+
+fn format_countdown(seconds: u64) -> String {
+	match seconds {
+		1 => String::from("1 second"),
+		60 => String::from("1 minute"),
+		seconds if seconds > 60 && seconds % 60 == 0 => format!("{} minutes", seconds / 60),
+		seconds => format!("{} seconds", seconds),
+	}
+}
+
+fn replace_if_changed<T: PartialEq>(value: &Mutex<T>, new_value: T) -> bool {
+	let mut value = value.lock().unwrap();
+	if *value == new_value {
+		return false;
+	}
+	*value = new_value;
+	true
+}
+
+/// Forwards lines typed into the runner's console to the server. Blocks until the console input
+/// is closed.
+pub fn forward_console(minecraft_server: &ServerManager) {
+	let stdin = std::io::stdin();
+	for line in stdin.lock().lines() {
+		let line = match line {
+			Ok(v) => v,
+			Err(e) => {
+				debug!("Console input closed: {:?}", e);
+				return;
+			}
+		};
+		if !minecraft_server.is_running() {
+			warn!("Server is not running, ignoring \"{}\".", line);
+			continue;
+		}
+		if let Err(e) = minecraft_server.send_command(&line) {
+			warn!("Failed to forward \"{}\" to the server: {:?}.", line, e);
+		}
+	}
+}
+
+/// The latest stdout lines and the senders of everyone following the output.
+#[derive(Default)]
+struct OutputBroadcast {
+	recent: VecDeque<Vec<u8>>,
+	followers: Vec<mpsc::Sender<Vec<u8>>>,
+}
+
+impl OutputBroadcast {
+	fn push(&mut self, line: &[u8]) {
+		if self.recent.len() == RECENT_OUTPUT_LINES {
+			self.recent.pop_front();
+		}
+		self.recent.push_back(line.to_vec());
+		self.followers
+			.retain(|follower| follower.send(line.to_vec()).is_ok());
+	}
+}
+
+/// Tracks whether the current server process finished loading. Every spawned process gets a new
+/// generation, so a restart invalidates anyone waiting on the previous process.
+#[derive(Default)]
+struct Readiness {
+	state: Mutex<ReadinessState>,
+	changed: Condvar,
+}
+
+#[derive(Default)]
+struct ReadinessState {
+	generation: u64,
+	ready: bool,
+	timed_out: bool,
+	spawned_at: Option<Instant>,
+}
+
+impl Readiness {
+	fn reset(&self) -> u64 {
+		let mut state = self.state.lock().unwrap();
+		state.generation += 1;
+		state.ready = false;
+		state.timed_out = false;
+		state.spawned_at = Some(Instant::now());
+		self.changed.notify_all();
+		state.generation
+	}
+
+	/// Marks the process as ready and returns how long it took to start. Returns `None` if the
+	/// process got replaced or was already marked as ready.
+	fn set_ready(&self, generation: u64) -> Option<Duration> {
+		let mut state = self.state.lock().unwrap();
+		if state.generation != generation || state.ready {
+			return None;
+		}
+		state.ready = true;
+		self.changed.notify_all();
+		state.spawned_at.map(|spawned_at| spawned_at.elapsed())
+	}
+
+	/// Blocks until the process of the given generation is ready. Returns false if it got
+	/// replaced by a newer one first.
+	fn wait(&self, generation: u64) -> bool {
+		let mut state = self.state.lock().unwrap();
+		loop {
+			if state.generation != generation {
+				return false;
+			}
+			if state.ready {
+				return true;
+			}
+			state = self.changed.wait(state).unwrap();
+		}
+	}
+
+	/// Waits up to `timeout` for the process of the given generation to get ready. Returns true,
+	/// marking the process as timed out, if it's still loading by then.
+	fn time_out(&self, generation: u64, timeout: Duration) -> bool {
+		let deadline = Instant::now() + timeout;
+		let mut state = self.state.lock().unwrap();
+		loop {
+			if state.generation != generation || state.ready {
+				return false;
+			}
+			let now = Instant::now();
+			if now >= deadline {
+				state.timed_out = true;
+				return true;
+			}
+			state = self.changed.wait_timeout(state, deadline - now).unwrap().0;
+		}
+	}
+
+	fn generation(&self) -> u64 {
+		self.state.lock().unwrap().generation
+	}
+}
+
+/// Lines of `logs/latest.log` logged when the server is killed for not starting in time.
+const STARTUP_TIMEOUT_LOG_LINES: usize = 20;
+
+fn kill_hung_server(
+	child: &Mutex<Option<Child>>,
+	readiness: &Readiness,
+	generation: u64,
+	timeout: Duration,
+	working_directory: &Path,
+) {
+	let mut child = child.lock().unwrap();
+	// A restart in the meantime replaced the process
+	if readiness.generation() != generation {
+		return;
+	}
+	let child = match child.as_mut() {
+		Some(v) => v,
+		None => return,
+	};
+	error!(
+		"=== Server didn't finish starting within {}s, killing it. ===",
+		timeout.as_secs()
+	);
+	let log_path = logs::latest_log_path(working_directory);
+	if let Ok(lines) = logs::tail(&log_path, STARTUP_TIMEOUT_LOG_LINES) {
+		error!("Last lines of {}:", log_path.display());
+		for line in lines {
+			error!("  {}", line);
+		}
+	}
+	if let Err(e) = child.kill() {
+		error!("Failed to kill the server: {:?}.", e);
+	}
+}
+
+fn send_startup_commands(child: &Mutex<Option<Child>>, startup_commands: &[String]) {
+	let mut child = child.lock().unwrap();
+	let child = match child.as_mut() {
+		Some(v) => v,
+		None => return,
+	};
+	for command in startup_commands {
+		info!("Sending startup command \"{}\".", command);
+		if let Err(e) = write_command(child, command) {
+			warn!("Failed to send startup command \"{}\": {:?}.", command, e);
+		}
+	}
+}
+
+/// Writes the commands `delay` apart, releasing the process in between so the console and the
+/// webserver can still send commands.
+fn send_stdin_script(
+	child: &Mutex<Option<Child>>,
+	readiness: &Readiness,
+	generation: u64,
+	commands: &[String],
+	delay: Duration,
+) {
+	info!("Sending {} commands of the stdin file.", commands.len());
+	for (i, command) in commands.iter().enumerate() {
+		if i > 0 {
+			std::thread::sleep(delay);
+		}
+		// Restarted in the meantime, the new process runs the script again
+		if readiness.generation() != generation {
+			return;
+		}
+		let mut child = child.lock().unwrap();
+		let child = match child.as_mut() {
+			Some(v) => v,
+			None => return,
+		};
+		debug!("Sending \"{}\" from the stdin file.", command);
+		if let Err(e) = write_command(child, command) {
+			warn!(
+				"Failed to send \"{}\" from the stdin file: {:?}.",
+				command, e
+			);
+			return;
+		}
+	}
+	info!("Sent the stdin file.");
+}
+
+/// Commands of a stdin file: one per line, skipping blank lines and `#` comments.
+pub fn parse_stdin_script(script: &str) -> Vec<String> {
+	script
+		.lines()
+		.map(str::trim)
+		.filter(|line| !line.is_empty() && !line.starts_with('#'))
+		.map(String::from)
+		.collect()
+}
+
+fn open_log(path: &Path) -> Result<File> {
+	OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(path)
+		.with_context(|| format!("Failed to open the log {:?}", path))
+}
+
+#[derive(Clone)]
+struct Timestamps {
+	target: TimestampOutput,
+	timestamped_line: Regex,
+}
+
+impl Timestamps {
+	/// Returns the line with the current time in front, unless it already starts with one.
+	fn apply(&self, line: &[u8]) -> Option<Vec<u8>> {
+		if self
+			.timestamped_line
+			.is_match(&String::from_utf8_lossy(line))
+		{
+			return None;
+		}
+		let now = chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, false);
+		Some([now.as_bytes(), &b" "[..], line].concat())
+	}
+}
+
+#[derive(Clone, Copy)]
+enum OutputStream {
+	Stdout,
+	Stderr,
+}
+
+/// Follows one output stream of a server process: marks the process as ready when it prints the
+/// done line, echoes the output to the same stream of ours and copies it to the log file.
+struct OutputWatcher {
+	stream: OutputStream,
+	readiness: Arc<Readiness>,
+	done_line: Option<Regex>,
+	prefix: Option<String>,
+	timestamps: Option<Timestamps>,
+	/// Set to re-emit the lines through `log` at their own level instead of echoing them
+	server_level: Option<Regex>,
+	log: Option<File>,
+	output: Option<Arc<Mutex<OutputBroadcast>>>,
+	generation: u64,
+}
+
+impl OutputWatcher {
+	/// Copies the output line by line, without altering it apart from the prefix and timestamps.
+	fn run(mut self, output: impl Read) {
+		let mut output = BufReader::new(output);
+		let mut line = Vec::new();
+		loop {
+			line.clear();
+			match output.read_until(b'\n', &mut line) {
+				Ok(0) => return,
+				Ok(_) => {
+					self.check_done_line(&line);
+					if let Some(output) = &self.output {
+						output.lock().unwrap().push(&line);
+					}
+					let timestamped = self
+						.timestamps
+						.as_ref()
+						.and_then(|timestamps| timestamps.apply(&line));
+					if let Some(log) = &mut self.log {
+						let line = timestamped.as_deref().unwrap_or(&line);
+						if let Err(e) = log.write_all(line).and_then(|_| log.flush()) {
+							warn!("Failed to write the server output log: {:?}.", e);
+							self.log = None;
+						}
+					}
+					let echoed = match self.timestamps.as_ref().map(|timestamps| timestamps.target)
+					{
+						Some(TimestampOutput::All) => timestamped.as_deref().unwrap_or(&line),
+						_ => &line[..],
+					};
+					if let Some(server_level) = &self.server_level {
+						let line = String::from_utf8_lossy(echoed);
+						let line = line.trim_end();
+						let level = parse_server_level(server_level, line);
+						match &self.prefix {
+							Some(prefix) => {
+								log!(target: SERVER_OUTPUT_TARGET, level, "{} {}", prefix, line)
+							}
+							None => log!(target: SERVER_OUTPUT_TARGET, level, "{}", line),
+						}
+					} else if self.echo(echoed).is_err() {
+						return;
+					}
+				}
+				Err(e) => {
+					debug!("Failed to read server output: {:?}", e);
+					return;
+				}
+			}
+		}
+	}
+
+	fn check_done_line(&self, line: &[u8]) {
+		let done_line = match &self.done_line {
+			Some(v) => v,
+			None => return,
+		};
+		if done_line.is_match(&String::from_utf8_lossy(line)) {
+			if let Some(startup_time) = self.readiness.set_ready(self.generation) {
+				info!("Server ready in {:.1}s", startup_time.as_secs_f64());
+			}
+		}
+	}
+
+	/// Writes the whole line at once, so lines of the stdout and stderr watchers don't tear each
+	/// other on the terminal.
+	fn echo(&self, line: &[u8]) -> std::io::Result<()> {
+		let line = match &self.prefix {
+			Some(prefix) => [prefix.as_bytes(), &b" "[..], line].concat(),
+			None => line.to_vec(),
+		};
+		match self.stream {
+			OutputStream::Stdout => {
+				let stdout = std::io::stdout();
+				let mut stdout = stdout.lock();
+				stdout.write_all(&line)?;
+				stdout.flush()
+			}
+			OutputStream::Stderr => {
+				let stderr = std::io::stderr();
+				let mut stderr = stderr.lock();
+				stderr.write_all(&line)?;
+				stderr.flush()
+			}
+		}
+	}
+}
+
+/// Maps the level of a server log line to ours, info when the line has none.
+fn parse_server_level(server_level: &Regex, line: &str) -> Level {
+	let level = server_level
+		.captures(line)
+		.and_then(|captures| captures.get(1))
+		.map(|level| level.as_str());
+	match level {
+		Some("TRACE") => Level::Trace,
+		Some("DEBUG") => Level::Debug,
+		Some("WARN") | Some("WARNING") => Level::Warn,
+		Some("ERROR") | Some("FATAL") | Some("SEVERE") => Level::Error,
+		_ => Level::Info,
+	}
+}
+
+fn write_command(child: &mut Child, command: &str) -> Result<()> {
+	let stdin = child
+		.stdin
+		.as_mut()
+		.ok_or_else(|| anyhow::anyhow!("Server stdin is not available"))?;
+	writeln!(stdin, "{}", command)?;
+	stdin.flush()?;
+	Ok(())
+}
+
+fn send_stop_commands(child: &mut Child, stop_commands: &[String]) {
+	info!("Stopping the server.");
+	for command in stop_commands {
+		if let Err(e) = write_command(child, command) {
+			warn!("Failed to send the stop command \"{}\": {:?}.", command, e);
+		}
+	}
+}
+
+fn stop_child(child: &mut Child, stop_commands: &[String]) -> Result<ExitStatus> {
+	if let Some(status) = child.try_wait()? {
+		return Ok(status);
+	}
+
+	send_stop_commands(child, stop_commands);
+
+	let deadline = Instant::now() + STOP_TIMEOUT;
+	loop {
+		if let Some(status) = child.try_wait()? {
+			return Ok(status);
+		}
+		if Instant::now() >= deadline {
+			warn!(
+				"Server didn't stop within {}s, killing it.",
+				STOP_TIMEOUT.as_secs()
+			);
+			child.kill()?;
+			return Ok(child.wait()?);
+		}
+		std::thread::sleep(POLL_INTERVAL);
+	}
+}
+
+fn stop_container(child: &mut Child, container: &str) -> Result<ExitStatus> {
+	if let Some(status) = child.try_wait()? {
+		return Ok(status);
+	}
+	if let Err(e) = docker::stop(container, STOP_TIMEOUT) {
+		warn!("{:?}. Killing the docker process.", e);
+		child.kill()?;
+	}
+	Ok(child.wait()?)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn default_done_line_matches_vanilla() {
+		let done_line = Regex::new(DEFAULT_DONE_LINE_PATTERN).unwrap();
+		assert!(done_line
+			.is_match("[12:34:56] [Server thread/INFO]: Done (12.345s)! For help, type \"help\""));
+		assert!(!done_line.is_match("<player> Done (with this)!"));
+	}
+
+	#[test]
+	fn only_lines_without_timestamps_are_timestamped() {
+		let timestamps = Timestamps {
+			target: TimestampOutput::Log,
+			timestamped_line: Regex::new(TIMESTAMPED_LINE_PATTERN).unwrap(),
+		};
+		assert_eq!(
+			timestamps.apply(b"[12:34:56] [Server thread/INFO]: Starting minecraft server\n"),
+			None
+		);
+		assert_eq!(
+			timestamps.apply(b"[2021-08-01 12:34:56] [main/INFO]: Loading mods\n"),
+			None
+		);
+		let line = timestamps
+			.apply(b"Loading libraries, please wait...\n")
+			.unwrap();
+		assert!(line.ends_with(b" Loading libraries, please wait...\n"));
+		assert!(timestamps.apply(b"[Server] hello\n").is_some());
+	}
+
+	#[test]
+	fn stdin_script_skips_comments_and_blank_lines() {
+		let script = "# Setup of the test world\n\ngamerule doDaylightCycle false\n  \n\ttime set day\n#op someone\n";
+		assert_eq!(
+			parse_stdin_script(script),
+			vec![
+				String::from("gamerule doDaylightCycle false"),
+				String::from("time set day")
+			]
+		);
+	}
+
+	#[test]
+	fn server_levels() {
+		let server_level = Regex::new(SERVER_LEVEL_PATTERN).unwrap();
+		let level = |line| parse_server_level(&server_level, line);
+		assert_eq!(
+			level("[12:00:00] [Server thread/INFO]: Starting minecraft server"),
+			Level::Info
+		);
+		assert_eq!(
+			level("[12:00:00] [Server thread/WARN]: Can't keep up!"),
+			Level::Warn
+		);
+		assert_eq!(
+			level("[12:00:00 ERROR]: Could not load 'plugins/broken.jar'"),
+			Level::Error
+		);
+		assert_eq!(
+			level("[12:00:00] [main/DEBUG] [forge/Loader]: Scanning mods"),
+			Level::Debug
+		);
+		assert_eq!(level("Loading libraries, please wait..."), Level::Info);
+		assert_eq!(level("<player> ERROR] is not a level"), Level::Info);
+	}
+
+	#[test]
+	fn restart_reasons() {
+		for reason in RestartReason::ALL {
+			assert_eq!(reason.name().parse::<RestartReason>(), Ok(*reason));
+		}
+		assert!("reboot".parse::<RestartReason>().is_err());
+
+		let mut messages = HashMap::new();
+		messages.insert(RestartReason::Crash, String::from("Oops, back in a minute"));
+		let server = ServerManager::new(PathBuf::from("java"), Vec::new(), PathBuf::from("."));
+		assert_eq!(server.restart_announcement(RestartReason::Crash), None);
+		let server = server.with_restart_announcements(messages);
+		assert_eq!(
+			server.restart_announcement(RestartReason::Crash),
+			Some("Oops, back in a minute")
+		);
+		assert_eq!(
+			server.restart_announcement(RestartReason::Scheduled),
+			Some("Restarting for scheduled maintenance")
+		);
+	}
+
+	#[test]
+	fn countdown_messages() {
+		assert_eq!(format_countdown(300), "5 minutes");
+		assert_eq!(format_countdown(60), "1 minute");
+		assert_eq!(format_countdown(90), "90 seconds");
+		assert_eq!(format_countdown(1), "1 second");
+	}
+
+	#[test]
+	fn readiness_of_replaced_process_is_ignored() {
+		let readiness = Readiness::default();
+		let first = readiness.reset();
+		let second = readiness.reset();
+		assert_eq!(readiness.set_ready(first), None);
+		assert!(!readiness.wait(first));
+		assert!(readiness.set_ready(second).is_some());
+		assert_eq!(readiness.set_ready(second), None);
+		assert!(readiness.wait(second));
+	}
+
+	#[test]
+	fn startup_times_out_only_while_loading() {
+		let readiness = Readiness::default();
+		let generation = readiness.reset();
+		assert!(readiness.time_out(generation, Duration::from_millis(10)));
+		assert!(readiness.state.lock().unwrap().timed_out);
+
+		let generation = readiness.reset();
+		assert!(!readiness.state.lock().unwrap().timed_out);
+		readiness.set_ready(generation);
+		assert!(!readiness.time_out(generation, Duration::from_millis(10)));
+	}
+}
@@ -0,0 +1,419 @@
+use crate::cgroup;
+use crate::config::MinecraftRunnerConfig;
+use crate::jvm::GcProfile;
+use crate::server::DEFAULT_STOP_COMMAND;
+use crate::Opt;
+use anyhow::{Context, Result};
+use log::*;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+pub const DEFAULT_MIN_MEMORY: &str = "1GiB";
+pub const DEFAULT_MAX_MEMORY: &str = "16GiB";
+
+/// Where an effective setting came from. Later sources take precedence over earlier ones.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum Source {
+	Default,
+	Config,
+	Env,
+	Flag,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Sourced<T> {
+	pub value: T,
+	pub source: Source,
+}
+
+impl<T> Sourced<T> {
+	fn new(value: T, source: Source) -> Self {
+		Sourced { value, source }
+	}
+}
+
+/// A memory size, e.g. `4GiB`, or a percentage of the memory the server can use, e.g. `75%`.
+#[derive(Debug, Clone)]
+pub enum MemorySize {
+	Absolute(human_size::SpecificSize),
+	Percent(f64),
+}
+
+impl FromStr for MemorySize {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.trim().strip_suffix('%') {
+			Some(percent) => match percent.trim().parse::<f64>() {
+				Ok(percent) if percent > 0.0 && percent <= 100.0 => {
+					Ok(MemorySize::Percent(percent))
+				}
+				_ => Err(format!(
+					"Invalid percentage \"{}\", expected more than 0% and at most 100%",
+					s
+				)),
+			},
+			None => s
+				.parse()
+				.map(MemorySize::Absolute)
+				.map_err(|e| format!("Invalid memory size \"{}\": {:?}", s, e)),
+		}
+	}
+}
+
+/// Settings that can be provided with environment variables.
+#[derive(Debug, Default)]
+pub struct EnvSettings {
+	/// `MINECRAFT_RUNNER_MIN`
+	pub min: Option<String>,
+	/// `MINECRAFT_RUNNER_MAX`
+	pub max: Option<String>,
+	/// `MINECRAFT_RUNNER_PORT`
+	pub port: Option<String>,
+	/// `MINECRAFT_RUNNER_JAR`, pins the jar for e.g. container images
+	pub jar: Option<String>,
+}
+
+impl EnvSettings {
+	pub fn from_env() -> Self {
+		EnvSettings {
+			min: std::env::var("MINECRAFT_RUNNER_MIN").ok(),
+			max: std::env::var("MINECRAFT_RUNNER_MAX").ok(),
+			port: std::env::var("MINECRAFT_RUNNER_PORT").ok(),
+			jar: std::env::var("MINECRAFT_RUNNER_JAR").ok(),
+		}
+	}
+}
+
+/// The final settings used to launch the server, after merging all the sources.
+#[derive(Serialize, Debug)]
+pub struct EffectiveSettings {
+	pub profile: Option<String>,
+	pub min_mib: Sourced<u64>,
+	pub max_mib: Sourced<u64>,
+	pub port: Option<Sourced<u16>>,
+	pub jvm_args: Sourced<Vec<String>>,
+	pub jar_preference: Option<Sourced<PathBuf>>,
+	/// The config's jar preference when `MINECRAFT_RUNNER_JAR` overrides it, tried when the jar of
+	/// the env doesn't exist.
+	pub fallback_jar_preference: Option<Sourced<PathBuf>>,
+	pub startup_commands: Sourced<Vec<String>>,
+	pub stop_commands: Sourced<Vec<String>>,
+	pub gc: Sourced<GcProfile>,
+	pub experimental_flags: Sourced<bool>,
+	pub server_type_flags: Sourced<bool>,
+	/// Vanilla server arguments passed after `nogui`, e.g. `--forceUpgrade`.
+	pub server_args: Sourced<Vec<String>>,
+}
+
+impl EffectiveSettings {
+	/// The preferred jars in the order they're tried.
+	pub fn jar_preferences(&self) -> Vec<&Path> {
+		self.jar_preference
+			.iter()
+			.chain(&self.fallback_jar_preference)
+			.map(|jar| jar.value.as_path())
+			.collect()
+	}
+}
+
+/// Merges the settings in the order: default < config < env < flags.
+pub fn resolve_settings(
+	opt: &Opt,
+	config: &MinecraftRunnerConfig,
+	env: &EnvSettings,
+) -> Result<EffectiveSettings> {
+	let profile = config.profile_settings(opt.profile.as_deref())?;
+
+	let min_mib = resolve_memory(
+		opt.min.clone(),
+		env.min.as_deref(),
+		profile.min.as_deref(),
+		DEFAULT_MIN_MEMORY,
+	)
+	.context("Failed to resolve the min memory")?;
+	let max_mib = resolve_memory(
+		opt.max.clone(),
+		env.max.as_deref(),
+		profile.max.as_deref(),
+		DEFAULT_MAX_MEMORY,
+	)
+	.context("Failed to resolve the max memory")?;
+
+	let port = if let Some(port) = opt.port {
+		Some(Sourced::new(port, Source::Flag))
+	} else if let Some(port) = env.port.as_deref() {
+		let port = port
+			.parse()
+			.with_context(|| format!("Invalid MINECRAFT_RUNNER_PORT \"{}\"", port))?;
+		Some(Sourced::new(port, Source::Env))
+	} else {
+		profile.port.map(|port| Sourced::new(port, Source::Config))
+	};
+
+	let jvm_args = match profile.jvm_args {
+		Some(jvm_args) => Sourced::new(jvm_args, Source::Config),
+		None => Sourced::new(Vec::new(), Source::Default),
+	};
+
+	let startup_commands = if !opt.startup_commands.is_empty() {
+		Sourced::new(opt.startup_commands.clone(), Source::Flag)
+	} else if !config.startup_commands.is_empty() {
+		Sourced::new(config.startup_commands.clone(), Source::Config)
+	} else {
+		Sourced::new(Vec::new(), Source::Default)
+	};
+
+	let stop_commands = if !opt.stop_commands.is_empty() {
+		Sourced::new(opt.stop_commands.clone(), Source::Flag)
+	} else if !config.stop_commands.is_empty() {
+		Sourced::new(config.stop_commands.clone(), Source::Config)
+	} else {
+		Sourced::new(vec![String::from(DEFAULT_STOP_COMMAND)], Source::Default)
+	};
+
+	let server_args: Vec<String> = [
+		(opt.force_upgrade, "--forceUpgrade"),
+		(opt.erase_cache, "--eraseCache"),
+		(opt.demo, "--demo"),
+	]
+	.iter()
+	.filter(|(enabled, _)| *enabled)
+	.map(|(_, arg)| String::from(*arg))
+	.collect();
+	let server_args = match server_args.is_empty() {
+		true => Sourced::new(server_args, Source::Default),
+		false => Sourced::new(server_args, Source::Flag),
+	};
+
+	let config_jar_preference = profile
+		.jar_preference
+		.map(|jar| Sourced::new(jar, Source::Config));
+
+	Ok(EffectiveSettings {
+		profile: opt.profile.clone(),
+		min_mib,
+		max_mib,
+		port,
+		jvm_args,
+		jar_preference: match &env.jar {
+			Some(jar) => Some(Sourced::new(PathBuf::from(jar), Source::Env)),
+			None => config_jar_preference.clone(),
+		},
+		fallback_jar_preference: env.jar.as_ref().and(config_jar_preference),
+		startup_commands,
+		stop_commands,
+		gc: match opt.gc {
+			Some(gc) => Sourced::new(gc, Source::Flag),
+			None => Sourced::new(GcProfile::G1, Source::Default),
+		},
+		experimental_flags: if opt.no_experimental_flags {
+			Sourced::new(false, Source::Flag)
+		} else {
+			Sourced::new(true, Source::Default)
+		},
+		server_type_flags: if opt.no_server_type_flags {
+			Sourced::new(false, Source::Flag)
+		} else {
+			Sourced::new(true, Source::Default)
+		},
+		server_args,
+	})
+}
+
+fn resolve_memory(
+	flag: Option<MemorySize>,
+	env: Option<&str>,
+	config: Option<&str>,
+	default: &str,
+) -> Result<Sourced<u64>> {
+	let (size, source) = match (flag, env, config) {
+		(Some(size), _, _) => (size, Source::Flag),
+		(None, Some(size), _) => (parse_memory(size)?, Source::Env),
+		(None, None, Some(size)) => (parse_memory(size)?, Source::Config),
+		(None, None, None) => (parse_memory(default)?, Source::Default),
+	};
+	Ok(Sourced::new(to_mib(size), source))
+}
+
+/// Overrides the memory coming from the config or the defaults with the `jar_memory` entry of the
+/// launched jar. Sizes from the environment or the flags are kept. Returns whether any size was
+/// overridden.
+pub fn apply_jar_memory(
+	settings: &mut EffectiveSettings,
+	config: &MinecraftRunnerConfig,
+	jar: &str,
+) -> Result<bool> {
+	let spec = match config.jar_memory.get(jar) {
+		Some(v) => v,
+		None => return Ok(false),
+	};
+	let min = override_memory(&mut settings.min_mib, spec.min.as_deref())
+		.with_context(|| format!("Failed to resolve the min memory of \"{}\"", jar))?;
+	let max = override_memory(&mut settings.max_mib, spec.max.as_deref())
+		.with_context(|| format!("Failed to resolve the max memory of \"{}\"", jar))?;
+	Ok(min || max)
+}
+
+fn override_memory(setting: &mut Sourced<u64>, size: Option<&str>) -> Result<bool> {
+	match (setting.source, size) {
+		(Source::Default, Some(size)) | (Source::Config, Some(size)) => {
+			*setting = Sourced::new(to_mib(parse_memory(size)?), Source::Config);
+			Ok(true)
+		}
+		_ => Ok(false),
+	}
+}
+
+fn to_mib(size: MemorySize) -> u64 {
+	match size {
+		MemorySize::Absolute(size) => size.into::<human_size::Mebibyte>().value().floor() as u64,
+		MemorySize::Percent(percent) => {
+			// A container can't use more than the host either
+			let host = cgroup::host_memory();
+			let (ceiling, source) = match cgroup::memory_limit() {
+				Some(limit) => (limit.min(host), "the cgroup memory limit"),
+				None => (host, "the host RAM"),
+			};
+			let mib = percent_to_mib(percent, ceiling);
+			info!(
+				"{}% of {} ({} MiB) is {} MiB.",
+				percent,
+				source,
+				ceiling / 1024 / 1024,
+				mib
+			);
+			mib
+		}
+	}
+}
+
+fn percent_to_mib(percent: f64, ceiling: u64) -> u64 {
+	(ceiling as f64 * percent / 100.0 / 1024.0 / 1024.0).floor() as u64
+}
+
+fn parse_memory(size: &str) -> Result<MemorySize> {
+	size.parse().map_err(|e: String| anyhow::anyhow!(e))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use structopt::StructOpt;
+
+	fn config() -> MinecraftRunnerConfig {
+		MinecraftRunnerConfig {
+			max: Some(String::from("4GiB")),
+			port: Some(25566),
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn defaults_when_nothing_is_set() {
+		let opt = Opt::from_iter(&["minecraft_runner"]);
+		let settings = resolve_settings(
+			&opt,
+			&MinecraftRunnerConfig::default(),
+			&EnvSettings::default(),
+		)
+		.unwrap();
+		assert_eq!(settings.min_mib, Sourced::new(1024, Source::Default));
+		assert_eq!(settings.max_mib, Sourced::new(16384, Source::Default));
+		assert_eq!(settings.port, None);
+	}
+
+	#[test]
+	fn precedence() {
+		let env = EnvSettings {
+			max: Some(String::from("6GiB")),
+			port: Some(String::from("25567")),
+			..Default::default()
+		};
+
+		let opt = Opt::from_iter(&["minecraft_runner"]);
+		let settings = resolve_settings(&opt, &config(), &EnvSettings::default()).unwrap();
+		assert_eq!(settings.max_mib, Sourced::new(4096, Source::Config));
+		assert_eq!(settings.port, Some(Sourced::new(25566, Source::Config)));
+
+		let settings = resolve_settings(&opt, &config(), &env).unwrap();
+		assert_eq!(settings.max_mib, Sourced::new(6144, Source::Env));
+		assert_eq!(settings.port, Some(Sourced::new(25567, Source::Env)));
+
+		let opt = Opt::from_iter(&["minecraft_runner", "--max", "8GiB", "--port", "25568"]);
+		let settings = resolve_settings(&opt, &config(), &env).unwrap();
+		assert_eq!(settings.max_mib, Sourced::new(8192, Source::Flag));
+		assert_eq!(settings.port, Some(Sourced::new(25568, Source::Flag)));
+	}
+
+	#[test]
+	fn percentages_of_memory() {
+		assert!(matches!(
+			"75%".parse::<MemorySize>(),
+			Ok(MemorySize::Percent(percent)) if percent == 75.0
+		));
+		assert!(matches!(
+			"4GiB".parse::<MemorySize>(),
+			Ok(MemorySize::Absolute(_))
+		));
+		assert!("0%".parse::<MemorySize>().is_err());
+		assert!("150%".parse::<MemorySize>().is_err());
+		assert_eq!(percent_to_mib(75.0, 8 * 1024 * 1024 * 1024), 6144);
+		assert_eq!(percent_to_mib(50.0, 3 * 1024 * 1024 * 1024), 1536);
+	}
+
+	#[test]
+	fn vanilla_server_flags() {
+		let opt = Opt::from_iter(&["minecraft_runner", "--erase-cache", "--force-upgrade"]);
+		let settings = resolve_settings(&opt, &config(), &EnvSettings::default()).unwrap();
+		assert_eq!(
+			settings.server_args,
+			Sourced::new(
+				vec![String::from("--forceUpgrade"), String::from("--eraseCache")],
+				Source::Flag
+			)
+		);
+	}
+
+	#[test]
+	fn env_jar_overrides_config_preference() {
+		let config = MinecraftRunnerConfig {
+			jar_preference: PathBuf::from("paper.jar"),
+			..Default::default()
+		};
+		let env = EnvSettings {
+			jar: Some(String::from("fabric.jar")),
+			..Default::default()
+		};
+		let opt = Opt::from_iter(&["minecraft_runner"]);
+		let settings = resolve_settings(&opt, &config, &env).unwrap();
+		assert_eq!(
+			settings.jar_preference,
+			Some(Sourced::new(PathBuf::from("fabric.jar"), Source::Env))
+		);
+		assert_eq!(
+			settings.jar_preferences(),
+			vec![Path::new("fabric.jar"), Path::new("paper.jar")]
+		);
+	}
+
+	#[test]
+	fn jar_memory_overrides_config_but_not_flags() {
+		let mut config = config();
+		config.jar_memory.insert(
+			String::from("modpack.jar"),
+			crate::config::MemorySpec {
+				min: Some(String::from("4GiB")),
+				max: Some(String::from("12GiB")),
+			},
+		);
+
+		let opt = Opt::from_iter(&["minecraft_runner", "--max", "8GiB"]);
+		let mut settings = resolve_settings(&opt, &config, &EnvSettings::default()).unwrap();
+		assert!(!apply_jar_memory(&mut settings, &config, "vanilla.jar").unwrap());
+		assert!(apply_jar_memory(&mut settings, &config, "modpack.jar").unwrap());
+		assert_eq!(settings.min_mib, Sourced::new(4096, Source::Config));
+		assert_eq!(settings.max_mib, Sourced::new(8192, Source::Flag));
+	}
+}
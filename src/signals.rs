@@ -0,0 +1,43 @@
+use crate::server::ServerManager;
+use anyhow::Result;
+use log::*;
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+use std::sync::{mpsc, Arc};
+
+/// Sends `reload_command` to the server whenever the runner receives SIGHUP, so datapacks and
+/// permissions can be reloaded without a restart. Only available on Unix.
+pub fn forward_sighup(minecraft_server: Arc<ServerManager>, reload_command: String) -> Result<()> {
+	let mut signals = Signals::new(&[SIGHUP])?;
+	std::thread::spawn(move || {
+		for _ in signals.forever() {
+			if !minecraft_server.is_ready() {
+				warn!("Received SIGHUP, but the server is still starting. Ignoring it.");
+				continue;
+			}
+			info!(
+				"Received SIGHUP, sending \"{}\" to the server.",
+				reload_command
+			);
+			if let Err(e) = minecraft_server.send_command(&reload_command) {
+				warn!("Failed to send \"{}\": {:?}.", reload_command, e);
+			}
+		}
+	});
+	Ok(())
+}
+
+/// Sends to `stop` whenever the runner receives SIGINT or SIGTERM, instead of the runner being
+/// killed. Only available on Unix.
+pub fn notify_termination(stop: mpsc::Sender<()>) -> Result<()> {
+	let mut signals = Signals::new(&[SIGINT, SIGTERM])?;
+	std::thread::spawn(move || {
+		for signal in signals.forever() {
+			info!("Received signal {}.", signal);
+			if stop.send(()).is_err() {
+				return;
+			}
+		}
+	});
+	Ok(())
+}
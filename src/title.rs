@@ -0,0 +1,37 @@
+use crate::ping;
+use crate::server::ServerManager;
+use std::ffi::OsStr;
+use std::net::SocketAddr;
+use std::os::windows::ffi::OsStrExt;
+use std::time::Duration;
+
+const UPDATE_INTERVAL: Duration = Duration::from_secs(10);
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Keeps the console window title at the server name and status, e.g. `survival - running (3
+/// online)`, so several servers can be told apart in the taskbar. Never returns. Only available on
+/// Windows.
+pub fn update(minecraft_server: &ServerManager, name: &str, address: SocketAddr) {
+	loop {
+		let status = if !minecraft_server.is_running() {
+			String::from("stopped")
+		} else if !minecraft_server.is_ready() {
+			String::from("starting")
+		} else {
+			match ping::online_players(address, PING_TIMEOUT) {
+				Ok(online) => format!("running ({} online)", online),
+				Err(_) => String::from("running"),
+			}
+		};
+		set_title(&format!("{} - {}", name, status));
+		std::thread::sleep(UPDATE_INTERVAL);
+	}
+}
+
+fn set_title(title: &str) {
+	let title: Vec<u16> = OsStr::new(title)
+		.encode_wide()
+		.chain(std::iter::once(0))
+		.collect();
+	unsafe { winapi::um::wincon::SetConsoleTitleW(title.as_ptr()) };
+}
@@ -0,0 +1,107 @@
+use anyhow::{Context, Result};
+use log::*;
+use std::io::BufRead;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Server running inside a detached tmux session, so admins can `tmux attach` to its console.
+pub struct TmuxSession {
+	name: String,
+}
+
+impl TmuxSession {
+	pub fn launch(
+		name: &str,
+		java: &Path,
+		args: &[String],
+		working_directory: &Path,
+	) -> Result<Self> {
+		let session = TmuxSession {
+			name: name.to_string(),
+		};
+		if session.is_running() {
+			anyhow::bail!("tmux session \"{}\" already exists", name);
+		}
+
+		let mut command = vec![java.to_string_lossy().into_owned()];
+		command.extend(args.iter().cloned());
+		let status = Command::new("tmux")
+			.args(&["new-session", "-d", "-s", name, "-c"])
+			.arg(working_directory)
+			.arg(shell_words::join(&command))
+			.status()
+			.map_err(|e| match e.kind() {
+				std::io::ErrorKind::NotFound => {
+					anyhow::anyhow!("tmux is not installed or not in PATH")
+				}
+				_ => anyhow::Error::from(e).context("Failed to launch tmux"),
+			})?;
+		if !status.success() {
+			anyhow::bail!("tmux new-session exited with {}", status);
+		}
+		Ok(session)
+	}
+
+	/// The session with the given name, if it's running.
+	pub fn existing(name: &str) -> Option<Self> {
+		let session = TmuxSession {
+			name: name.to_string(),
+		};
+		Some(session).filter(TmuxSession::is_running)
+	}
+
+	pub fn is_running(&self) -> bool {
+		Command::new("tmux")
+			.args(&["has-session", "-t", &self.name])
+			.stdout(Stdio::null())
+			.stderr(Stdio::null())
+			.status()
+			.map(|status| status.success())
+			.unwrap_or_default()
+	}
+
+	/// Types the command into the session, as if it was entered on the attached console.
+	pub fn send_command(&self, command: &str) -> Result<()> {
+		self.send_keys(&["-l", command])?;
+		self.send_keys(&["Enter"])
+	}
+
+	fn send_keys(&self, keys: &[&str]) -> Result<()> {
+		let status = Command::new("tmux")
+			.args(&["send-keys", "-t", &self.name])
+			.args(keys)
+			.status()
+			.context("Failed to run tmux send-keys")?;
+		if !status.success() {
+			anyhow::bail!("tmux send-keys exited with {}", status);
+		}
+		Ok(())
+	}
+
+	/// Blocks until the session ends, i.e. the server exits or the session is killed.
+	pub fn wait(&self) {
+		while self.is_running() {
+			std::thread::sleep(POLL_INTERVAL);
+		}
+	}
+}
+
+/// Forwards lines typed into the runner's console to the tmux session.
+pub fn forward_console(session: &TmuxSession) {
+	let stdin = std::io::stdin();
+	for line in stdin.lock().lines() {
+		let line = match line {
+			Ok(v) => v,
+			Err(e) => {
+				debug!("Console input closed: {:?}", e);
+				return;
+			}
+		};
+		if let Err(e) = session.send_command(&line) {
+			warn!("Failed to forward \"{}\" to the server: {:?}.", line, e);
+		}
+	}
+}
@@ -0,0 +1,177 @@
+use anyhow::{Context, Result};
+use log::*;
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const VERSION_MANIFEST_URL: &str =
+	"https://launchermeta.mojang.com/mc/game/version_manifest_v2.json";
+/// Only bounds the manifest request, the jar download can take as long as it needs.
+const MANIFEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize, Debug)]
+pub struct VersionManifest {
+	pub latest: LatestVersions,
+	pub versions: Vec<ManifestVersion>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LatestVersions {
+	pub release: String,
+	pub snapshot: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ManifestVersion {
+	pub id: String,
+	#[serde(rename = "type")]
+	pub version_type: String,
+	pub url: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct VersionDetails {
+	downloads: Downloads,
+}
+
+#[derive(Deserialize, Debug)]
+struct Downloads {
+	server: Option<Download>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Download {
+	sha1: String,
+	size: u64,
+	url: String,
+}
+
+pub fn fetch_version_manifest() -> Result<VersionManifest> {
+	let agent = ureq::AgentBuilder::new().timeout(MANIFEST_TIMEOUT).build();
+	let manifest = agent
+		.get(VERSION_MANIFEST_URL)
+		.call()
+		.context("Failed to fetch the version manifest")?
+		.into_string()?;
+	Ok(serde_json::from_str(&manifest)?)
+}
+
+/// Downloads the vanilla `server.jar` of the given version (latest release when `None`) into
+/// `directory`, verifying it against the SHA1 from the manifest. Nothing is left behind on
+/// failure.
+pub fn download_server_jar(version: Option<&str>, directory: &Path) -> Result<PathBuf> {
+	let manifest = fetch_version_manifest()?;
+	let version = version.unwrap_or(&manifest.latest.release);
+	let version = manifest
+		.versions
+		.iter()
+		.find(|v| v.id == version)
+		.ok_or_else(|| anyhow::anyhow!("Version \"{}\" not found in the manifest.", version))?;
+
+	let details: VersionDetails = serde_json::from_str(
+		&ureq::get(&version.url)
+			.call()
+			.with_context(|| format!("Failed to fetch details of version {}", version.id))?
+			.into_string()?,
+	)?;
+	let download = details
+		.downloads
+		.server
+		.ok_or_else(|| anyhow::anyhow!("Version {} has no server jar.", version.id))?;
+
+	info!("Downloading the {} server jar.", version.id);
+	let jar = directory.join("server.jar");
+	let partial_jar = directory.join("server.jar.part");
+	if let Err(e) = download_verified(&download, &partial_jar) {
+		let _ = std::fs::remove_file(&partial_jar);
+		return Err(e);
+	}
+	std::fs::rename(&partial_jar, &jar)?;
+	info!("Downloaded {}.", jar.display());
+	Ok(jar)
+}
+
+fn download_verified(download: &Download, destination: &Path) -> Result<()> {
+	let mut reader = ureq::get(&download.url)
+		.call()
+		.context("Failed to download the server jar")?
+		.into_reader();
+	let mut file =
+		std::fs::File::create(destination).with_context(|| format!("Path: {:?}", destination))?;
+
+	let mut hasher = Sha1::new();
+	let mut buf = vec![0; 64 * 1024];
+	let mut downloaded = 0u64;
+	let mut last_reported_percent = 0;
+	loop {
+		let read = reader.read(&mut buf)?;
+		if read == 0 {
+			break;
+		}
+		hasher.update(&buf[..read]);
+		file.write_all(&buf[..read])?;
+		downloaded += read as u64;
+
+		let percent = (downloaded * 100).checked_div(download.size).unwrap_or(100);
+		if percent >= last_reported_percent + 10 {
+			last_reported_percent = percent - percent % 10;
+			info!(
+				"Downloaded {}% ({}/{} bytes).",
+				percent, downloaded, download.size
+			);
+		}
+	}
+	file.flush()?;
+
+	let sha1 = format!("{:x}", hasher.finalize());
+	if !sha1.eq_ignore_ascii_case(&download.sha1) {
+		anyhow::bail!(
+			"SHA1 mismatch of the downloaded jar: expected {}, got {}.",
+			download.sha1,
+			sha1
+		);
+	}
+	Ok(())
+}
+
+/// Whether the version is a snapshot, pre-release or release candidate rather than a release.
+/// Uses the `type` from the version manifest, falling back to the shape of the id when the
+/// manifest can't be fetched or doesn't list the version.
+pub fn is_snapshot(version: &str) -> bool {
+	match fetch_version_manifest() {
+		Ok(manifest) => {
+			if let Some(listed) = manifest.versions.iter().find(|v| v.id == version) {
+				return listed.version_type != "release";
+			}
+		}
+		Err(e) => debug!("Checking the version id only: {:?}", e),
+	}
+	looks_like_snapshot(version)
+}
+
+/// Snapshots are named like `23w45a`, pre-releases and release candidates like `1.20-pre1` and
+/// `1.20-rc1` (`1.14 Pre-Release 1` in older versions).
+fn looks_like_snapshot(version: &str) -> bool {
+	let pattern = regex::Regex::new(
+		r"^\d{2}w\d{2}[a-z~]$|-(pre|rc)\d+$| (Pre-Release|Release Candidate) \d+$",
+	)
+	.unwrap();
+	pattern.is_match(version)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn snapshot_ids() {
+		assert!(looks_like_snapshot("23w45a"));
+		assert!(looks_like_snapshot("1.20-pre1"));
+		assert!(looks_like_snapshot("1.18-rc3"));
+		assert!(looks_like_snapshot("1.14 Pre-Release 2"));
+		assert!(!looks_like_snapshot("1.17.1"));
+		assert!(!looks_like_snapshot("1.20"));
+	}
+}
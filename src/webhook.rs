@@ -0,0 +1,94 @@
+use crate::events::PlayerEvent;
+use anyhow::Result;
+use serde::Serialize;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Payload POSTed to the webhook. `content` and `text` carry the same human readable message, so
+/// the payload is accepted by both Discord and Slack.
+#[derive(Serialize, Debug)]
+pub struct Notification {
+	pub content: String,
+	pub text: String,
+	pub server_name: String,
+	pub exit_code: Option<i32>,
+	pub exit_status: String,
+	pub timestamp: String,
+	pub last_lines: Vec<String>,
+	/// Description of the crash report written during the session, if any.
+	pub crash_report: Option<String>,
+}
+
+impl Notification {
+	pub fn crash(
+		server_name: &str,
+		exit_code: Option<i32>,
+		exit_status: String,
+		last_lines: Vec<String>,
+		crash_report: Option<String>,
+	) -> Self {
+		let mut message = format!(
+			"Minecraft server \"{}\" crashed ({}).",
+			server_name, exit_status
+		);
+		if let Some(crash_report) = &crash_report {
+			message.push_str("\nCrash report: ");
+			message.push_str(crash_report);
+		}
+		if !last_lines.is_empty() {
+			message.push_str("\n```\n");
+			message.push_str(&last_lines.join("\n"));
+			message.push_str("\n```");
+		}
+		Notification {
+			content: message.clone(),
+			text: message,
+			server_name: server_name.to_string(),
+			exit_code,
+			exit_status,
+			timestamp: chrono::Utc::now().to_rfc3339(),
+			last_lines,
+			crash_report,
+		}
+	}
+}
+
+/// Payload POSTed to the webhook when a player joins or leaves, Discord and Slack compatible like
+/// [`Notification`].
+#[derive(Serialize, Debug)]
+pub struct PlayerNotification {
+	pub content: String,
+	pub text: String,
+	pub server_name: String,
+	#[serde(flatten)]
+	pub event: PlayerEvent,
+	pub timestamp: String,
+}
+
+impl PlayerNotification {
+	pub fn new(server_name: &str, event: PlayerEvent) -> Self {
+		let message = match &event {
+			PlayerEvent::Joined(player) => format!("{} joined \"{}\".", player, server_name),
+			PlayerEvent::Left(player) => format!("{} left \"{}\".", player, server_name),
+		};
+		PlayerNotification {
+			content: message.clone(),
+			text: message,
+			server_name: server_name.to_string(),
+			event,
+			timestamp: chrono::Utc::now().to_rfc3339(),
+		}
+	}
+}
+
+/// Sends the notification with a short timeout, so an unreachable webhook can't hold up shutdown.
+pub fn send(url: &str, notification: &impl Serialize) -> Result<()> {
+	let body = serde_json::to_string(notification)?;
+	let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+	agent
+		.post(url)
+		.set("Content-Type", "application/json")
+		.send_string(&body)?;
+	Ok(())
+}
@@ -1,31 +1,219 @@
+use crate::events::OnlinePlayers;
+use crate::find_jar;
+use crate::metrics::Metrics;
+use crate::rate_limit::{RateLimit, RateLimiter};
+use crate::server::{RestartReason, ServerManager};
+use actix_web::dev::Service;
+use actix_web::http::header;
 use actix_web::rt::System;
-use actix_web::{get, App, HttpServer, Responder};
-use anyhow::Result;
+use actix_web::{get, post, web, App, HttpRequest, HttpResponse, HttpServer, Responder};
+use anyhow::{Context, Result};
 use log::*;
+use serde::{Deserialize, Serialize};
 use std::net::ToSocketAddrs;
-use std::process::ChildStdin;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, SystemTime};
 
-pub fn start_web_server<Addr>(minecraft_server_stdin: ChildStdin, address: Addr)
-where
+/// State shared between the runner and the request handlers.
+///
+/// The webserver is started before the jar is known, so the server manager is only set once the
+/// server is launched.
+#[derive(Default)]
+pub struct WebState {
+	minecraft_server: Mutex<Option<Arc<ServerManager>>>,
+	jar_selection: Mutex<Option<JarSelection>>,
+	port: Mutex<Option<u16>>,
+	name: Mutex<String>,
+	/// Set when the players are tracked from the join and leave lines
+	players: Mutex<Option<Arc<OnlinePlayers>>>,
+	/// The stop or restart scheduled with a grace period, and when it happens
+	scheduled: Mutex<Option<(&'static str, SystemTime)>>,
+	pub metrics: Metrics,
+	rate_limiter: Option<RateLimiter>,
+}
+
+struct JarSelection {
+	jars: Vec<PathBuf>,
+	sender: mpsc::Sender<PathBuf>,
+}
+
+impl WebState {
+	/// Throttles the endpoints that control the server, in a middleware checking the limit before
+	/// any of them runs. Read-only endpoints are never throttled.
+	pub fn with_rate_limit(mut self, rate_limit: RateLimit) -> Self {
+		self.rate_limiter = Some(RateLimiter::new(rate_limit));
+		self
+	}
+
+	/// Returns the 429 response when the client exceeded the rate limit.
+	fn throttle(&self, req: &HttpRequest) -> Option<HttpResponse> {
+		let rate_limiter = self.rate_limiter.as_ref()?;
+		let ip = req.peer_addr()?.ip();
+		let retry_after = rate_limiter.check(ip).err()?;
+		warn!("Rate limit exceeded by {}.", ip);
+		Some(
+			HttpResponse::TooManyRequests()
+				.insert_header((
+					header::RETRY_AFTER,
+					retry_after.as_secs_f64().ceil().to_string(),
+				))
+				.finish(),
+		)
+	}
+
+	pub fn set_minecraft_server(&self, minecraft_server: Arc<ServerManager>) {
+		*self.minecraft_server.lock().unwrap() = Some(minecraft_server);
+	}
+
+	pub fn set_name(&self, name: String) {
+		*self.name.lock().unwrap() = name;
+	}
+
+	pub fn set_port(&self, port: u16) {
+		*self.port.lock().unwrap() = Some(port);
+	}
+
+	pub fn set_players(&self, players: Arc<OnlinePlayers>) {
+		*self.players.lock().unwrap() = Some(players);
+	}
+
+	fn players(&self) -> Option<Arc<OnlinePlayers>> {
+		self.players.lock().unwrap().clone()
+	}
+
+	fn minecraft_server(&self) -> Option<Arc<ServerManager>> {
+		self.minecraft_server.lock().unwrap().clone()
+	}
+
+	/// Offers the jars at `GET /jars` and blocks until one is chosen with `POST /jars/select`.
+	pub fn select_jar(&self, jars: &[PathBuf], timeout: Duration) -> Result<PathBuf> {
+		let (sender, receiver) = mpsc::channel();
+		*self.jar_selection.lock().unwrap() = Some(JarSelection {
+			jars: jars.to_vec(),
+			sender,
+		});
+
+		info!(
+			"Multiple jars found. Waiting up to {}s for one to be chosen with POST /jars/select.",
+			timeout.as_secs()
+		);
+		let chosen_jar = receiver.recv_timeout(timeout);
+		*self.jar_selection.lock().unwrap() = None;
+
+		chosen_jar
+			.map_err(|_| anyhow::anyhow!("No jar was selected within {}s.", timeout.as_secs()))
+	}
+}
+
+/// Loads the PEM encoded certificate chain and private key the webserver is served with.
+pub fn load_tls_config(cert: &Path, key: &Path) -> Result<rustls::ServerConfig> {
+	use rustls::internal::pemfile;
+	use std::fs::File;
+	use std::io::BufReader;
+
+	let certs = pemfile::certs(&mut BufReader::new(
+		File::open(cert).with_context(|| format!("Path: {:?}", cert))?,
+	))
+	.map_err(|_| anyhow::anyhow!("Invalid TLS certificate {:?}", cert))?;
+	if certs.is_empty() {
+		anyhow::bail!("No certificates found in {:?}", cert);
+	}
+
+	let read_keys =
+		|parse: fn(&mut dyn std::io::BufRead) -> Result<Vec<rustls::PrivateKey>, ()>| {
+			File::open(key)
+				.with_context(|| format!("Path: {:?}", key))
+				.and_then(|file| {
+					parse(&mut BufReader::new(file))
+						.map_err(|_| anyhow::anyhow!("Invalid TLS key {:?}", key))
+				})
+		};
+	let mut keys = read_keys(pemfile::pkcs8_private_keys)?;
+	if keys.is_empty() {
+		keys = read_keys(pemfile::rsa_private_keys)?;
+	}
+	let key = match keys.into_iter().next() {
+		Some(v) => v,
+		None => anyhow::bail!("No private key found in {:?}", key),
+	};
+
+	let mut config = rustls::ServerConfig::new(rustls::NoClientAuth::new());
+	config
+		.set_single_cert(certs, key)
+		.context("The TLS certificate and key don't match")?;
+	Ok(config)
+}
+
+pub fn start_web_server<Addr>(
+	state: Arc<WebState>,
+	address: Addr,
+	tls_config: Option<rustls::ServerConfig>,
+) where
 	Addr: ToSocketAddrs + Send + 'static,
 {
 	std::thread::spawn(move || {
 		System::new().block_on(async move {
-			if let Err(e) = start_actix_server(minecraft_server_stdin, address).await {
+			if let Err(e) = start_actix_server(state, address, tls_config).await {
 				error!("Webserver exited with {:?}", e);
 			}
 		})
 	});
 }
 
-async fn start_actix_server<Addr>(_minecraft_server_stdin: ChildStdin, address: Addr) -> Result<()>
+/// The endpoints that control the server, throttled with `--web-rate-limit`. Only these paths are
+/// checked, so requests to unknown paths don't use up a client's limit.
+const CONTROL_PATHS: &[&str] = &["/stop", "/restart", "/jars/select"];
+
+async fn start_actix_server<Addr>(
+	state: Arc<WebState>,
+	address: Addr,
+	tls_config: Option<rustls::ServerConfig>,
+) -> Result<()>
 where
 	Addr: ToSocketAddrs + Send + 'static,
 {
-	HttpServer::new(|| App::new().service(index))
-		.bind(address)?
-		.run()
-		.await?;
+	let state = web::Data::from(state);
+	let server = HttpServer::new(move || {
+		let throttle_state = state.clone();
+		App::new()
+			.app_data(state.clone())
+			.wrap_fn(move |req, srv| {
+				let throttled = if CONTROL_PATHS.contains(&req.path()) {
+					throttle_state.throttle(req.request())
+				} else {
+					None
+				};
+				let response = match throttled {
+					Some(response) => Err(req.into_response(response)),
+					None => Ok(srv.call(req)),
+				};
+				async move {
+					match response {
+						Ok(response) => response.await,
+						Err(throttled) => Ok(throttled),
+					}
+				}
+			})
+			.service(index)
+			.service(status)
+			.service(restart)
+			.service(stop)
+			.service(metrics)
+			.service(jars)
+			.service(select_jar)
+	});
+	let server = match tls_config {
+		Some(tls_config) => {
+			info!("Webserver uses TLS.");
+			server.bind_rustls(address, tls_config)?
+		}
+		None => {
+			info!("Webserver uses plain HTTP.");
+			server.bind(address)?
+		}
+	};
+	server.run().await?;
 
 	Ok(())
 }
@@ -34,3 +222,307 @@ where
 async fn index() -> impl Responder {
 	"Hello, World"
 }
+
+#[derive(Serialize)]
+struct Status {
+	name: String,
+	running: bool,
+	ready: bool,
+	pid: Option<u32>,
+	port: Option<u16>,
+	restart_count: u64,
+	last_exit_code: Option<i32>,
+	/// RFC 3339
+	last_restart_time: Option<String>,
+	last_restart_reason: Option<RestartReason>,
+	/// Online players, when they're tracked
+	players: Option<Vec<String>>,
+}
+
+#[get("/status")]
+async fn status(state: web::Data<WebState>) -> HttpResponse {
+	let minecraft_server = state.minecraft_server();
+	let last_restart = minecraft_server
+		.as_ref()
+		.map(|server| server.last_restart())
+		.unwrap_or_default();
+	HttpResponse::Ok().json(Status {
+		name: state.name.lock().unwrap().clone(),
+		running: minecraft_server
+			.as_ref()
+			.map(|server| server.is_running())
+			.unwrap_or_default(),
+		ready: minecraft_server
+			.as_ref()
+			.map(|server| server.is_ready())
+			.unwrap_or_default(),
+		restart_count: minecraft_server
+			.as_ref()
+			.map(|server| server.restart_count())
+			.unwrap_or_default(),
+		pid: minecraft_server.and_then(|server| server.pid()),
+		port: *state.port.lock().unwrap(),
+		last_exit_code: last_restart.exit_code,
+		last_restart_time: last_restart
+			.time
+			.map(|time| chrono::DateTime::<chrono::Utc>::from(time).to_rfc3339()),
+		last_restart_reason: last_restart.reason,
+		players: state.players().map(|players| players.list()),
+	})
+}
+
+#[get("/metrics")]
+async fn metrics(state: web::Data<WebState>) -> HttpResponse {
+	if let Some(minecraft_server) = state.minecraft_server() {
+		let metrics = &state.metrics;
+		metrics.set(
+			"minecraft_uptime_seconds",
+			"Seconds since the server process was launched",
+			minecraft_server
+				.uptime()
+				.map(|uptime| uptime.as_secs_f64())
+				.unwrap_or_default(),
+		);
+		metrics.set(
+			"minecraft_restarts_total",
+			"Restarts since the runner was launched",
+			minecraft_server.restart_count() as f64,
+		);
+		let last_restart = minecraft_server.last_restart();
+		if let Some(exit_code) = last_restart.exit_code {
+			metrics.set(
+				"minecraft_last_exit_code",
+				"Exit code of the process replaced by the latest restart",
+				exit_code as f64,
+			);
+		}
+		if let Some(time) = last_restart.time {
+			metrics.set(
+				"minecraft_last_restart_timestamp_seconds",
+				"Unix time of the latest restart",
+				time.duration_since(std::time::UNIX_EPOCH)
+					.map(|since_epoch| since_epoch.as_secs_f64())
+					.unwrap_or_default(),
+			);
+		}
+		metrics.set(
+			"minecraft_ready",
+			"Whether the server finished loading",
+			if minecraft_server.is_ready() {
+				1.0
+			} else {
+				0.0
+			},
+		);
+		if let Some(players) = state.players() {
+			metrics.set(
+				"minecraft_players_online",
+				"Players online according to the join and leave lines",
+				players.count() as f64,
+			);
+		}
+	}
+	HttpResponse::Ok()
+		.content_type("text/plain; version=0.0.4")
+		.body(state.metrics.render())
+}
+
+/// `?grace=SECS` of `/stop` and `/restart`: the players are counted down to the action with
+/// `say` messages for that long.
+#[derive(Deserialize)]
+struct Grace {
+	grace: Option<u64>,
+}
+
+/// `?reason=` of `/restart`, `manual` by default, e.g. `scheduled` for a restart requested by a
+/// cron job.
+#[derive(Deserialize)]
+struct Reason {
+	reason: Option<RestartReason>,
+}
+
+#[derive(Serialize)]
+struct Scheduled {
+	action: &'static str,
+	/// RFC 3339
+	at: String,
+}
+
+impl Scheduled {
+	fn new(action: &'static str, at: SystemTime) -> Self {
+		Scheduled {
+			action,
+			at: chrono::DateTime::<chrono::Utc>::from(at).to_rfc3339(),
+		}
+	}
+}
+
+/// Runs `action` on a thread after counting down `grace`. Responds with 202 and the time it's
+/// scheduled at, or 409 when another one is already scheduled.
+fn schedule(
+	state: web::Data<WebState>,
+	minecraft_server: Arc<ServerManager>,
+	grace: Duration,
+	name: &'static str,
+	action: impl FnOnce(&ServerManager) -> Result<()> + Send + 'static,
+) -> HttpResponse {
+	let mut scheduled = state.scheduled.lock().unwrap();
+	if let Some((scheduled_name, at)) = *scheduled {
+		return HttpResponse::Conflict().json(Scheduled::new(scheduled_name, at));
+	}
+	let at = SystemTime::now() + grace;
+	*scheduled = Some((name, at));
+	drop(scheduled);
+
+	info!(
+		"Scheduled a {} in {}s over the webserver.",
+		name,
+		grace.as_secs()
+	);
+	let countdown = match name {
+		"restart" => "restarting",
+		_ => "stopping",
+	};
+	let state = state.into_inner();
+	std::thread::spawn(move || {
+		minecraft_server.announce_countdown(grace, countdown);
+		if let Err(e) = action(&minecraft_server) {
+			error!("Failed the scheduled {}: {:?}", name, e);
+		}
+		*state.scheduled.lock().unwrap() = None;
+	});
+	HttpResponse::Accepted().json(Scheduled::new(name, at))
+}
+
+/// Gracefully stops the server, after counting the players down with `?grace=SECS`. The runner
+/// exits once the server stopped.
+#[post("/stop")]
+async fn stop(state: web::Data<WebState>, grace: web::Query<Grace>) -> HttpResponse {
+	let minecraft_server = match state.minecraft_server() {
+		Some(v) => v,
+		None => return HttpResponse::ServiceUnavailable().body("Server is not launched yet"),
+	};
+	if let Some(grace) = grace.grace.filter(|grace| *grace > 0) {
+		return schedule(
+			state,
+			minecraft_server,
+			Duration::from_secs(grace),
+			"stop",
+			|server| server.stop().map(|_| ()),
+		);
+	}
+	let stopped_at = SystemTime::now();
+	match web::block(move || minecraft_server.stop()).await {
+		Ok(Ok(_)) => HttpResponse::Ok().json(Scheduled::new("stop", stopped_at)),
+		Ok(Err(e)) => {
+			error!("Failed to stop the server: {:?}", e);
+			HttpResponse::InternalServerError().body(format!("{:?}", e))
+		}
+		Err(e) => {
+			error!("Failed to stop the server: {:?}", e);
+			HttpResponse::InternalServerError().finish()
+		}
+	}
+}
+
+/// Gracefully stops the server and launches it again. Responds with the PID of the new process,
+/// or with the time of the restart when it's delayed with `?grace=SECS`.
+#[post("/restart")]
+async fn restart(
+	state: web::Data<WebState>,
+	grace: web::Query<Grace>,
+	reason: web::Query<Reason>,
+) -> HttpResponse {
+	let minecraft_server = match state.minecraft_server() {
+		Some(v) => v,
+		None => return HttpResponse::ServiceUnavailable().body("Server is not launched yet"),
+	};
+	if minecraft_server.is_single_run() {
+		return HttpResponse::Conflict().body("Restarts are disabled with --once");
+	}
+	let reason = reason.reason.unwrap_or(RestartReason::Manual);
+	if let Some(grace) = grace.grace.filter(|grace| *grace > 0) {
+		return schedule(
+			state,
+			minecraft_server,
+			Duration::from_secs(grace),
+			"restart",
+			move |server| server.restart(reason).map(|_| ()),
+		);
+	}
+	match web::block(move || minecraft_server.restart(reason)).await {
+		Ok(Ok(pid)) => HttpResponse::Ok().body(pid.to_string()),
+		Ok(Err(e)) => {
+			error!("Failed to restart the server: {:?}", e);
+			HttpResponse::InternalServerError().body(format!("{:?}", e))
+		}
+		Err(e) => {
+			error!("Failed to restart the server: {:?}", e);
+			HttpResponse::InternalServerError().finish()
+		}
+	}
+}
+
+/// Lists the jars to choose from when the runner is waiting for a selection, in the same order as
+/// the console prompt.
+#[get("/jars")]
+async fn jars(state: web::Data<WebState>) -> HttpResponse {
+	match &*state.jar_selection.lock().unwrap() {
+		Some(selection) => {
+			let server_jar_idx = find_jar::server_jar_idx(&selection.jars);
+			let jars: Vec<String> = (0..selection.jars.len())
+				.filter_map(|idx| {
+					find_jar::get_jar_from_jars(&selection.jars, idx, server_jar_idx).ok()
+				})
+				.filter_map(|jar| jar.file_name().map(ToOwned::to_owned))
+				.map(|name| name.to_string_lossy().into_owned())
+				.collect();
+			HttpResponse::Ok().json(jars)
+		}
+		None => HttpResponse::NotFound().body("No jar selection is pending"),
+	}
+}
+
+/// Either the file name of the jar, or its number (starting at 1) in the `GET /jars` list.
+#[derive(Deserialize)]
+struct JarChoice {
+	jar: Option<String>,
+	index: Option<usize>,
+}
+
+#[post("/jars/select")]
+async fn select_jar(state: web::Data<WebState>, choice: web::Json<JarChoice>) -> HttpResponse {
+	let selection = state.jar_selection.lock().unwrap();
+	let selection = match &*selection {
+		Some(v) => v,
+		None => return HttpResponse::NotFound().body("No jar selection is pending"),
+	};
+
+	let jar = match (&choice.jar, choice.index) {
+		(Some(name), _) => selection
+			.jars
+			.iter()
+			.find(|jar| jar.file_name().map(|file_name| file_name == name.as_str()) == Some(true))
+			.cloned()
+			.ok_or_else(|| anyhow::anyhow!("Unknown jar \"{}\"", name)),
+		(None, Some(index)) => index
+			.checked_sub(1)
+			.ok_or_else(|| anyhow::anyhow!("Jar numbers start at 1"))
+			.and_then(|idx| {
+				Ok(find_jar::get_jar_from_jars(
+					&selection.jars,
+					idx,
+					find_jar::server_jar_idx(&selection.jars),
+				)?)
+			}),
+		(None, None) => Err(anyhow::anyhow!("Either \"jar\" or \"index\" is required")),
+	};
+	match jar {
+		Ok(jar) => {
+			info!("\"{}\" was selected over the webserver.", jar.display());
+			let _ = selection.sender.send(jar);
+			HttpResponse::Ok().finish()
+		}
+		Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+	}
+}
@@ -0,0 +1,131 @@
+use crate::properties;
+use serde::Serialize;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const DIMENSION_SUFFIXES: &[&str] = &["_nether", "_the_end"];
+
+/// Total size of the files in `path`, recursively.
+pub fn dir_size(path: &Path) -> io::Result<u64> {
+	let mut size = 0;
+	for entry in walkdir::WalkDir::new(path) {
+		let entry = entry?;
+		if entry.file_type().is_file() {
+			size += entry.metadata()?.len();
+		}
+	}
+	Ok(size)
+}
+
+/// Name of the world directory, `level-name` in server.properties.
+pub fn level_name(working_directory: &Path) -> String {
+	properties::get_property(working_directory, "level-name")
+		.ok()
+		.flatten()
+		.filter(|name| !name.is_empty())
+		.unwrap_or_else(|| String::from("world"))
+}
+
+/// Directories of the overworld, the nether and the end. The nether and the end are stored next to
+/// the overworld by Spigot-based servers.
+pub fn dimension_dirs(working_directory: &Path) -> Vec<PathBuf> {
+	let level_name = level_name(working_directory);
+	std::iter::once(&"")
+		.chain(DIMENSION_SUFFIXES)
+		.map(|suffix| working_directory.join(format!("{}{}", level_name, suffix)))
+		.collect()
+}
+
+/// A world in the server directory, as printed by `list-worlds`.
+#[derive(Serialize, Debug)]
+pub struct WorldInfo {
+	pub name: String,
+	/// Bytes, the nether and the end stored next to the world included
+	pub size: u64,
+	/// RFC 3339, when level.dat was last saved
+	pub last_modified: Option<String>,
+	/// `level-name` of server.properties, launched by the server
+	pub active: bool,
+}
+
+/// Directories of `working_directory` holding a `level.dat`, sorted by name. The nether and the
+/// end stored next to a world by Spigot-based servers count towards that world.
+pub fn list_worlds(working_directory: &Path) -> io::Result<Vec<WorldInfo>> {
+	let mut names: Vec<String> = std::fs::read_dir(working_directory)?
+		.filter_map(|entry| entry.ok())
+		.filter(|entry| entry.path().join("level.dat").is_file())
+		.filter_map(|entry| entry.file_name().into_string().ok())
+		.collect();
+	names.sort_unstable();
+
+	let active = level_name(working_directory);
+	let mut worlds: Vec<WorldInfo> = Vec::new();
+	for name in &names {
+		let overworld = DIMENSION_SUFFIXES
+			.iter()
+			.filter_map(|suffix| name.strip_suffix(suffix))
+			.find(|overworld| names.iter().any(|name| name == overworld));
+		let size = dir_size(&working_directory.join(name))?;
+		if let Some(overworld) = overworld {
+			if let Some(world) = worlds.iter_mut().find(|world| world.name == overworld) {
+				world.size += size;
+				continue;
+			}
+		}
+		let last_modified = std::fs::metadata(working_directory.join(name).join("level.dat"))
+			.and_then(|metadata| metadata.modified())
+			.ok()
+			.map(|time| chrono::DateTime::<chrono::Local>::from(time).to_rfc3339());
+		worlds.push(WorldInfo {
+			name: name.clone(),
+			size,
+			last_modified,
+			active: *name == active,
+		});
+	}
+	Ok(worlds)
+}
+
+/// Combined size of all the dimension directories. Missing ones are skipped.
+pub fn world_size(working_directory: &Path) -> io::Result<u64> {
+	let mut size = 0;
+	for dir in dimension_dirs(working_directory) {
+		if dir.is_dir() {
+			size += dir_size(&dir)?;
+		}
+	}
+	Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn worlds_are_listed_with_their_dimensions() {
+		let root = std::env::temp_dir().join(format!(
+			"minecraft_runner_list_worlds_{}",
+			std::process::id()
+		));
+		for (dir, level_dat) in &[
+			("world", "1234"),
+			("world_nether", "12"),
+			("creative", "1"),
+			("plugins", ""),
+		] {
+			std::fs::create_dir_all(root.join(dir)).unwrap();
+			if !level_dat.is_empty() {
+				std::fs::write(root.join(dir).join("level.dat"), level_dat).unwrap();
+			}
+		}
+		std::fs::write(root.join("server.properties"), "level-name=creative\n").unwrap();
+
+		let worlds = list_worlds(&root).unwrap();
+		std::fs::remove_dir_all(&root).unwrap();
+		let worlds: Vec<(&str, u64, bool)> = worlds
+			.iter()
+			.map(|world| (world.name.as_str(), world.size, world.active))
+			.collect();
+		assert_eq!(worlds, vec![("creative", 1, true), ("world", 6, false)]);
+	}
+}